@@ -0,0 +1,506 @@
+use crate::{Cli, Command};
+use chrono::TimeDelta;
+use furrctorio_core::{
+  constants::BUILTIN_MODS,
+  credentials::{default_credentials_path, default_player_data_paths},
+  error::Error as CoreError,
+  prelude::{
+    list_saves, Context, ContextBuilder, CredentialChain, DownloadEvent, Downloader, FModShort,
+    LoginError, MetadataCache, ModQuery, ReleasePolicy, RetryPolicy, SaveFile, SortField, SortOrder,
+  },
+};
+use futures::StreamExt;
+use url::Url;
+use furrctorio_yaml::prelude::{
+  Change, ConfigError, ConfigModEntry, FurrConfig, LockFile, Plan, SaveSync,
+};
+use std::{
+  error::Error,
+  path::{Path, PathBuf},
+};
+
+type CliResult = Result<(), Box<dyn Error>>;
+
+/// How the portal is reached and mods are downloaded, from the global arguments.
+struct Options {
+  jobs: usize,
+  limit_rate: Option<u64>,
+  portal_url: Option<Url>,
+  retries: u32,
+  offline: bool,
+  player_data: Option<PathBuf>,
+}
+
+impl Options {
+  fn context_builder(&self) -> Result<ContextBuilder, CoreError> {
+    let mut builder = Context::builder()
+      .retry_policy(RetryPolicy {
+        max_retries: self.retries,
+        ..Default::default()
+      })
+      .offline(self.offline);
+    if let Some(folder) = MetadataCache::default_folder() {
+      builder = builder.cache(MetadataCache::new(folder));
+    }
+    match &self.portal_url {
+      Some(url) => builder.portal_url(url.clone()),
+      None => Ok(builder),
+    }
+  }
+
+  /// Creates a Context with the first credentials found, see [`CredentialChain::standard`].
+  ///
+  /// The `player-data.json` given on the command line is tried right after the environment and
+  /// the credentials file.
+  fn authenticated_context(&self) -> Result<Context, Box<dyn Error>> {
+    let mut chain = CredentialChain::new().environment();
+    if let Some(path) = default_credentials_path() {
+      chain = chain.file(path);
+    }
+    if let Some(player_data) = &self.player_data {
+      chain = chain.player_data(player_data);
+    }
+    for path in default_player_data_paths() {
+      chain = chain.player_data(path);
+    }
+    let chain = chain.keyring();
+    Ok(self.context_builder()?.credentials_from(&chain)?.build()?)
+  }
+}
+
+/// Runs the command given on the command line.
+pub async fn run(cli: Cli) -> CliResult {
+  let path = cli.config.as_path();
+  let options = Options {
+    jobs: cli.jobs,
+    limit_rate: cli.limit_rate,
+    portal_url: cli.portal_url,
+    retries: cli.retries,
+    offline: cli.offline,
+    player_data: cli.player_data,
+  };
+  let ctx = options.context_builder()?.build()?;
+
+  match cli.command {
+    Command::Init {
+      factorio_version,
+      mod_folder,
+      space_age,
+      force,
+      from_save,
+    } => init(path, factorio_version, mod_folder, space_age, force, from_save).await,
+    Command::Add {
+      name,
+      version,
+      disabled,
+    } => {
+      let mut cfg = FurrConfig::load(path).await?;
+      // Makes sure the mod exists before writing it to the configuration, the built-in mods
+      // are not on the portal.
+      let name = match BUILTIN_MODS.contains(&name.as_str()) {
+        true => name,
+        false => ctx.get_mod_info(&name).await?.name,
+      };
+      cfg.add_mod(ConfigModEntry::new(name.clone(), version, !disabled))?;
+      cfg.save(path).await?;
+      println!("Added '{}'", name);
+      Ok(())
+    }
+    Command::Remove { name } => {
+      let mut cfg = FurrConfig::load(path).await?;
+      cfg.remove_mod(&name)?;
+      cfg.save(path).await?;
+      println!("Removed '{}'", name);
+      Ok(())
+    }
+    Command::Login {
+      username,
+      email_code,
+    } => login(&options, username, email_code).await,
+    Command::Enable { name } => set_enabled(path, &name, true).await,
+    Command::Disable { name } => set_enabled(path, &name, false).await,
+    Command::Install => install(path, None, &options).await,
+    Command::Update { names } => install(path, Some(names), &options).await,
+    Command::Plan => plan(path, &ctx).await,
+    Command::Apply { dry_run } => apply(path, dry_run, &ctx, &options).await,
+    Command::Info { name } => info(&ctx, &name).await,
+    Command::Search {
+      sort,
+      order,
+      page,
+      page_size,
+      factorio_version,
+      hide_deprecated,
+      all,
+    } => {
+      let mut query = ModQuery::new().page(page).hide_deprecated(hide_deprecated);
+      if let Some(sort) = sort {
+        query = query.sort(sort.parse::<SortField>()?);
+      }
+      if let Some(order) = order {
+        query = query.sort_order(order.parse::<SortOrder>()?);
+      }
+      if let Some(page_size) = page_size {
+        query = query.page_size(page_size);
+      }
+      if let Some(factorio_version) = factorio_version {
+        query = query.factorio_version(factorio_version);
+      }
+      if all {
+        search_all(&ctx, &query, options.jobs).await
+      } else {
+        search(&ctx, &query).await
+      }
+    }
+    Command::List => list(path).await,
+    Command::Outdated {
+      exclude_zero_major,
+      cooldown,
+    } => {
+      let policy = ReleasePolicy {
+        exclude_zero_major,
+        cooldown: cooldown.map(|days| TimeDelta::days(days.into())),
+      };
+      outdated(path, &ctx, &policy).await
+    }
+    Command::Saves { folder } => saves(path, folder).await,
+    Command::Sync { save, dry_run } => sync(path, &save, dry_run).await,
+  }
+}
+
+async fn init(
+  path: &Path,
+  factorio_version: Option<semver::Version>,
+  mod_folder: Option<PathBuf>,
+  space_age: bool,
+  force: bool,
+  from_save: Option<PathBuf>,
+) -> CliResult {
+  if path.exists() && !force {
+    return Err(format!("'{}' already exists, use --force to overwrite it", path.display()).into());
+  }
+
+  let mut cfg = match from_save {
+    Some(save) => FurrConfig::from_save(&SaveFile::load(&save).await?.header)?,
+    None => FurrConfig::default(),
+  };
+  let metadata = cfg.metadata_mut();
+  if factorio_version.is_some() {
+    metadata.factorio_version = factorio_version;
+  }
+  if mod_folder.is_some() {
+    metadata.factorio_mod_folder = mod_folder;
+  }
+  metadata.space_age |= space_age;
+
+  cfg.save(path).await?;
+  println!("Created '{}'", path.display());
+  Ok(())
+}
+
+/// Logs in with the password read from the first line of the standard input.
+///
+/// When the account uses email authentication and no code was given, the code is read from
+/// the next line.
+async fn login(options: &Options, username: String, email_code: Option<String>) -> CliResult {
+  let password = read_line()?;
+  if password.is_empty() {
+    return Err("the password must be given on the standard input".into());
+  }
+
+  let login = |email_code| async {
+    options
+      .context_builder()?
+      .login(username.clone(), password.clone(), email_code)
+      .await
+  };
+  let ctx = match login(email_code.clone()).await {
+    Err(CoreError::LoginError(LoginError::EmailCodeRequired)) if email_code.is_none() => {
+      eprint!("Enter the code sent by email: ");
+      login(Some(read_line()?)).await?
+    }
+    result => result?,
+  };
+  let credentials = ctx.credentials();
+  match credentials.save_to_keyring() {
+    Ok(()) => println!("Logged in as '{}', the token is saved in the keyring", ctx.username),
+    Err(e) => {
+      let path = default_credentials_path().ok_or(e)?;
+      credentials.save_to_file(&path)?;
+      println!("Logged in as '{}', the token is saved in '{}'", ctx.username, path.display());
+    }
+  }
+  Ok(())
+}
+
+/// Reads a line of the standard input, without its line break.
+fn read_line() -> std::io::Result<String> {
+  let mut line = String::new();
+  std::io::stdin().read_line(&mut line)?;
+  Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+async fn set_enabled(path: &Path, name: &str, enabled: bool) -> CliResult {
+  let mut cfg = FurrConfig::load(path).await?;
+  cfg.set_enabled(name, enabled)?;
+  cfg.save(path).await?;
+  println!(
+    "{} '{}'",
+    if enabled { "Enabled" } else { "Disabled" },
+    name
+  );
+  Ok(())
+}
+
+async fn info(ctx: &Context, name: &str) -> CliResult {
+  let fmod = ctx.get_mod_info(name).await?;
+
+  println!("{} ({})", fmod.title, fmod.name);
+  println!("  owner:     {}", fmod.owner);
+  println!("  category:  {}", fmod.category);
+  println!("  downloads: {}", fmod.downloads_count);
+  if let Some(release) = &fmod.latest_release {
+    println!("  latest:    {} ({})", release.version, release.released_at);
+    if let Some(fv) = release.info_json.factorio_version {
+      println!("  factorio:  {}", fv);
+    }
+  }
+  println!();
+  println!("{}", fmod.summary);
+  Ok(())
+}
+
+async fn search(ctx: &Context, query: &ModQuery) -> CliResult {
+  let list = ctx.search(query).await?;
+
+  for fmod in &list.results {
+    print_search_result(fmod);
+  }
+  println!(
+    "Page {} of {}, {} mods",
+    list.pagination.page, list.pagination.page_count, list.pagination.count
+  );
+  Ok(())
+}
+
+async fn search_all(ctx: &Context, query: &ModQuery, prefetch: usize) -> CliResult {
+  let mut mods = std::pin::pin!(ctx.search_all(query, prefetch));
+
+  let mut count = 0;
+  while let Some(fmod) = mods.next().await {
+    print_search_result(&fmod?);
+    count += 1;
+  }
+  println!("{} mods", count);
+  Ok(())
+}
+
+fn print_search_result(fmod: &FModShort) {
+  let latest = fmod.latest_release.as_ref().map(|r| r.version.to_string());
+  println!(
+    "{} ({}) {} - {} downloads",
+    fmod.name,
+    fmod.title,
+    latest.as_deref().unwrap_or("-"),
+    fmod.downloads_count
+  );
+}
+
+async fn list(path: &Path) -> CliResult {
+  let cfg = FurrConfig::load(path).await?;
+
+  for entry in &cfg.mods {
+    println!(
+      "{} {} {}",
+      if entry.enabled { "[x]" } else { "[ ]" },
+      entry.name,
+      entry.version
+    );
+  }
+  Ok(())
+}
+
+async fn saves(path: &Path, folder: Option<PathBuf>) -> CliResult {
+  let folder = match folder {
+    Some(folder) => folder,
+    None => {
+      let cfg = FurrConfig::load(path).await?;
+      cfg.mod_folder()?.with_file_name("saves")
+    }
+  };
+
+  for save in list_saves(&folder).await? {
+    println!("{}", save);
+    for saved in &save.header.mods {
+      println!("    {}", saved);
+    }
+  }
+  Ok(())
+}
+
+async fn sync(path: &Path, save: &Path, dry_run: bool) -> CliResult {
+  let mut cfg = FurrConfig::load(path).await?;
+  let save = SaveFile::load(save).await?;
+  let sync = SaveSync::new(&cfg, &save.header)?;
+
+  print!("{}", sync);
+  if !dry_run && !sync.is_empty() {
+    sync.apply(&mut cfg);
+    cfg.save(path).await?;
+  }
+  Ok(())
+}
+
+async fn outdated(path: &Path, ctx: &Context, policy: &ReleasePolicy) -> CliResult {
+  let cfg = FurrConfig::load(path).await?;
+  let lock = LockFile::load(&LockFile::path_for(path)).await?;
+
+  let mut outdated = 0;
+  for (name, release) in cfg.latest_releases(ctx, policy).await? {
+    let locked = lock.as_ref().and_then(|lock| lock.get(&name));
+    match (locked, release) {
+      (_, Err(e)) => println!("{}", e),
+      (Some(locked), Ok(release)) if locked.version == release.version => (),
+      (locked, Ok(release)) => {
+        outdated += 1;
+        match locked {
+          Some(locked) => println!("{} {} -> {}", name, locked.version, release.version),
+          None => println!("{} (not locked) -> {}", name, release.version),
+        }
+      }
+    }
+  }
+
+  if outdated == 0 {
+    println!("Every mod is up to date");
+  }
+  Ok(())
+}
+
+/// Installs the releases of the lockfile and removes the other releases of the locked mods.
+///
+/// The lockfile is resolved again when it is missing or out of date. When `update` is set,
+/// the given mods, or every mod if the list is empty, are resolved to their latest release.
+/// Unlike `apply`, mods outside of the lockfile and `mod-list.json` are left untouched.
+async fn install(path: &Path, update: Option<Vec<String>>, options: &Options) -> CliResult {
+  let cfg = FurrConfig::load(path).await?;
+  let ctx = options.authenticated_context()?;
+  let lock_path = LockFile::path_for(path);
+  let mut previous = LockFile::load(&lock_path).await?;
+
+  let lock = match (&mut previous, update) {
+    (Some(lock), None) if lock.is_up_to_date(&cfg) => lock.clone(),
+    (Some(lock), Some(names)) if !names.is_empty() => {
+      lock.mods.retain(|m| !names.contains(&m.name));
+      resolve(&cfg, &ctx, previous.as_ref(), &lock_path).await?
+    }
+    (_, Some(_)) => resolve(&cfg, &ctx, None, &lock_path).await?,
+    (_, None) => resolve(&cfg, &ctx, previous.as_ref(), &lock_path).await?,
+  };
+
+  let mut plan = Plan::new(&cfg, &lock).await?;
+  plan.changes.retain(|change| match change {
+    Change::Remove { installed } => lock.get(&installed.name).is_some(),
+    Change::Enable { .. } | Change::Disable { .. } => false,
+    _ => true,
+  });
+
+  print!("{}", plan);
+  apply_plan(&plan, &ctx, options).await
+}
+
+async fn plan(path: &Path, ctx: &Context) -> CliResult {
+  let cfg = FurrConfig::load(path).await?;
+  let lock = current_lock(&cfg, ctx, path, false).await?;
+
+  print!("{}", Plan::new(&cfg, &lock).await?);
+  Ok(())
+}
+
+async fn apply(path: &Path, dry_run: bool, ctx: &Context, options: &Options) -> CliResult {
+  let cfg = FurrConfig::load(path).await?;
+  let lock = current_lock(&cfg, ctx, path, !dry_run).await?;
+  let plan = Plan::new(&cfg, &lock).await?;
+
+  print!("{}", plan);
+  if !dry_run && !plan.is_empty() {
+    apply_plan(&plan, &options.authenticated_context()?, options).await?;
+  }
+  Ok(())
+}
+
+/// Applies a plan, printing each downloaded file.
+///
+/// The token is checked first, so that an expired one fails before anything is downloaded.
+async fn apply_plan(plan: &Plan, ctx: &Context, options: &Options) -> CliResult {
+  if let Some(release) = plan.releases().first() {
+    ctx.check_token(release).await?;
+  }
+
+  let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+  let mut downloader = Downloader::new(ctx).parallelism(options.jobs).events(sender);
+  if let Some(limit_rate) = options.limit_rate {
+    downloader = downloader.bandwidth_limit(limit_rate * 1024);
+  }
+
+  let printer = tokio::spawn(async move {
+    let (mut finished, mut files, mut downloaded) = (0, 0, 0);
+    while let Some(event) = receiver.recv().await {
+      match event {
+        DownloadEvent::Finished { file_name, .. } => {
+          finished += 1;
+          println!("  [{}/{}] {}", finished, files, file_name);
+        }
+        DownloadEvent::Failed { file_name, error } => eprintln!("  failed {}: {}", file_name, error),
+        DownloadEvent::Overall {
+          files: total,
+          downloaded: bytes,
+          ..
+        } => (files, downloaded) = (total, bytes),
+        _ => (),
+      }
+    }
+    if files > 0 {
+      println!("Downloaded {:.1} MiB", downloaded as f64 / (1024.0 * 1024.0));
+    }
+  });
+
+  let result = plan.apply_with(&downloader).await;
+  drop(downloader);
+  printer.await?;
+  Ok(result?)
+}
+
+/// Returns the lockfile of the configuration, resolving it again if it is missing or out of date.
+///
+/// The new lockfile is only written when `save` is set.
+async fn current_lock(
+  cfg: &FurrConfig,
+  ctx: &Context,
+  path: &Path,
+  save: bool,
+) -> Result<LockFile, ConfigError> {
+  let lock_path = LockFile::path_for(path);
+  let previous = LockFile::load(&lock_path).await?;
+
+  match previous {
+    Some(lock) if lock.is_up_to_date(cfg) => Ok(lock),
+    previous if save => resolve(cfg, ctx, previous.as_ref(), &lock_path).await,
+    previous => {
+      println!("'{}' is out of date, resolving the configuration again", lock_path.display());
+      LockFile::resolve(cfg, ctx, previous.as_ref()).await
+    }
+  }
+}
+
+async fn resolve(
+  cfg: &FurrConfig,
+  ctx: &Context,
+  previous: Option<&LockFile>,
+  lock_path: &Path,
+) -> Result<LockFile, ConfigError> {
+  let lock = LockFile::resolve(cfg, ctx, previous).await?;
+  lock.save(lock_path).await?;
+  println!("Locked {} mods in '{}'", lock.mods.len(), lock_path.display());
+  Ok(lock)
+}
@@ -0,0 +1,204 @@
+mod commands;
+
+use clap::{Parser, Subcommand};
+use furrctorio_core::{download::DEFAULT_PARALLELISM, prelude::{FactorioVersionReq, GameVersion, PageSize}};
+use semver::Version;
+use std::path::PathBuf;
+use url::Url;
+use tracing_subscriber::EnvFilter;
+
+/// A CLI mod manager for Factorio servers.
+#[derive(Debug, Parser)]
+#[command(name = "furrctorio", version, about)]
+pub struct Cli {
+  /// Path of the furrctorio configuration file.
+  #[arg(
+    short,
+    long,
+    global = true,
+    env = "FURRCTORIO_CONFIG",
+    default_value = "furrctorio.yaml"
+  )]
+  pub config: PathBuf,
+
+  /// How many mods are downloaded, or pages fetched, at the same time.
+  #[arg(short, long, global = true, default_value_t = DEFAULT_PARALLELISM)]
+  pub jobs: usize,
+
+  /// Limits the total download rate, in KiB/s.
+  #[arg(long, global = true)]
+  pub limit_rate: Option<u64>,
+
+  /// How many times a failed request to the portal is sent again.
+  #[arg(long, global = true, default_value_t = 3)]
+  pub retries: u32,
+
+  /// Answers from the metadata cache only, without contacting the portal.
+  #[arg(long, global = true, env = "FURRCTORIO_OFFLINE")]
+  pub offline: bool,
+
+  /// A `player-data.json` to take the credentials from, tried right after the environment and
+  /// the credentials file.
+  #[arg(long, global = true, env = "FACTORIO_PLAYER_DATA")]
+  pub player_data: Option<PathBuf>,
+
+  /// Uses a mirror of the mod portal, serving the API under `/api`.
+  #[arg(long, global = true, env = "FURRCTORIO_PORTAL_URL")]
+  pub portal_url: Option<Url>,
+
+  #[command(subcommand)]
+  pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+  /// Creates a new configuration file.
+  Init {
+    /// The Factorio version of the server.
+    #[arg(long)]
+    factorio_version: Option<Version>,
+    /// The folder the mods are installed in.
+    #[arg(long)]
+    mod_folder: Option<PathBuf>,
+    /// The game has the Space Age expansion, whose mods come with Factorio 2.0.
+    #[arg(long)]
+    space_age: bool,
+    /// Overwrites an existing configuration file.
+    #[arg(long)]
+    force: bool,
+    /// Adds the mods of a save, pinned to the version the save was made with.
+    #[arg(long)]
+    from_save: Option<PathBuf>,
+  },
+  /// Logs in to the mod portal and saves the token in the OS keyring.
+  ///
+  /// Without a keyring, the token is saved in `credentials.json` in the furrctorio data directory.
+  ///
+  /// The password is read from the first line of the standard input.
+  Login {
+    /// The Factorio username or email.
+    username: String,
+    /// The code sent by email when the portal asks for it.
+    #[arg(long)]
+    email_code: Option<String>,
+  },
+  /// Adds a mod to the configuration.
+  Add {
+    /// The name of the mod on the mod portal.
+    name: String,
+    /// The version requirement of the mod, like `>= 1.1` or `^0.12`.
+    #[arg(long, default_value = "*")]
+    version: FactorioVersionReq,
+    /// Adds the mod as disabled.
+    #[arg(long)]
+    disabled: bool,
+  },
+  /// Removes a mod from the configuration.
+  Remove {
+    /// The name of the mod.
+    name: String,
+  },
+  /// Enables a mod of the configuration.
+  Enable {
+    /// The name of the mod.
+    name: String,
+  },
+  /// Disables a mod of the configuration.
+  Disable {
+    /// The name of the mod.
+    name: String,
+  },
+  /// Installs the releases of the lockfile, resolving it first if it is missing or out of date.
+  Install,
+  /// Resolves the latest matching releases again, then installs them.
+  Update {
+    /// Only updates these mods, keeping the others at their locked release.
+    names: Vec<String>,
+  },
+  /// Shows the changes `apply` would make to the mod folder.
+  Plan,
+  /// Makes the mod folder match the lockfile, removing every other mod.
+  Apply {
+    /// Only prints the changes.
+    #[arg(long)]
+    dry_run: bool,
+  },
+  /// Shows information about a mod from the mod portal.
+  Info {
+    /// The name of the mod.
+    name: String,
+  },
+  /// Searches the mod list of the mod portal.
+  Search {
+    /// Sorts the mods by this field.
+    #[arg(long, value_parser = ["name", "created_at", "updated_at"])]
+    sort: Option<String>,
+    /// The direction of the sort.
+    #[arg(long, value_parser = ["asc", "desc"])]
+    order: Option<String>,
+    /// The page to show, starting at 1.
+    #[arg(long, default_value_t = 1)]
+    page: usize,
+    /// How many mods are shown per page, or `max` for every mod.
+    #[arg(long, value_parser = parse_page_size)]
+    page_size: Option<PageSize>,
+    /// Only shows the mods with a release for this Factorio version, like `1.1`.
+    #[arg(long)]
+    factorio_version: Option<GameVersion>,
+    /// Leaves out deprecated mods.
+    #[arg(long)]
+    hide_deprecated: bool,
+    /// Shows every page from `--page` to the last one.
+    #[arg(long)]
+    all: bool,
+  },
+  /// Lists the mods of the configuration.
+  List,
+  /// Lists the mods that have a newer matching release than the locked one.
+  Outdated {
+    /// Ignores the releases with a `0.y.z` version, for mods that use them for unstable releases.
+    #[arg(long)]
+    exclude_zero_major: bool,
+    /// Ignores releases published less than this many days ago.
+    #[arg(long)]
+    cooldown: Option<u32>,
+  },
+  /// Lists the saves of a folder with their version and mods.
+  Saves {
+    /// The save folder, by default the `saves` folder next to the mod folder.
+    folder: Option<PathBuf>,
+  },
+  /// Makes the configuration load the mods of a save, in the version the save was made with.
+  Sync {
+    /// The zip file of the save.
+    save: PathBuf,
+    /// Only prints the changes.
+    #[arg(long)]
+    dry_run: bool,
+  },
+}
+
+fn parse_page_size(value: &str) -> Result<PageSize, String> {
+  match value {
+    "max" => Ok(PageSize::Max),
+    count => count
+      .parse()
+      .map(PageSize::Count)
+      .map_err(|_| format!("'{}' is neither a number nor 'max'", count)),
+  }
+}
+
+#[tokio::main]
+async fn main() {
+  tracing_subscriber::fmt()
+    .with_env_filter(EnvFilter::from_default_env())
+    .with_writer(std::io::stderr)
+    .init();
+
+  let cli = Cli::parse();
+
+  if let Err(e) = commands::run(cli).await {
+    eprintln!("error: {}", e);
+    std::process::exit(1);
+  }
+}
@@ -0,0 +1,212 @@
+//! Primitives of Factorio's binary formats, shared by `mod-settings.dat` and save files.
+
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// The version of Factorio that wrote a binary file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+pub struct ApplicationVersion {
+  pub major: u16,
+  pub minor: u16,
+  pub patch: u16,
+  pub build: u16,
+}
+
+impl ApplicationVersion {
+  pub fn new(major: u16, minor: u16, patch: u16, build: u16) -> Self {
+    Self {
+      major,
+      minor,
+      patch,
+      build,
+    }
+  }
+}
+
+impl Display for ApplicationVersion {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+  }
+}
+
+/// Reads little-endian values from a byte slice.
+pub(crate) struct BinaryReader<'a> {
+  data: &'a [u8],
+  position: usize,
+}
+
+impl<'a> BinaryReader<'a> {
+  pub(crate) fn new(data: &'a [u8]) -> Self {
+    Self { data, position: 0 }
+  }
+
+  pub(crate) fn position(&self) -> usize {
+    self.position
+  }
+
+  pub(crate) fn is_empty(&self) -> bool {
+    self.position >= self.data.len()
+  }
+
+  pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+    let end = self.position.checked_add(len).filter(|&end| end <= self.data.len());
+    let Some(end) = end else {
+      return Err(Error::UnexpectedEnd {
+        offset: self.position,
+        wanted: len,
+        available: self.data.len() - self.position,
+      });
+    };
+
+    let bytes = &self.data[self.position..end];
+    self.position = end;
+    Ok(bytes)
+  }
+
+  fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+    let mut array = [0; N];
+    array.copy_from_slice(self.bytes(N)?);
+    Ok(array)
+  }
+
+  pub(crate) fn u8(&mut self) -> Result<u8, Error> {
+    Ok(self.array::<1>()?[0])
+  }
+
+  pub(crate) fn bool(&mut self) -> Result<bool, Error> {
+    Ok(self.u8()? != 0)
+  }
+
+  pub(crate) fn u16(&mut self) -> Result<u16, Error> {
+    Ok(u16::from_le_bytes(self.array()?))
+  }
+
+  pub(crate) fn u32(&mut self) -> Result<u32, Error> {
+    Ok(u32::from_le_bytes(self.array()?))
+  }
+
+  pub(crate) fn u64(&mut self) -> Result<u64, Error> {
+    Ok(u64::from_le_bytes(self.array()?))
+  }
+
+  pub(crate) fn i64(&mut self) -> Result<i64, Error> {
+    Ok(i64::from_le_bytes(self.array()?))
+  }
+
+  pub(crate) fn f64(&mut self) -> Result<f64, Error> {
+    Ok(f64::from_le_bytes(self.array()?))
+  }
+
+  /// Reads a number stored on one byte, or on 255 followed by a u16 when it does not fit.
+  pub(crate) fn optimized_u16(&mut self) -> Result<u16, Error> {
+    match self.u8()? {
+      255 => self.u16(),
+      value => Ok(value as u16),
+    }
+  }
+
+  /// Reads a number stored on one byte, or on 255 followed by a u32 when it does not fit.
+  pub(crate) fn optimized_u32(&mut self) -> Result<u32, Error> {
+    match self.u8()? {
+      255 => self.u32(),
+      value => Ok(value as u32),
+    }
+  }
+
+  /// Reads a string prefixed by its optimized length.
+  pub(crate) fn string(&mut self) -> Result<String, Error> {
+    let len = self.optimized_u32()? as usize;
+    let bytes = self.bytes(len)?;
+    String::from_utf8(bytes.to_vec())
+      .map_err(|e| Error::ParcingError(format!("Invalid UTF-8 string: {}", e)))
+  }
+
+  pub(crate) fn version(&mut self) -> Result<ApplicationVersion, Error> {
+    Ok(ApplicationVersion::new(self.u16()?, self.u16()?, self.u16()?, self.u16()?))
+  }
+}
+
+/// Writes little-endian values to a byte buffer.
+#[derive(Default)]
+pub(crate) struct BinaryWriter {
+  data: Vec<u8>,
+}
+
+impl BinaryWriter {
+  pub(crate) fn into_bytes(self) -> Vec<u8> {
+    self.data
+  }
+
+  pub(crate) fn u8(&mut self, value: u8) {
+    self.data.push(value);
+  }
+
+  pub(crate) fn bool(&mut self, value: bool) {
+    self.u8(value as u8);
+  }
+
+  pub(crate) fn u16(&mut self, value: u16) {
+    self.data.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub(crate) fn u32(&mut self, value: u32) {
+    self.data.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub(crate) fn u64(&mut self, value: u64) {
+    self.data.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub(crate) fn i64(&mut self, value: i64) {
+    self.data.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub(crate) fn f64(&mut self, value: f64) {
+    self.data.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub(crate) fn optimized_u32(&mut self, value: u32) {
+    if value < 255 {
+      self.u8(value as u8);
+    } else {
+      self.u8(255);
+      self.u32(value);
+    }
+  }
+
+  pub(crate) fn string(&mut self, value: &str) {
+    self.optimized_u32(value.len() as u32);
+    self.data.extend_from_slice(value.as_bytes());
+  }
+
+  pub(crate) fn version(&mut self, version: &ApplicationVersion) {
+    self.u16(version.major);
+    self.u16(version.minor);
+    self.u16(version.patch);
+    self.u16(version.build);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_optimized_numbers() {
+    let mut writer = BinaryWriter::default();
+    writer.optimized_u32(254);
+    writer.optimized_u32(255);
+    writer.string("base");
+    let data = writer.into_bytes();
+
+    assert_eq!(&data[..6], &[254, 255, 255, 0, 0, 0]);
+
+    let mut reader = BinaryReader::new(&data);
+    assert_eq!(reader.optimized_u32().unwrap(), 254);
+    assert_eq!(reader.optimized_u32().unwrap(), 255);
+    assert_eq!(reader.string().unwrap(), "base");
+    assert!(reader.is_empty());
+    assert!(reader.u8().is_err());
+  }
+}
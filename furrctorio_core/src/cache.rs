@@ -0,0 +1,286 @@
+//! A persistent cache of the JSON answered by the mod portal.
+//!
+//! A [`MetadataCache`] keeps the last answer of each portal URL on disk. Fresh answers are
+//! used without a request, stale ones are revalidated with `If-None-Match` and
+//! `If-Modified-Since`, and mods that disappear from the portal keep their last known
+//! metadata. In offline mode, a [`Context`] only answers from the cache.
+//!
+//! [`Context`]: crate::model::context::Context
+
+use crate::{
+  error::{error_for_status, Error},
+  model::context::Context,
+};
+use chrono::{DateTime, Utc};
+use reqwest::{
+  header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+  RequestBuilder, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::{
+  path::{Path, PathBuf},
+  time::Duration,
+};
+use tracing::{debug, instrument, warn};
+use url::Url;
+
+/// The kinds of portal requests, which each have their own time to live.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachedEndpoint {
+  /// `/api/mods/{name}`.
+  Mod,
+  /// `/api/mods/{name}/full`.
+  ModFull,
+  /// `/api/mods`, with any query.
+  Listing,
+}
+
+/// Returns the furrctorio data directory, holding the cache and the credentials file.
+///
+/// It is `FURRCTORIO_DATA_DIR` if it is set, or `furrctorio` in the data directory of the
+/// platform, like `~/.local/share` on Linux.
+pub fn data_dir() -> Option<PathBuf> {
+  match std::env::var_os("FURRCTORIO_DATA_DIR") {
+    Some(data) => Some(PathBuf::from(data)),
+    None => Some(dirs::data_dir()?.join("furrctorio")),
+  }
+}
+
+/// The answer to a request, as stored on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+  url: Url,
+  etag: Option<String>,
+  last_modified: Option<String>,
+  fetched_at: DateTime<Utc>,
+  body: String,
+}
+
+/// Stores the answers of the portal in a folder, one JSON file per URL.
+#[derive(Debug, Clone)]
+pub struct MetadataCache {
+  folder: PathBuf,
+  mod_ttl: Duration,
+  mod_full_ttl: Duration,
+  listing_ttl: Duration,
+}
+
+impl MetadataCache {
+  /// Creates a cache in a folder, which is created when the first answer is stored.
+  ///
+  /// Mods are considered fresh for an hour and listings for ten minutes.
+  pub fn new(folder: impl Into<PathBuf>) -> Self {
+    Self {
+      folder: folder.into(),
+      mod_ttl: Duration::from_secs(60 * 60),
+      mod_full_ttl: Duration::from_secs(60 * 60),
+      listing_ttl: Duration::from_secs(10 * 60),
+    }
+  }
+
+  /// Returns the default folder of the cache, `metadata` in the furrctorio data directory.
+  pub fn default_folder() -> Option<PathBuf> {
+    Some(data_dir()?.join("metadata"))
+  }
+
+  /// Sets how long the answers of an endpoint are used without asking the portal.
+  pub fn ttl(mut self, endpoint: CachedEndpoint, ttl: Duration) -> Self {
+    match endpoint {
+      CachedEndpoint::Mod => self.mod_ttl = ttl,
+      CachedEndpoint::ModFull => self.mod_full_ttl = ttl,
+      CachedEndpoint::Listing => self.listing_ttl = ttl,
+    }
+    self
+  }
+
+  pub fn folder(&self) -> &Path {
+    &self.folder
+  }
+
+  fn ttl_of(&self, endpoint: CachedEndpoint) -> Duration {
+    match endpoint {
+      CachedEndpoint::Mod => self.mod_ttl,
+      CachedEndpoint::ModFull => self.mod_full_ttl,
+      CachedEndpoint::Listing => self.listing_ttl,
+    }
+  }
+
+  fn path(&self, url: &Url) -> PathBuf {
+    let mut hasher = Sha1::new();
+    hasher.update(url.as_str());
+    self.folder.join(format!("{:x}.json", hasher.finalize()))
+  }
+
+  async fn load(&self, url: &Url) -> Option<CacheEntry> {
+    let data = tokio::fs::read(self.path(url)).await.ok()?;
+    match serde_json::from_slice::<CacheEntry>(&data) {
+      // Two URLs with the same hash are not worth handling, but must not mix up.
+      Ok(entry) if &entry.url == url => Some(entry),
+      Ok(_) => None,
+      Err(e) => {
+        warn!("Ignoring the invalid cache entry of '{}': {}", url, e);
+        None
+      }
+    }
+  }
+
+  async fn store(&self, entry: &CacheEntry) -> Result<(), Error> {
+    let data = serde_json::to_vec(entry)
+      .map_err(|e| Error::ParcingError(format!("Could not serialize a cache entry: {}", e)))?;
+    tokio::fs::create_dir_all(&self.folder)
+      .await
+      .map_err(Error::IoError)?;
+
+    // Written next to the entry, then renamed, so that a reader never sees half an entry.
+    let path = self.path(&entry.url);
+    let part = path.with_extension("json.part");
+    tokio::fs::write(&part, data).await.map_err(Error::IoError)?;
+    tokio::fs::rename(&part, &path).await.map_err(Error::IoError)
+  }
+
+  /// Returns the body of a GET request, from the cache when it is fresh enough.
+  ///
+  /// # Arguments
+  ///
+  /// * `ctx` - The context sending the request when the cache cannot answer.
+  /// * `endpoint` - The kind of request, which sets how long its answer is fresh.
+  /// * `request` - The request, without credentials since the answer is stored.
+  /// * `offline` - Only answers from the cache, whatever the age of the answer.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<String, Error>` - Returns the body, or `Error::NotCached` when offline and nothing is stored.
+  #[instrument(skip_all, fields(endpoint = ?endpoint))]
+  pub(crate) async fn fetch(
+    &self,
+    ctx: &Context,
+    endpoint: CachedEndpoint,
+    request: RequestBuilder,
+    offline: bool,
+  ) -> Result<String, Error> {
+    let (client, request) = request.build_split();
+    let request = request.map_err(Error::RequestError)?;
+    let url = request.url().clone();
+    let cached = self.load(&url).await;
+
+    let cached = match cached {
+      Some(entry) if offline => return Ok(entry.body),
+      None if offline => return Err(Error::NotCached(url.to_string())),
+      Some(entry) => {
+        let age = (Utc::now() - entry.fetched_at).to_std().unwrap_or_default();
+        if age < self.ttl_of(endpoint) {
+          debug!("Fresh in the cache: {}", url);
+          return Ok(entry.body);
+        }
+        Some(entry)
+      }
+      None => None,
+    };
+
+    let mut request = RequestBuilder::from_parts(client, request);
+    if let Some(entry) = &cached {
+      if let Some(etag) = &entry.etag {
+        request = request.header(IF_NONE_MATCH, etag);
+      }
+      if let Some(last_modified) = &entry.last_modified {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+      }
+    }
+
+    let response = ctx.send(request).await.map_err(Error::RequestError)?;
+    let header = |name| {
+      response
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+    };
+    let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+
+    let entry = match (response.status(), cached) {
+      (StatusCode::NOT_MODIFIED, Some(entry)) => {
+        debug!("Not modified: {}", url);
+        CacheEntry {
+          fetched_at: Utc::now(),
+          etag: etag.or(entry.etag),
+          last_modified: last_modified.or(entry.last_modified),
+          ..entry
+        }
+      }
+      (StatusCode::NOT_FOUND, Some(entry)) => {
+        warn!("'{}' is gone from the portal, using its last known answer", url);
+        return Ok(entry.body);
+      }
+      (status, _) if status.is_success() => CacheEntry {
+        url,
+        etag,
+        last_modified,
+        fetched_at: Utc::now(),
+        body: response.text().await.map_err(Error::RequestError)?,
+      },
+      // Errors are not stored.
+      (status, _) => {
+        error_for_status(response).await?;
+        return Err(Error::ParcingError(format!("Unexpected status {} from '{}'", status, url)));
+      }
+    };
+
+    if let Err(e) = self.store(&entry).await {
+      warn!("Could not store '{}' in the cache: {}", entry.url, e);
+    }
+    Ok(entry.body)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{serve, Response};
+
+  #[tokio::test]
+  async fn test_fetch() {
+    let server = serve(|index, _| match index {
+      0 => Response::new("200 OK").header("ETag", "\"v1\"").body("{\"v\":1}\n"),
+      1 => Response::new("304 Not Modified"),
+      _ => Response::new("404 Not Found").body("{}"),
+    })
+    .await;
+    let url = server.url.join("api/mods/flib").unwrap();
+    let folder = std::env::temp_dir().join(format!("furrctorio-cache-{}", std::process::id()));
+    let cache = MetadataCache::new(&folder).ttl(CachedEndpoint::Mod, Duration::ZERO);
+    let ctx = Context::anonymous().unwrap();
+    let fetch = |offline| cache.fetch(&ctx, CachedEndpoint::Mod, ctx.client().get(url.clone()), offline);
+
+    assert_eq!(fetch(false).await.unwrap(), "{\"v\":1}\n");
+    // Revalidated with the ETag of the first answer.
+    assert_eq!(fetch(false).await.unwrap(), "{\"v\":1}\n");
+    assert_eq!(server.requests()[1].header("if-none-match"), Some("\"v1\""));
+    // Gone from the portal.
+    assert_eq!(fetch(false).await.unwrap(), "{\"v\":1}\n");
+    // Offline, without any request.
+    assert_eq!(fetch(true).await.unwrap(), "{\"v\":1}\n");
+    assert_eq!(server.requests().len(), 3);
+
+    let missing = ctx.client().get(url.join("stdlib").unwrap());
+    let res = cache.fetch(&ctx, CachedEndpoint::Mod, missing, true).await;
+    tokio::fs::remove_dir_all(&folder).await.unwrap();
+    assert!(matches!(res, Err(Error::NotCached(_))));
+  }
+
+  #[tokio::test]
+  async fn test_fresh() {
+    let server = serve(|_, _| Response::new("200 OK").body("[]")).await;
+    let url = server.url.join("api/mods").unwrap();
+    let folder = std::env::temp_dir().join(format!("furrctorio-fresh-{}", std::process::id()));
+    let cache = MetadataCache::new(&folder);
+    let ctx = Context::anonymous().unwrap();
+
+    for _ in 0..3 {
+      let request = ctx.client().get(url.clone());
+      assert_eq!(cache.fetch(&ctx, CachedEndpoint::Listing, request, false).await.unwrap(), "[]");
+    }
+    tokio::fs::remove_dir_all(&folder).await.unwrap();
+    assert_eq!(server.requests().len(), 1);
+  }
+}
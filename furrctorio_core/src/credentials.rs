@@ -0,0 +1,364 @@
+//! Finds the Factorio credentials needed to download mods.
+//!
+//! A [`CredentialChain`] tries its sources in order and stops at the first one that has both
+//! a username and a token: explicit values, the `FACTORIO_USERNAME` and `FACTORIO_TOKEN`
+//! environment variables, a credentials file of furrctorio, the `player-data.json` written by
+//! the game, or the OS keyring.
+
+use crate::{cache::data_dir, error::Error};
+use serde::{Deserialize, Serialize};
+use std::{
+  fmt::Display,
+  path::{Path, PathBuf},
+};
+use tracing::{debug, instrument};
+
+/// The keyring service the credentials are stored under.
+pub const KEYRING_SERVICE: &str = "furrctorio";
+/// The keyring account the credentials are stored under.
+const KEYRING_USER: &str = "factorio";
+
+/// A username and the token of the mod portal.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Credentials {
+  pub username: String,
+  pub(crate) token: String,
+}
+
+impl Credentials {
+  pub fn new(username: impl Into<String>, token: impl Into<String>) -> Self {
+    Self {
+      username: username.into(),
+      token: token.into(),
+    }
+  }
+
+  /// Stores the credentials in the OS keyring, for [`CredentialSource::Keyring`].
+  pub fn save_to_keyring(&self) -> Result<(), Error> {
+    let secret = serde_json::json!({ "username": self.username, "token": self.token });
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+      .and_then(|entry| entry.set_password(&secret.to_string()))
+      .map_err(Error::KeyringError)
+  }
+
+  /// Stores the credentials in a file, for [`CredentialSource::File`].
+  ///
+  /// The folders of the file are created if needed. On Unix, only the owner can read the file.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - The file to write, usually [`default_credentials_path`].
+  pub fn save_to_file(&self, path: &Path) -> Result<(), Error> {
+    if let Some(folder) = path.parent() {
+      std::fs::create_dir_all(folder)?;
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options.open(path)?;
+    serde_json::to_writer_pretty(file, self).map_err(std::io::Error::from)?;
+    Ok(())
+  }
+}
+
+impl std::fmt::Debug for Credentials {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    // The token is as good as a password, it must not end up in logs.
+    f.debug_struct("Credentials")
+      .field("username", &self.username)
+      .finish_non_exhaustive()
+  }
+}
+
+/// The fields of `player-data.json` holding the credentials.
+#[derive(Deserialize)]
+struct PlayerData {
+  #[serde(rename = "service-username")]
+  username: Option<String>,
+  #[serde(rename = "service-token")]
+  token: Option<String>,
+}
+
+/// A place credentials can be found.
+#[derive(Clone, PartialEq, Eq)]
+pub enum CredentialSource {
+  /// Values given by the caller, like command line arguments.
+  Explicit(Credentials),
+  /// The `FACTORIO_USERNAME` and `FACTORIO_TOKEN` environment variables.
+  Environment,
+  /// A JSON file with the `username` and `token` fields, written by [`Credentials::save_to_file`].
+  File(PathBuf),
+  /// The `service-username` and `service-token` fields of a `player-data.json`.
+  PlayerData(PathBuf),
+  /// The credentials saved by [`Credentials::save_to_keyring`].
+  Keyring,
+}
+
+impl Display for CredentialSource {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      CredentialSource::Explicit(_) => write!(f, "explicit credentials"),
+      CredentialSource::Environment => write!(f, "environment"),
+      CredentialSource::File(path) | CredentialSource::PlayerData(path) => {
+        write!(f, "'{}'", path.display())
+      }
+      CredentialSource::Keyring => write!(f, "keyring"),
+    }
+  }
+}
+
+impl std::fmt::Debug for CredentialSource {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self)
+  }
+}
+
+/// No source of a chain had credentials.
+#[derive(Debug)]
+pub struct CredentialError {
+  /// Every source that was tried, in order, with the reason it gave nothing.
+  pub tried: Vec<(CredentialSource, String)>,
+}
+
+impl Display for CredentialError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.tried.is_empty() {
+      return write!(f, "No Factorio credentials found, no source was configured");
+    }
+
+    write!(f, "No Factorio credentials found, tried:")?;
+    for (source, reason) in &self.tried {
+      write!(f, "\n  {}: {}", source, reason)?;
+    }
+    Ok(())
+  }
+}
+
+impl std::error::Error for CredentialError {}
+
+/// The sources of credentials, tried in order.
+#[derive(Debug, Clone, Default)]
+pub struct CredentialChain {
+  sources: Vec<CredentialSource>,
+}
+
+impl CredentialChain {
+  /// Creates a chain without any source.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The usual chain: the environment, the credentials file in the furrctorio data directory,
+  /// the `player-data.json` of a local game, then the keyring.
+  pub fn standard() -> Self {
+    let mut chain = Self::new().environment();
+    if let Some(path) = default_credentials_path() {
+      chain = chain.file(path);
+    }
+    for path in default_player_data_paths() {
+      chain = chain.player_data(path);
+    }
+    chain.keyring()
+  }
+
+  pub fn explicit(mut self, username: impl Into<String>, token: impl Into<String>) -> Self {
+    self
+      .sources
+      .push(CredentialSource::Explicit(Credentials::new(username, token)));
+    self
+  }
+
+  pub fn environment(mut self) -> Self {
+    self.sources.push(CredentialSource::Environment);
+    self
+  }
+
+  pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+    self.sources.push(CredentialSource::File(path.into()));
+    self
+  }
+
+  pub fn player_data(mut self, path: impl Into<PathBuf>) -> Self {
+    self.sources.push(CredentialSource::PlayerData(path.into()));
+    self
+  }
+
+  pub fn keyring(mut self) -> Self {
+    self.sources.push(CredentialSource::Keyring);
+    self
+  }
+
+  pub fn sources(&self) -> &[CredentialSource] {
+    &self.sources
+  }
+
+  /// Returns the credentials of the first source that has them.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<(Credentials, &CredentialSource), Error>` - Returns the credentials and where they were found, or `Error::CredentialError` listing every source tried.
+  #[instrument(skip(self))]
+  pub fn resolve(&self) -> Result<(Credentials, &CredentialSource), Error> {
+    Ok(self.resolve_with(|name| std::env::var(name).ok())?)
+  }
+
+  fn resolve_with(
+    &self,
+    env: impl Fn(&str) -> Option<String>,
+  ) -> Result<(Credentials, &CredentialSource), CredentialError> {
+    let mut tried = Vec::new();
+    for source in &self.sources {
+      match read_source(source, &env) {
+        Ok(credentials) => {
+          debug!("Using the credentials of {}", source);
+          return Ok((credentials, source));
+        }
+        Err(reason) => {
+          debug!("No credentials in {}: {}", source, reason);
+          tried.push((source.clone(), reason));
+        }
+      }
+    }
+    Err(CredentialError { tried })
+  }
+}
+
+fn read_source(
+  source: &CredentialSource,
+  env: impl Fn(&str) -> Option<String>,
+) -> Result<Credentials, String> {
+  let credentials = match source {
+    CredentialSource::Explicit(credentials) => credentials.clone(),
+    CredentialSource::Environment => {
+      let var = |name| env(name).ok_or_else(|| format!("{} is not set", name));
+      Credentials::new(var("FACTORIO_USERNAME")?, var("FACTORIO_TOKEN")?)
+    }
+    CredentialSource::File(path) => {
+      let data = std::fs::read(path).map_err(|e| e.to_string())?;
+      serde_json::from_slice(&data).map_err(|e| format!("invalid file: {}", e))?
+    }
+    CredentialSource::PlayerData(path) => {
+      let data = std::fs::read(path).map_err(|e| e.to_string())?;
+      let data: PlayerData = serde_json::from_slice(&data).map_err(|e| e.to_string())?;
+      match (data.username, data.token) {
+        (Some(username), Some(token)) => Credentials::new(username, token),
+        _ => return Err("not logged in to the mod portal".to_string()),
+      }
+    }
+    CredentialSource::Keyring => {
+      let secret = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+        .and_then(|entry| entry.get_password())
+        .map_err(|e| e.to_string())?;
+      serde_json::from_str(&secret).map_err(|e| format!("invalid entry: {}", e))?
+    }
+  };
+
+  if credentials.username.is_empty() || credentials.token.is_empty() {
+    return Err("the username or the token is empty".to_string());
+  }
+  Ok(credentials)
+}
+
+/// Returns the credentials file of furrctorio, `credentials.json` in its data directory.
+///
+/// See [`data_dir`] for where the data directory is.
+pub fn default_credentials_path() -> Option<PathBuf> {
+  Some(data_dir()?.join("credentials.json"))
+}
+
+/// Returns where the game keeps `player-data.json` on this platform.
+pub fn default_player_data_paths() -> Vec<PathBuf> {
+  let mut paths = Vec::new();
+  if let Some(home) = dirs::home_dir() {
+    paths.push(home.join(".factorio").join("player-data.json"));
+  }
+  if cfg!(any(target_os = "windows", target_os = "macos")) {
+    if let Some(data) = dirs::data_dir() {
+      paths.push(data.join("factorio").join("player-data.json"));
+    }
+  }
+  paths
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_resolve() {
+    let folder = std::env::temp_dir().join(format!("furrctorio-credentials-{}", std::process::id()));
+    std::fs::create_dir_all(&folder).unwrap();
+    let player_data = folder.join("player-data.json");
+    std::fs::write(
+      &player_data,
+      r#"{"service-username": "furr", "service-token": "abc123", "latest-multiplayer-connections": []}"#,
+    )
+    .unwrap();
+    let missing = folder.join("missing.json");
+
+    let chain = CredentialChain::new()
+      .environment()
+      .player_data(&missing)
+      .player_data(&player_data)
+      .keyring();
+    let (credentials, source) = chain.resolve_with(|_| None).unwrap();
+    assert_eq!(credentials, Credentials::new("furr", "abc123"));
+    assert_eq!(source, &CredentialSource::PlayerData(player_data.clone()));
+
+    let env = |name: &str| Some(format!("{}-value", name));
+    let (credentials, source) = chain.resolve_with(env).unwrap();
+    assert_eq!(credentials.username, "FACTORIO_USERNAME-value");
+    assert_eq!(source, &CredentialSource::Environment);
+
+    std::fs::write(&player_data, r#"{"service-username": "furr"}"#).unwrap();
+    let chain = CredentialChain::new()
+      .explicit("", "")
+      .environment()
+      .player_data(&player_data);
+    let error = chain.resolve_with(|_| None).unwrap_err();
+    std::fs::remove_dir_all(&folder).unwrap();
+
+    assert_eq!(error.tried.len(), 3);
+    assert_eq!(
+      error.to_string(),
+      format!(
+        "No Factorio credentials found, tried:\n  explicit credentials: the username or the token is empty\n  environment: FACTORIO_USERNAME is not set\n  '{}': not logged in to the mod portal",
+        player_data.display()
+      )
+    );
+  }
+
+  #[test]
+  fn test_resolve_file() {
+    let folder = std::env::temp_dir().join(format!("furrctorio-credentials-file-{}", std::process::id()));
+    let file = folder.join("furrctorio").join("credentials.json");
+    let chain = CredentialChain::new().environment().file(&file).keyring();
+
+    let error = chain.resolve_with(|_| None).unwrap_err();
+    assert_eq!(error.tried[1].0, CredentialSource::File(file.clone()));
+
+    Credentials::new("furr", "abc123").save_to_file(&file).unwrap();
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::PermissionsExt;
+      let mode = std::fs::metadata(&file).unwrap().permissions().mode();
+      assert_eq!(mode & 0o777, 0o600);
+    }
+    let (credentials, source) = chain.resolve_with(|_| None).unwrap();
+    assert_eq!(credentials, Credentials::new("furr", "abc123"));
+    assert_eq!(source, &CredentialSource::File(file.clone()));
+
+    std::fs::write(&file, r#"{"username": "furr"}"#).unwrap();
+    let error = chain.resolve_with(|_| None).unwrap_err();
+    std::fs::remove_dir_all(&folder).unwrap();
+    assert!(error.tried[1].1.starts_with("invalid file: missing field `token`"));
+  }
+
+  #[test]
+  fn test_debug_hides_token() {
+    let credentials = Credentials::new("furr", "abc123");
+    assert!(!format!("{:?}", credentials).contains("abc123"));
+  }
+}
@@ -0,0 +1,338 @@
+//! Concurrent downloads of many releases, with progress events.
+//!
+//! A [`Downloader`] runs a bounded number of downloads at the same time, optionally sharing
+//! a bandwidth limit, and reports what happens through [`DownloadEvent`]s that a CLI or any
+//! other UI can render.
+
+use crate::{
+  error::Error,
+  retry::RateLimiter,
+  model::{
+    context::Context,
+    fmod::{FModFull, FModRelease},
+  },
+};
+use futures::{stream, StreamExt};
+use std::{
+  path::{Path, PathBuf},
+  sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::instrument;
+use url::Url;
+
+/// How many requests are sent at the same time when nothing else is configured.
+pub const DEFAULT_PARALLELISM: usize = 8;
+
+/// Something that happened during a batch of downloads.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadEvent {
+  /// A download started, resuming after `resumed` bytes left by an interrupted download.
+  Started {
+    file_name: String,
+    resumed: u64,
+    size: Option<u64>,
+  },
+  /// Bytes of a file were written to disk.
+  Progress {
+    file_name: String,
+    downloaded: u64,
+    size: Option<u64>,
+  },
+  /// A file was downloaded and its SHA1 matches.
+  Finished { file_name: String, path: PathBuf },
+  /// A file could not be downloaded.
+  Failed { file_name: String, error: String },
+  /// The progress of the whole batch, sent after every other event.
+  Overall {
+    finished: usize,
+    failed: usize,
+    files: usize,
+    downloaded: u64,
+  },
+}
+
+/// The state shared by every download of a batch.
+struct Batch {
+  events: Option<UnboundedSender<DownloadEvent>>,
+  limiter: Option<RateLimiter>,
+  files: usize,
+  finished: AtomicUsize,
+  failed: AtomicUsize,
+  downloaded: AtomicU64,
+}
+
+impl Batch {
+  fn send(&self, event: DownloadEvent) {
+    let Some(events) = &self.events else {
+      return;
+    };
+
+    // A closed channel only means that nobody listens anymore.
+    let _ = events.send(event);
+    let _ = events.send(DownloadEvent::Overall {
+      finished: self.finished.load(Ordering::Relaxed),
+      failed: self.failed.load(Ordering::Relaxed),
+      files: self.files,
+      downloaded: self.downloaded.load(Ordering::Relaxed),
+    });
+  }
+}
+
+/// Reports the progress of one download and applies the bandwidth limit of its batch.
+pub(crate) struct Transfer<'a> {
+  batch: Option<&'a Batch>,
+  file_name: String,
+  downloaded: u64,
+  size: Option<u64>,
+}
+
+impl<'a> Transfer<'a> {
+  /// Creates a transfer that is not part of a batch, and reports nothing.
+  pub(crate) fn new(file_name: &str) -> Self {
+    Self {
+      batch: None,
+      file_name: file_name.to_string(),
+      downloaded: 0,
+      size: None,
+    }
+  }
+
+  pub(crate) fn start(&mut self, resumed: u64, size: Option<u64>) {
+    self.downloaded = resumed;
+    self.size = size;
+    if let Some(batch) = self.batch {
+      batch.send(DownloadEvent::Started {
+        file_name: self.file_name.clone(),
+        resumed,
+        size,
+      });
+    }
+  }
+
+  pub(crate) async fn advance(&mut self, bytes: u64) {
+    self.downloaded += bytes;
+    let Some(batch) = self.batch else {
+      return;
+    };
+
+    if let Some(limiter) = &batch.limiter {
+      limiter.consume(bytes).await;
+    }
+    batch.downloaded.fetch_add(bytes, Ordering::Relaxed);
+    batch.send(DownloadEvent::Progress {
+      file_name: self.file_name.clone(),
+      downloaded: self.downloaded,
+      size: self.size,
+    });
+  }
+}
+
+/// Fetches metadata and downloads releases concurrently.
+pub struct Downloader<'a> {
+  ctx: &'a Context,
+  parallelism: usize,
+  bytes_per_second: Option<u64>,
+  events: Option<UnboundedSender<DownloadEvent>>,
+}
+
+impl<'a> Downloader<'a> {
+  /// Creates a downloader sending requests with the given context.
+  pub fn new(ctx: &'a Context) -> Self {
+    Self {
+      ctx,
+      parallelism: DEFAULT_PARALLELISM,
+      bytes_per_second: None,
+      events: None,
+    }
+  }
+
+  /// Sets how many requests may run at the same time, at least one.
+  pub fn parallelism(mut self, parallelism: usize) -> Self {
+    self.parallelism = parallelism.max(1);
+    self
+  }
+
+  /// Limits the total download rate of a batch, in bytes per second.
+  pub fn bandwidth_limit(mut self, bytes_per_second: u64) -> Self {
+    self.bytes_per_second = Some(bytes_per_second.max(1));
+    self
+  }
+
+  /// Sends the progress of every batch to a channel.
+  pub fn events(mut self, events: UnboundedSender<DownloadEvent>) -> Self {
+    self.events = Some(events);
+    self
+  }
+
+  /// Fetches the full information of many mods.
+  ///
+  /// # Returns
+  ///
+  /// * `Vec<Result<FModFull, Error>>` - Returns the information of each mod, in the order of `names`.
+  #[instrument(skip_all)]
+  pub async fn fetch(&self, names: &[&str]) -> Vec<Result<FModFull, Error>> {
+    stream::iter(names)
+      .map(|name| self.ctx.get_mod_info_full(name))
+      .buffered(self.parallelism)
+      .collect()
+      .await
+  }
+
+  /// Downloads many releases into a folder, see [`FModRelease::download_to`].
+  ///
+  /// A failed download does not stop the others.
+  ///
+  /// # Arguments
+  ///
+  /// * `releases` - The releases to download.
+  /// * `folder` - The folder to download the releases into, usually the mod folder.
+  ///
+  /// # Returns
+  ///
+  /// * `Vec<Result<PathBuf, Error>>` - Returns the path of each release, in the order of `releases`.
+  #[instrument(skip_all)]
+  pub async fn download(&self, releases: &[FModRelease], folder: &Path) -> Vec<Result<PathBuf, Error>> {
+    let jobs = releases
+      .iter()
+      .map(|release| {
+        let url = release.download_request_url(self.ctx).map_err(Error::InvalidUrl);
+        (release, url)
+      })
+      .collect();
+    self.download_from(jobs, folder).await
+  }
+
+  async fn download_from(
+    &self,
+    jobs: Vec<(&FModRelease, Result<Url, Error>)>,
+    folder: &Path,
+  ) -> Vec<Result<PathBuf, Error>> {
+    let batch = Batch {
+      events: self.events.clone(),
+      limiter: self
+        .bytes_per_second
+        .map(|bytes_per_second| RateLimiter::new(bytes_per_second as f64)),
+      files: jobs.len(),
+      finished: AtomicUsize::new(0),
+      failed: AtomicUsize::new(0),
+      downloaded: AtomicU64::new(0),
+    };
+
+    stream::iter(jobs)
+      .map(|(release, url)| {
+        let batch = &batch;
+        async move {
+          let mut transfer = Transfer {
+            batch: Some(batch),
+            ..Transfer::new(&release.file_name)
+          };
+          let result = match url {
+            Ok(url) => release.download_url_to(self.ctx, url, folder, &mut transfer).await,
+            Err(e) => Err(e),
+          };
+
+          let file_name = release.file_name.clone();
+          match &result {
+            Ok(path) => {
+              batch.finished.fetch_add(1, Ordering::Relaxed);
+              batch.send(DownloadEvent::Finished {
+                file_name,
+                path: path.clone(),
+              });
+            }
+            Err(e) => {
+              batch.failed.fetch_add(1, Ordering::Relaxed);
+              batch.send(DownloadEvent::Failed {
+                file_name,
+                error: e.to_string(),
+              });
+            }
+          }
+          result
+        }
+      })
+      .buffered(self.parallelism)
+      .collect()
+      .await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{self, Response};
+  use sha1::{Digest, Sha1};
+  use std::time::Duration;
+
+  const DATA: &[u8] = &[42; 100];
+
+  /// Serves `DATA` on a local port.
+  async fn serve() -> Url {
+    let server = test_util::serve(|_, _| Response::new("200 OK").body(DATA)).await;
+    server.url.join("download").unwrap()
+  }
+
+  fn release(file_name: &str, sha1: &str) -> FModRelease {
+    FModRelease {
+      file_name: file_name.to_string(),
+      sha1: sha1.to_string(),
+      ..Default::default()
+    }
+  }
+
+  #[tokio::test]
+  async fn test_download_batch() {
+    let url = serve().await;
+    let folder = std::env::temp_dir().join(format!("furrctorio-batch-{}", std::process::id()));
+    tokio::fs::create_dir_all(&folder).await.unwrap();
+
+    let mut hasher = Sha1::new();
+    hasher.update(DATA);
+    let sha1 = format!("{:x}", hasher.finalize());
+    let releases = [
+      release("a_1.0.0.zip", &sha1),
+      release("b_1.0.0.zip", "0000000000000000000000000000000000000000"),
+      release("c_1.0.0.zip", &sha1),
+    ];
+
+    let ctx = Context::anonymous().unwrap();
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let downloader = Downloader::new(&ctx)
+      .parallelism(2)
+      .bandwidth_limit(1000)
+      .events(sender);
+
+    let start = std::time::Instant::now();
+    let jobs = releases.iter().map(|r| (r, Ok(url.clone()))).collect();
+    let results = downloader.download_from(jobs, &folder).await;
+    let elapsed = start.elapsed();
+    tokio::fs::remove_dir_all(&folder).await.unwrap();
+
+    assert_eq!(results[0].as_ref().unwrap(), &folder.join("a_1.0.0.zip"));
+    assert!(matches!(results[1], Err(Error::ChecksumMismatch(_))));
+    assert!(results[2].is_ok());
+    // 300 bytes at 1000 bytes per second.
+    assert!(elapsed >= Duration::from_millis(250), "{:?}", elapsed);
+
+    drop(downloader);
+    let mut events = Vec::new();
+    while let Some(event) = receiver.recv().await {
+      events.push(event);
+    }
+    assert!(events.contains(&DownloadEvent::Progress {
+      file_name: "c_1.0.0.zip".to_string(),
+      downloaded: 100,
+      size: Some(100),
+    }));
+    assert_eq!(
+      events.last(),
+      Some(&DownloadEvent::Overall {
+        finished: 2,
+        failed: 1,
+        files: 3,
+        downloaded: 300,
+      })
+    );
+  }
+}
@@ -1,14 +1,215 @@
+use crate::{
+  credentials::CredentialError, model::dependency::DependencyError, resolver::ResolveError,
+  selector::NoCompatibleRelease,
+};
+use reqwest::{Response, StatusCode};
 use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use url::Url;
 
+/// Every error returned by the public functions of this crate.
 #[derive(Debug)]
 pub enum Error {
+  /// Data, like a version, a JSON answer or a binary file, could not be parsed.
   ParcingError(String),
+  /// Binary data ended before everything was read.
+  UnexpectedEnd {
+    offset: usize,
+    wanted: usize,
+    available: usize,
+  },
   InvalidPreffix(String),
+  /// A dependency of `info.json` could not be parsed.
+  InvalidDependency(DependencyError),
   IoError(std::io::Error),
-  APIError(APIError),
+  /// The request could not be sent, or its answer could not be read.
+  RequestError(reqwest::Error),
+  /// The server answered with an error status, with the message of the portal when it sent one.
+  ///
+  /// The credentials of download URLs are redacted from `url`.
+  HttpError {
+    status: StatusCode,
+    url: String,
+    api: Option<APIError>,
+  },
+  InvalidUrl(url::ParseError),
+  ChecksumMismatch(String),
+  /// The Context is offline and the answer to this URL is not in its cache.
+  NotCached(String),
+  LoginError(LoginError),
+  CredentialError(CredentialError),
+  /// The OS keyring could not be read or written.
+  KeyringError(keyring::Error),
+  /// The dependencies cannot be satisfied.
+  ResolveError(ResolveError),
+  /// No release of a mod matches its requirement, the version of the game and the policy.
+  NoCompatibleRelease(Box<NoCompatibleRelease>),
+  /// The mod is not part of the configuration.
+  ModNotFound(String),
+  /// The mod is already part of the configuration.
+  ModAlreadyPresent(String),
+  /// No mod folder is configured.
+  MissingModFolder,
+  /// The Factorio version is needed but not configured.
+  MissingFactorioVersion,
+  /// The configuration enables a mod of an expansion the game doesn't have.
+  MissingDlc(String),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Display for Error {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Error::ParcingError(message) => write!(f, "{}", message),
+      Error::UnexpectedEnd {
+        offset,
+        wanted,
+        available,
+      } => write!(
+        f,
+        "Unexpected end of data: {} bytes wanted at offset {}, {} available",
+        wanted, offset, available
+      ),
+      Error::InvalidPreffix(prefix) => write!(f, "Invalid dependency prefix '{}'", prefix),
+      Error::InvalidDependency(e) => write!(f, "{}", e),
+      Error::IoError(e) => write!(f, "IO error: {}", e),
+      Error::RequestError(e) => write!(f, "Request failed: {}", e),
+      Error::HttpError {
+        status,
+        url,
+        api: Some(api),
+      } => write!(f, "'{}' answered {}: {}", url, status, api),
+      Error::HttpError { status, url, .. } => write!(f, "'{}' answered {}", url, status),
+      Error::InvalidUrl(e) => write!(f, "Invalid URL: {}", e),
+      Error::ChecksumMismatch(file) => write!(f, "Checksum mismatch for '{}'", file),
+      Error::NotCached(url) => write!(f, "Offline, and '{}' is not cached", url),
+      Error::LoginError(e) => write!(f, "{}", e),
+      Error::CredentialError(e) => write!(f, "{}", e),
+      Error::KeyringError(e) => write!(f, "Keyring error: {}", e),
+      Error::ResolveError(e) => write!(f, "{}", e),
+      Error::NoCompatibleRelease(e) => write!(f, "{}", e),
+      Error::ModNotFound(name) => write!(f, "Mod '{}' is not in the configuration", name),
+      Error::ModAlreadyPresent(name) => write!(f, "Mod '{}' is already in the configuration", name),
+      Error::MissingModFolder => write!(f, "No mod folder is configured"),
+      Error::MissingFactorioVersion => write!(f, "No Factorio version is configured"),
+      Error::MissingDlc(name) => write!(
+        f,
+        "Mod '{}' comes with the Space Age expansion, which the configured game doesn't have",
+        name
+      ),
+    }
+  }
+}
+
+impl std::error::Error for Error {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Error::InvalidDependency(e) => Some(e),
+      Error::IoError(e) => Some(e),
+      Error::RequestError(e) => Some(e),
+      Error::InvalidUrl(e) => Some(e),
+      Error::LoginError(e) => Some(e),
+      Error::CredentialError(e) => Some(e),
+      Error::KeyringError(e) => Some(e),
+      Error::ResolveError(e) => Some(e),
+      Error::NoCompatibleRelease(e) => Some(e.as_ref()),
+      _ => None,
+    }
+  }
+}
+
+impl From<std::io::Error> for Error {
+  fn from(e: std::io::Error) -> Self {
+    Error::IoError(e)
+  }
+}
+
+impl From<reqwest::Error> for Error {
+  fn from(e: reqwest::Error) -> Self {
+    Error::RequestError(redact_error(e))
+  }
+}
+
+impl From<url::ParseError> for Error {
+  fn from(e: url::ParseError) -> Self {
+    Error::InvalidUrl(e)
+  }
+}
+
+impl From<serde_json::Error> for Error {
+  fn from(e: serde_json::Error) -> Self {
+    Error::ParcingError(format!("Invalid JSON: {}", e))
+  }
+}
+
+impl From<LoginError> for Error {
+  fn from(e: LoginError) -> Self {
+    Error::LoginError(e)
+  }
+}
+
+impl From<DependencyError> for Error {
+  fn from(e: DependencyError) -> Self {
+    Error::InvalidDependency(e)
+  }
+}
+
+impl From<CredentialError> for Error {
+  fn from(e: CredentialError) -> Self {
+    Error::CredentialError(e)
+  }
+}
+
+impl From<ResolveError> for Error {
+  fn from(e: ResolveError) -> Self {
+    Error::ResolveError(e)
+  }
+}
+
+/// Turns an error status into `Error::HttpError`, reading the message of the portal from the body.
+pub(crate) async fn error_for_status(response: Response) -> Result<Response, Error> {
+  let status = response.status();
+  if !status.is_client_error() && !status.is_server_error() {
+    return Ok(response);
+  }
+
+  let url = redact_url(response.url()).to_string();
+  let api = response
+    .text()
+    .await
+    .ok()
+    .and_then(|body| serde_json::from_str(&body).ok());
+  Err(Error::HttpError { status, url, api })
+}
+
+/// Returns a copy of a URL without the values of its `username` and `token` query pairs, which
+/// download URLs carry.
+pub(crate) fn redact_url(url: &Url) -> Url {
+  if !url.query_pairs().any(|(key, _)| key == "username" || key == "token") {
+    return url.clone();
+  }
+
+  let pairs: Vec<(String, String)> = url
+    .query_pairs()
+    .map(|(key, value)| match key.as_ref() {
+      "username" | "token" => (key.into_owned(), "***".to_string()),
+      _ => (key.into_owned(), value.into_owned()),
+    })
+    .collect();
+  let mut redacted = url.clone();
+  redacted.query_pairs_mut().clear().extend_pairs(&pairs);
+  redacted
+}
+
+/// Redacts the URL of a request error, see [`redact_url`].
+pub(crate) fn redact_error(mut e: reqwest::Error) -> reqwest::Error {
+  if let Some(url) = e.url_mut() {
+    *url = redact_url(url);
+  }
+  e
+}
+
+/// The body of the errors of the mod portal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct APIError {
   pub message: String,
 }
@@ -20,3 +221,38 @@ impl std::fmt::Display for APIError {
 }
 
 impl std::error::Error for APIError {}
+
+/// Why the authentication server refused to log in, or a token was rejected.
+#[derive(Debug)]
+pub enum LoginError {
+  /// The account uses email authentication, log in again with the code sent by email.
+  EmailCodeRequired,
+  /// The username or the password is wrong, with the message of the server.
+  InvalidCredentials(String),
+  /// The account does not own the game, which downloading mods requires.
+  MissingGameOwnership,
+  /// Too many attempts were made, wait before logging in again.
+  TooManyAttempts,
+  /// The token was rejected by the mod portal, it is wrong or has expired.
+  InvalidToken,
+  /// The server refused with an error this crate does not know.
+  Rejected { error: String, message: String },
+  /// The answer of the server could not be understood.
+  UnexpectedResponse(String),
+}
+
+impl std::fmt::Display for LoginError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      LoginError::EmailCodeRequired => write!(f, "The code sent by email is required to log in"),
+      LoginError::InvalidCredentials(message) => write!(f, "Invalid credentials: {}", message),
+      LoginError::MissingGameOwnership => write!(f, "The account does not own Factorio"),
+      LoginError::TooManyAttempts => write!(f, "Too many login attempts, try again later"),
+      LoginError::InvalidToken => write!(f, "The token is invalid or has expired, log in again"),
+      LoginError::Rejected { error, message } => write!(f, "Login refused ({}): {}", error, message),
+      LoginError::UnexpectedResponse(body) => write!(f, "Unexpected login response: {}", body),
+    }
+  }
+}
+
+impl std::error::Error for LoginError {}
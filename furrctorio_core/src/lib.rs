@@ -1,4 +1,13 @@
 pub mod model;
 pub mod error;
 pub mod prelude;
-pub mod constants;
\ No newline at end of file
+pub mod constants;
+pub mod resolver;
+pub mod selector;
+pub mod binary;
+pub mod download;
+pub mod retry;
+pub mod cache;
+pub mod credentials;
+#[cfg(test)]
+mod test_util;
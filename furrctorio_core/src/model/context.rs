@@ -1,24 +1,310 @@
-use crate::constants::FactorioVersions;
-use reqwest::Method;
-use serde::Deserialize;
+use crate::{
+  cache::{CachedEndpoint, MetadataCache},
+  credentials::{CredentialChain, Credentials},
+  error::{error_for_status, redact_error, Error, LoginError},
+  retry::{RateLimiter, RetryPolicy},
+};
+use futures::{
+  future::BoxFuture,
+  stream::{self, FuturesOrdered},
+  FutureExt, Stream, StreamExt,
+};
+use reqwest::{Certificate, Method, Proxy, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize};
 use serde_json::{from_value, Value};
-use tracing::{debug, instrument};
+use std::{
+  collections::{HashMap, VecDeque},
+  sync::Arc,
+  time::Duration,
+};
+use tracing::{debug, instrument, warn};
 use url::Url;
 use urlencoding::encode;
 
 use super::{
-  fmod::{FModFull, FModShort},
-  pagination::FModList,
+  fmod::{FModFull, FModRelease, FModShort},
+  pagination::{FModList, Pagination},
+  query::{ModQuery, PageSize},
+  version::GameVersion,
 };
 
+/// How many mod names are sent in one `namelist` request, to keep URLs short.
+pub const NAMELIST_BATCH_SIZE: usize = 100;
+
+/// The user agent sent with every request when nothing else is configured.
+pub const DEFAULT_USER_AGENT: &str = concat!("furrctorio/", env!("CARGO_PKG_VERSION"));
+
+/// The base URLs a Context sends its requests to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoints {
+  /// The authentication server, `https://auth.factorio.com` by default.
+  pub auth: Url,
+  /// The mods API, `https://mods.factorio.com/api` by default.
+  pub mods_api: Url,
+  /// The host the `download_url` of releases are relative to, `https://mods.factorio.com` by default.
+  pub download: Url,
+}
+
+impl Default for Endpoints {
+  fn default() -> Self {
+    Self {
+      auth: Url::parse("https://auth.factorio.com").unwrap(),
+      mods_api: Url::parse("https://mods.factorio.com/api").unwrap(),
+      download: Url::parse("https://mods.factorio.com").unwrap(),
+    }
+  }
+}
+
+impl Endpoints {
+  /// Appends a path to a base URL, keeping the path, the query and the fragment of the base.
+  ///
+  /// The path must already be percent-encoded, like the `download_url` of releases.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<Url, url::ParseError>` - Returns the URL, or `RelativeUrlWithCannotBeABaseBase` if the base can't have a path, like a `mailto:` URL.
+  pub(crate) fn join(base: &Url, path: &str) -> Result<Url, url::ParseError> {
+    if base.cannot_be_a_base() {
+      return Err(url::ParseError::RelativeUrlWithCannotBeABaseBase);
+    }
+
+    let mut url = base.clone();
+    url.set_path(&format!(
+      "{}/{}",
+      base.path().trim_end_matches('/'),
+      path.trim_start_matches('/')
+    ));
+    Ok(url)
+  }
+}
+
+/// The response of the authentication server.
 #[derive(Debug, Deserialize)]
+struct Login {
+  username: String,
+  token: String,
+}
+
+/// The response of the authentication server when it refuses to log in.
+#[derive(Debug, Deserialize)]
+struct LoginFailure {
+  error: String,
+  #[serde(default)]
+  message: String,
+}
+
+impl From<LoginFailure> for LoginError {
+  fn from(failure: LoginFailure) -> Self {
+    match failure.error.as_str() {
+      "email-authentication-required" => LoginError::EmailCodeRequired,
+      "login-failed" => LoginError::InvalidCredentials(failure.message),
+      "missing-game-ownership" => LoginError::MissingGameOwnership,
+      "too-many-attempts" => LoginError::TooManyAttempts,
+      _ => LoginError::Rejected {
+        error: failure.error,
+        message: failure.message,
+      },
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
 pub struct Context {
   pub username: String,
   pub(crate) token: String,
+  client: reqwest::Client,
+  endpoints: Endpoints,
+  retry: RetryPolicy,
+  /// Shared by the clones of the Context, so that they respect the rate together.
+  limiter: Option<Arc<RateLimiter>>,
+  cache: Option<MetadataCache>,
+  offline: bool,
 }
 
-impl Context {
-  /// Creates a new Context instance by sending a POST request to the Factorio authentication server.
+/// Configures the HTTP client and the endpoints of a [`Context`].
+///
+/// Every request of the built Context goes through the same client, so connections are reused.
+#[derive(Debug)]
+pub struct ContextBuilder {
+  endpoints: Endpoints,
+  user_agent: String,
+  timeout: Option<Duration>,
+  connect_timeout: Option<Duration>,
+  proxy: Option<Proxy>,
+  certificates: Vec<Certificate>,
+  credentials: Option<(String, String)>,
+  retry: RetryPolicy,
+  requests_per_second: Option<f64>,
+  cache: Option<MetadataCache>,
+  offline: bool,
+}
+
+impl Default for ContextBuilder {
+  fn default() -> Self {
+    Self {
+      endpoints: Endpoints::default(),
+      user_agent: DEFAULT_USER_AGENT.to_string(),
+      timeout: None,
+      connect_timeout: None,
+      proxy: None,
+      certificates: Vec::new(),
+      credentials: None,
+      retry: RetryPolicy::default(),
+      requests_per_second: None,
+      cache: None,
+      offline: false,
+    }
+  }
+}
+
+impl ContextBuilder {
+  /// Sets every endpoint at once.
+  pub fn endpoints(mut self, endpoints: Endpoints) -> Self {
+    self.endpoints = endpoints;
+    self
+  }
+
+  pub fn auth_url(mut self, url: Url) -> Self {
+    self.endpoints.auth = url;
+    self
+  }
+
+  pub fn mods_api_url(mut self, url: Url) -> Self {
+    self.endpoints.mods_api = url;
+    self
+  }
+
+  pub fn download_url(mut self, url: Url) -> Self {
+    self.endpoints.download = url;
+    self
+  }
+
+  /// Points every endpoint at a mirror of the portal, with the API under `/api`.
+  ///
+  /// Authentication still goes to the auth endpoint, which is left untouched.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<Self, Error>` - Returns the builder, or `Error::InvalidUrl` if the URL can't have a path.
+  pub fn portal_url(mut self, url: Url) -> Result<Self, Error> {
+    self.endpoints.mods_api = Endpoints::join(&url, "api")?;
+    self.endpoints.download = url;
+    Ok(self)
+  }
+
+  pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+    self.user_agent = user_agent.into();
+    self
+  }
+
+  /// Sets the timeout of whole requests, from connecting to reading the body.
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = Some(timeout);
+    self
+  }
+
+  pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+    self.connect_timeout = Some(timeout);
+    self
+  }
+
+  /// Sends every request through a proxy, instead of the proxy of the environment.
+  pub fn proxy(mut self, proxy: Proxy) -> Self {
+    self.proxy = Some(proxy);
+    self
+  }
+
+  /// Trusts an extra certificate authority, like the one of a local mirror.
+  pub fn add_root_certificate(mut self, certificate: Certificate) -> Self {
+    self.certificates.push(certificate);
+    self
+  }
+
+  /// Authenticates the requests with a username and a token, see [`Context::new_from_env`].
+  pub fn credentials(mut self, username: impl Into<String>, token: impl Into<String>) -> Self {
+    self.credentials = Some((username.into(), token.into()));
+    self
+  }
+
+  /// Sets when failed requests are sent again, see [`RetryPolicy`].
+  pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+    self.retry = retry;
+    self
+  }
+
+  /// Limits how many requests are sent per second, retries included.
+  pub fn requests_per_second(mut self, requests_per_second: f64) -> Self {
+    self.requests_per_second = Some(requests_per_second).filter(|rate| *rate > 0.0);
+    self
+  }
+
+  /// Keeps the answers of the portal on disk, see [`MetadataCache`].
+  pub fn cache(mut self, cache: MetadataCache) -> Self {
+    self.cache = Some(cache);
+    self
+  }
+
+  /// Answers only from the cache, without sending any request to the portal.
+  ///
+  /// Requests whose answer is not cached fail with `Error::NotCached`.
+  pub fn offline(mut self, offline: bool) -> Self {
+    self.offline = offline;
+    self
+  }
+
+  /// Authenticates the requests with the first credentials found by a chain.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<Self, Error>` - Returns the builder, or `Error::CredentialError` listing every source tried.
+  pub fn credentials_from(self, chain: &CredentialChain) -> Result<Self, Error> {
+    let (credentials, _) = chain.resolve()?;
+    Ok(self.credentials(credentials.username, credentials.token))
+  }
+
+  fn client(&self) -> Result<reqwest::Client, reqwest::Error> {
+    let mut builder = reqwest::Client::builder().user_agent(&self.user_agent);
+    if let Some(timeout) = self.timeout {
+      builder = builder.timeout(timeout);
+    }
+    if let Some(timeout) = self.connect_timeout {
+      builder = builder.connect_timeout(timeout);
+    }
+    if let Some(proxy) = &self.proxy {
+      builder = builder.proxy(proxy.clone());
+    }
+    for certificate in &self.certificates {
+      builder = builder.add_root_certificate(certificate.clone());
+    }
+    builder.build()
+  }
+
+  /// Creates the Context, without credentials unless [`ContextBuilder::credentials`] was called.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<Context, Error>` - Returns the Context or `Error::RequestError` if the HTTP client cannot be created.
+  pub fn build(self) -> Result<Context, Error> {
+    let client = self.client()?;
+    let (username, token) = self.credentials.unwrap_or_default();
+    Ok(Context {
+      username,
+      token,
+      client,
+      endpoints: self.endpoints,
+      retry: self.retry,
+      limiter: self
+        .requests_per_second
+        .map(|rate| Arc::new(RateLimiter::new(rate))),
+      cache: self.cache,
+      offline: self.offline,
+    })
+  }
+
+  /// Creates the Context by logging in to the authentication server.
+  ///
+  /// Accounts with email authentication first fail with `LoginError::EmailCodeRequired`, the
+  /// login is then sent again with the code the server emailed.
   ///
   /// # Arguments
   ///
@@ -28,47 +314,85 @@ impl Context {
   ///
   /// # Returns
   ///
-  /// * `Result<Self, reqwest::Error>` - Returns a Result containing the created Context instance or a reqwest::Error.
-  #[instrument]
-  pub async fn new(
+  /// * `Result<Context, Error>` - Returns a Result containing the created Context instance, or `Error::LoginError` with the reason the server refused.
+  #[instrument(skip(self, password))]
+  pub async fn login(
+    self,
     username: String,
     password: String,
     email_code: Option<String>,
-  ) -> Result<Self, reqwest::Error> {
-    let req_url = Url::parse_with_params(
-      "https://auth.factorio.com/api-login",
-      &[
-        ("username", encode(&username).to_string().as_str()),
-        ("password", encode(&password).to_string().as_str()),
-        ("api_version", "4"),
-        ("require_game_ownership", "true"),
-      ],
-    )
-    .unwrap();
-    debug!("{}", req_url);
-
-    let req = reqwest::Client::new().request(Method::POST, req_url);
+  ) -> Result<Context, Error> {
+    let mut ctx = self.build()?;
+    let mut form = vec![
+      ("username", username.clone()),
+      ("password", password),
+      ("api_version", "4".to_string()),
+      ("require_game_ownership", "true".to_string()),
+    ];
+    if let Some(code) = email_code {
+      form.push(("email_authentication_code", code));
+    }
+    debug!("{}", ctx.endpoints.auth);
 
-    let code: Value = if let Some(code) = email_code {
-      req.header("email_code", code)
-    } else {
-      req
+    // Sent as a form so that the password never ends up in a logged URL.
+    let req = ctx
+      .client
+      .post(Endpoints::join(&ctx.endpoints.auth, "api-login")?)
+      .form(&form);
+    let response = ctx.send(req).await?;
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS {
+      return Err(LoginError::TooManyAttempts.into());
     }
-    .send()
-    .await?
-    .json()
-    .await?;
-
-    if let Ok(token) = from_value::<Vec<String>>(code.clone()) {
-      return Ok(Context {
-        username,
-        token: token.first().unwrap().clone(),
-      });
-    } else if let Ok(ctx) = from_value::<Context>(code.clone()) {
-      return Ok(ctx);
+    let code: Value = response.json().await?;
+
+    (ctx.username, ctx.token) = if let Ok(login) = from_value::<Login>(code.clone()) {
+      (login.username, login.token)
+    } else if let Some(token) = from_value::<Vec<String>>(code.clone())
+      .ok()
+      .and_then(|tokens| tokens.into_iter().next())
+    {
+      (username, token)
+    } else if let Ok(failure) = from_value::<LoginFailure>(code.clone()) {
+      return Err(LoginError::from(failure).into());
     } else {
-      panic!("Coud not login.")
+      return Err(LoginError::UnexpectedResponse(format!("{}: {}", status, code)).into());
+    };
+
+    Ok(ctx)
+  }
+}
+
+impl Context {
+  /// Creates a builder to configure the HTTP client and the endpoints of a Context.
+  pub fn builder() -> ContextBuilder {
+    ContextBuilder::default()
+  }
+
+  /// Creates a new Context instance by sending a POST request to the Factorio authentication server.
+  ///
+  /// The credentials are then saved to the OS keyring, so that [`CredentialChain::standard`]
+  /// finds them next time. Failing to save them is only logged.
+  ///
+  /// # Arguments
+  ///
+  /// * `username` - The username for authentication.
+  /// * `password` - The password for authentication.
+  /// * `email_code` - The email authentication code.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<Self, Error>` - Returns a Result containing the created Context instance, or `Error::LoginError` with the reason the server refused.
+  pub async fn new(
+    username: String,
+    password: String,
+    email_code: Option<String>,
+  ) -> Result<Self, Error> {
+    let ctx = Self::builder().login(username, password, email_code).await?;
+    if let Err(e) = ctx.credentials().save_to_keyring() {
+      warn!("Could not save the credentials to the keyring: {}", e);
     }
+    Ok(ctx)
   }
 
   /// Creates a new Context instance from environment variables.
@@ -76,26 +400,127 @@ impl Context {
   /// This function retrieves the Factorio username and token from the environment variables
   /// "FACTORIO_USERNAME" and "FACTORIO_TOKEN" respectively, and uses them to create a new Context instance.
   ///
-  /// # Panics
+  /// # Returns
+  ///
+  /// * `Result<Self, Error>` - Returns a new Context instance, or `Error::CredentialError` if a variable is not set.
+  pub fn new_from_env() -> Result<Self, Error> {
+    Self::builder()
+      .credentials_from(&CredentialChain::new().environment())?
+      .build()
+  }
+
+  /// Creates a Context without credentials.
   ///
-  /// This function will panic if either the "FACTORIO_USERNAME" or "FACTORIO_TOKEN" environment variables
-  /// are not set.
+  /// Such a Context can only be used for requests that do not need authentication,
+  /// like fetching mod information. Downloads will be rejected by the server.
   ///
   /// # Returns
   ///
-  /// * `Context` - Returns a new Context instance.
-  pub fn new_from_env() -> Self {
-    Context {
-      // Retrieve the Factorio username from the environment variable "FACTORIO_USERNAME".
-      // If the environment variable is not set, this will panic.
-      username: std::env::var("FACTORIO_USERNAME")
-        .expect("FACTORIO_USERNAME must be set in the environment"),
-
-      // Retrieve the Factorio token from the environment variable "FACTORIO_TOKEN".
-      // If the environment variable is not set, this will panic.
-      token: std::env::var("FACTORIO_TOKEN")
-        .expect("FACTORIO_TOKEN must be set in the environment"),
+  /// * `Result<Self, Error>` - Returns a new Context instance with an empty username and token, or `Error::RequestError` if the HTTP client cannot be created.
+  pub fn anonymous() -> Result<Self, Error> {
+    Self::builder().build()
+  }
+
+  /// Returns the username and the token the requests are authenticated with.
+  pub fn credentials(&self) -> Credentials {
+    Credentials::new(&self.username, &self.token)
+  }
+
+  /// Checks that the mod portal accepts the token, before starting long downloads with it.
+  ///
+  /// The portal has no endpoint to check a token, so this starts downloading a release and
+  /// stops after the headers: an invalid or expired token is sent to the login page instead.
+  /// An offline Context does not download anything, so its token is not checked.
+  ///
+  /// # Arguments
+  ///
+  /// * `release` - Any release, usually the first one about to be downloaded.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<(), Error>` - Returns `LoginError::InvalidToken` if the token is rejected.
+  #[instrument(skip_all, fields(username = %self.username))]
+  pub async fn check_token(&self, release: &FModRelease) -> Result<(), Error> {
+    if self.offline {
+      return Ok(());
     }
+    if self.username.is_empty() || self.token.is_empty() {
+      return Err(LoginError::InvalidToken.into());
+    }
+
+    let response = self
+      .send(self.client.get(release.download_request_url(self)?))
+      .await?;
+    if response.url().path().contains("login")
+      || matches!(response.status(), StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+    {
+      return Err(LoginError::InvalidToken.into());
+    }
+    error_for_status(response).await?;
+    Ok(())
+  }
+
+  /// Returns the base URLs the requests are sent to.
+  pub fn endpoints(&self) -> &Endpoints {
+    &self.endpoints
+  }
+
+  /// Returns the HTTP client shared by every request of this Context.
+  pub(crate) fn client(&self) -> &reqwest::Client {
+    &self.client
+  }
+
+  /// Sends a request through the retry policy and the rate limiter of this Context.
+  pub(crate) async fn send(
+    &self,
+    request: reqwest::RequestBuilder,
+  ) -> Result<reqwest::Response, reqwest::Error> {
+    self
+      .retry
+      .send(request, self.limiter.as_deref())
+      .await
+      .map_err(redact_error)
+  }
+
+  /// Returns true if this Context only answers from its cache.
+  pub fn is_offline(&self) -> bool {
+    self.offline
+  }
+
+  /// Sends a GET request to the portal and parses the JSON answer, going through the cache.
+  ///
+  /// # Arguments
+  ///
+  /// * `endpoint` - The kind of request, which sets how long its answer is cached.
+  /// * `request` - The request, which must not carry credentials.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<T, Error>` - Returns the answer, or `Error::NotCached` when offline and nothing is cached.
+  async fn get_json<T: DeserializeOwned>(
+    &self,
+    endpoint: CachedEndpoint,
+    request: reqwest::RequestBuilder,
+  ) -> Result<T, Error> {
+    let body = match &self.cache {
+      Some(cache) => cache.fetch(self, endpoint, request, self.offline).await?,
+      None if self.offline => {
+        let url = request.build().map_err(Error::RequestError)?.url().to_string();
+        return Err(Error::NotCached(url));
+      }
+      None => {
+        let response = error_for_status(self.send(request).await?).await?;
+        response.text().await?
+      }
+    };
+
+    serde_json::from_str(&body)
+      .map_err(|e| Error::ParcingError(format!("Invalid answer from the portal: {}", e)))
+  }
+
+  /// Returns the URL of a path of the mods API.
+  fn api_url(&self, path: &str) -> Result<Url, Error> {
+    Ok(Endpoints::join(&self.endpoints.mods_api, path)?)
   }
 
   /// Creates a new request builder with the specified method and URL.
@@ -108,14 +533,14 @@ impl Context {
   ///
   /// # Returns
   ///
-  /// * `Result<reqwest::RequestBuilder, url::ParseError>` - Returns a Result containing the request builder or a url::ParseError.
+  /// * `Result<reqwest::RequestBuilder, Error>` - Returns a Result containing the request builder or `Error::InvalidUrl`.
   fn get_request(
     &self,
     method: Method,
     url: &str,
     auth: bool,
     options: Option<Vec<(&str, &str)>>,
-  ) -> Result<reqwest::RequestBuilder, url::ParseError> {
+  ) -> Result<reqwest::RequestBuilder, Error> {
     let req_url = if auth {
       // If authentication is required, parse the URL with username and token as parameters.
       let mut parms = vec![
@@ -136,8 +561,8 @@ impl Context {
       Url::parse(url)?
     };
 
-    // Create a new request builder with the specified method and URL.
-    let base = reqwest::Client::new().request(method, req_url);
+    // Create a new request builder with the specified method and URL, on the shared client.
+    let base = self.client.request(method, req_url);
 
     // Return the request builder.
     Ok(base)
@@ -151,20 +576,15 @@ impl Context {
   ///
   /// # Returns
   ///
-  /// * `Result<FModShort, reqwest::Error>` - Returns a Result containing the short mod information or a reqwest::Error.
-  pub async fn get_mod_info(&self, mod_name: &str) -> Result<FModShort, reqwest::Error> {
-    self
-      .get_request(
-        Method::GET,
-        &format!("https://mods.factorio.com/api/mods/{}", encode(mod_name)),
-        false,
-        None,
-      )
-      .unwrap()
-      .send()
-      .await?
-      .json()
-      .await
+  /// * `Result<FModShort, Error>` - Returns a Result containing the short mod information or an Error.
+  pub async fn get_mod_info(&self, mod_name: &str) -> Result<FModShort, Error> {
+    let request = self.get_request(
+      Method::GET,
+      self.api_url(&format!("mods/{}", encode(mod_name)))?.as_str(),
+      false,
+      None,
+    )?;
+    self.get_json(CachedEndpoint::Mod, request).await
   }
 
   /// Fetches full information about a mod from the Factorio mods server.
@@ -175,26 +595,140 @@ impl Context {
   ///
   /// # Returns
   ///
-  /// * `Result<FModFull, reqwest::Error>` - Returns a Result containing the full mod information or a reqwest::Error.
-  pub async fn get_mod_info_full(&self, mod_name: &str) -> Result<FModFull, reqwest::Error> {
+  /// * `Result<FModFull, Error>` - Returns a Result containing the full mod information or an Error.
+  pub async fn get_mod_info_full(&self, mod_name: &str) -> Result<FModFull, Error> {
+    let request = self.get_request(
+      Method::GET,
+      self.api_url(&format!("mods/{}/full", encode(mod_name)))?.as_str(),
+      false,
+      None,
+    )?;
+    self.get_json(CachedEndpoint::ModFull, request).await
+  }
+
+  /// Fetches short information about many mods, in as few requests as possible.
+  ///
+  /// The mods are requested through the `namelist` parameter of the mod list, by batches of
+  /// [`NAMELIST_BATCH_SIZE`] names. The returned mods have all of their releases, but no
+  /// `latest_release`. Mods that do not exist on the portal are left out of the map.
+  ///
+  /// # Arguments
+  ///
+  /// * `names` - The names of the mods.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<HashMap<String, FModShort>, Error>` - Returns the mods by name or an Error.
+  #[instrument(skip_all, fields(count = names.len()))]
+  pub async fn get_mods_by_name(
+    &self,
+    names: &[&str],
+  ) -> Result<HashMap<String, FModShort>, Error> {
+    let mut mods = HashMap::new();
+    for batch in names.chunks(NAMELIST_BATCH_SIZE) {
+      let list: FModList = self
+        .get_json(CachedEndpoint::Listing, self.namelist_request(batch)?)
+        .await?;
+      debug!("Found {} of {} mods", list.results.len(), batch.len());
+      mods.extend(list.results.into_iter().map(|m| (m.name.clone(), m)));
+    }
+    Ok(mods)
+  }
+
+  fn namelist_request(&self, names: &[&str]) -> Result<reqwest::RequestBuilder, Error> {
+    self.search_request(
+      &ModQuery::new()
+        .namelist(names.iter().copied())
+        .page_size(PageSize::Max),
+    )
+  }
+
+  /// Fetches a page of the mod list from the Factorio mods server.
+  ///
+  /// # Arguments
+  ///
+  /// * `query` - The sorting, filters and page of the list.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<FModList, Error>` - Returns a Result containing the list of mods or an Error.
+  #[instrument(skip(self))]
+  pub async fn search(&self, query: &ModQuery) -> Result<FModList, Error> {
     self
-      .get_request(
-        Method::GET,
-        &format!(
-          "https://mods.factorio.com/api/mods/{}/full",
-          encode(mod_name)
-        ),
-        false,
-        None,
-      )
-      .unwrap()
-      .send()
-      .await
-      .unwrap()
-      .json()
+      .get_json(CachedEndpoint::Listing, self.search_request(query)?)
       .await
   }
 
+  /// Fetches every mod of a listing, page after page.
+  ///
+  /// Each page is found from the `next` link of the page before it, and up to `prefetch`
+  /// pages are requested ahead of the one being read. The requests made ahead are checked
+  /// against every page received, and made again if the number of pages or the links changed
+  /// meanwhile. Mods are returned in the order of the pages, and the stream ends after the page
+  /// without a `next` link. A page that cannot be fetched becomes an error item, the following
+  /// pages are still returned.
+  ///
+  /// # Arguments
+  ///
+  /// * `query` - The sorting and filters of the listing, and the page to start from.
+  /// * `prefetch` - How many pages are requested at the same time, at least one.
+  ///
+  /// # Returns
+  ///
+  /// * `impl Stream<Item = Result<FModShort, Error>>` - Returns the mods of every page.
+  pub fn search_all<'a>(
+    &'a self,
+    query: &ModQuery,
+    prefetch: usize,
+  ) -> impl Stream<Item = Result<FModShort, Error>> + 'a {
+    let first = self.search_request(query);
+    stream::once(async move { self.get_json::<FModList>(CachedEndpoint::Listing, first?).await })
+      .flat_map(move |first| match first {
+        Ok(first) => self.pages_after(first, prefetch).left_stream(),
+        Err(e) => stream::iter([Err(e)]).right_stream(),
+      })
+  }
+
+  /// Returns the mods of a page followed by the mods of the pages after it.
+  fn pages_after(
+    &self,
+    first: FModList,
+    prefetch: usize,
+  ) -> impl Stream<Item = Result<FModShort, Error>> + '_ {
+    let walk = PageWalk {
+      position: first.pagination.page,
+      last: first.pagination,
+      requested: VecDeque::new(),
+      pending: FuturesOrdered::new(),
+    };
+
+    let rest = stream::unfold(walk, move |mut walk| async move {
+      walk.request(self, prefetch.max(1));
+      let page = walk.pending.next().await?;
+      let (position, _) = walk.requested.pop_front()?;
+      walk.position = position;
+      if let Ok(page) = &page {
+        walk.received(&page.pagination);
+      }
+      Some((page, walk))
+    })
+    .flat_map(|page| match page {
+      Ok(page) => stream::iter(page.results).map(Ok).left_stream(),
+      Err(e) => stream::iter([Err(e)]).right_stream(),
+    });
+    stream::iter(first.results).map(Ok).chain(rest)
+  }
+
+  fn search_request(&self, query: &ModQuery) -> Result<reqwest::RequestBuilder, Error> {
+    let params = query.params();
+    self.get_request(
+      Method::GET,
+      self.api_url("mods")?.as_str(),
+      false,
+      Some(params.iter().map(|(k, v)| (*k, v.as_str())).collect()),
+    )
+  }
+
   /// Fetches a list of mods from the Factorio mods server.
   ///
   /// # Arguments
@@ -204,40 +738,68 @@ impl Context {
   ///
   /// # Returns
   ///
-  /// * `Result<FModList, reqwest::Error>` - Returns a Result containing the list of mods or a reqwest::Error.
+  /// * `Result<FModList, Error>` - Returns a Result containing the list of mods or an Error.
   pub async fn get_mods(
     &self,
     page: usize,
-    factorio_version: Option<FactorioVersions>,
-  ) -> Result<FModList, reqwest::Error> {
-    // Convert the Factorio version to a string if it is provided.
-    let fv_str = factorio_version.map(|fv| fv.to_string());
-
-    // Create a vector of parameters for the request.
-    let mut parms = fv_str
-      .as_ref()
-      .map(|txt| vec![("factorio_version", txt.as_str())])
-      .unwrap_or_default();
-
-    // Convert the page number to a string and add it to the parameters.
-    let page_str = page.to_string();
-    parms.push(("page", page_str.as_str()));
-
-    // Create a new request builder with the specified method, URL, and parameters.
-    self
-      .get_request(
-        Method::GET,
-        "https://mods.factorio.com/api/mods",
-        false,
-        Some(parms),
-      )
-      .unwrap()
-      // Send the request and await the response.
-      .send()
-      .await?
-      // Parse the response as JSON and await the result.
-      .json()
-      .await
+    factorio_version: Option<GameVersion>,
+  ) -> Result<FModList, Error> {
+    let query = ModQuery::new().page(page);
+    let query = match factorio_version {
+      Some(fv) => query.factorio_version(fv),
+      None => query,
+    };
+    self.search(&query).await
+  }
+}
+
+/// The progress of [`Context::pages_after`] through a listing.
+struct PageWalk<'a> {
+  /// The number of the last page returned, or failed.
+  position: usize,
+  /// The pagination of the last page received.
+  last: Pagination,
+  /// The number and URL of the pages requested ahead, in order.
+  requested: VecDeque<(usize, Url)>,
+  /// The requests of the pages in `requested`.
+  pending: FuturesOrdered<BoxFuture<'a, Result<FModList, Error>>>,
+}
+
+impl<'a> PageWalk<'a> {
+  /// Requests the pages after the last one requested, until `prefetch` pages are pending.
+  fn request(&mut self, ctx: &'a Context, prefetch: usize) {
+    while self.pending.len() < prefetch {
+      let page = self.requested.back().map_or(self.position, |(page, _)| *page) + 1;
+      let Some(url) = self.last.page_url(page) else {
+        break;
+      };
+      self.requested.push_back((page, url.clone()));
+      self.pending.push_back(
+        async move {
+          let request = ctx.get_request(Method::GET, url.as_str(), false, None)?;
+          ctx.get_json::<FModList>(CachedEndpoint::Listing, request).await
+        }
+        .boxed(),
+      );
+    }
+  }
+
+  /// Records the pagination of a page received, and drops the pages requested ahead that no
+  /// longer match it.
+  fn received(&mut self, pagination: &Pagination) {
+    self.last = pagination.clone();
+    let stale = self
+      .requested
+      .iter()
+      .any(|(page, url)| self.last.page_url(*page).as_ref() != Some(url));
+    if stale {
+      debug!(
+        "Listing changed on page {} ({} pages), requesting the next pages again",
+        self.last.page, self.last.page_count
+      );
+      self.requested.clear();
+      self.pending = FuturesOrdered::new();
+    }
   }
 }
 
@@ -246,11 +808,16 @@ mod tests {
   use core::panic;
 
 use super::*;
+  use crate::model::{
+    pagination::{Pagination, PaginationLinks},
+    query::{SortField, SortOrder},
+  };
+  use crate::test_util::{serve, Response};
 
   #[tokio::test]
   async fn test_get_mods() {
     dotenv::dotenv().ok();
-    let ctx = Context::new_from_env();
+    let ctx = Context::new_from_env().unwrap();
     let mods = ctx.get_mods(1, None).await.unwrap();
 
     assert_eq!(mods.pagination.page, 1);
@@ -276,11 +843,196 @@ use super::*;
     assert!(mods.pagination.links.prev.is_none());
   }
 
+  #[test]
+  fn test_namelist_request() {
+    let request = Context::anonymous().unwrap()
+      .namelist_request(&["flib", "Squeak Through"])
+      .unwrap()
+      .build()
+      .unwrap();
+
+    assert_eq!(
+      request.url().as_str(),
+      "https://mods.factorio.com/api/mods?namelist=flib%2CSqueak+Through&page_size=max"
+    );
+  }
+
+  #[test]
+  fn test_search_request() {
+    let query = ModQuery::new()
+      .sort(SortField::UpdatedAt)
+      .sort_order(SortOrder::Desc)
+      .page_size(PageSize::Count(10))
+      .hide_deprecated(true)
+      .factorio_version(GameVersion::V1_1);
+    let request = Context::anonymous().unwrap().search_request(&query).unwrap().build().unwrap();
+
+    assert_eq!(
+      request.url().as_str(),
+      "https://mods.factorio.com/api/mods?page_size=10&sort=updated_at&sort_order=desc&hide_deprecated=true&version=1.1"
+    );
+  }
+
+  fn page(page: usize, page_count: usize, base: &str) -> FModList {
+    let url = |page: usize| Some(Url::parse(&format!("{}?sort=name&page={}", base, page)).unwrap());
+    FModList {
+      pagination: Pagination {
+        count: page_count * 2,
+        links: PaginationLinks {
+          first: None,
+          last: url(page_count),
+          next: if page < page_count { url(page + 1) } else { None },
+          prev: None,
+        },
+        page,
+        page_count,
+        page_size: 2,
+      },
+      results: (page * 2 - 1..=page * 2)
+        .map(|i| FModShort {
+          name: format!("mod-{}", i),
+          ..Default::default()
+        })
+        .collect(),
+    }
+  }
+
+  /// Serves the pages of a listing, and returns its URL.
+  async fn serve_pages(pages: fn(usize, &str) -> FModList) -> String {
+    let server = serve(move |_, request| {
+      let number = request.query("page").unwrap().parse().unwrap();
+      let mut base = request.url.clone();
+      base.set_query(None);
+      Response::json(&pages(number, base.as_str()))
+    })
+    .await;
+    server.url.join("api/mods").unwrap().to_string()
+  }
+
+  async fn walk(first: FModList, prefetch: usize) -> Vec<String> {
+    let ctx = Context::anonymous().unwrap();
+    ctx
+      .pages_after(first, prefetch)
+      .map(|m| m.unwrap().name)
+      .collect()
+      .await
+  }
+
+  #[tokio::test]
+  async fn test_pages_after() {
+    let base = serve_pages(|number, base| page(number, 3, base)).await;
+    assert_eq!(
+      walk(page(1, 3, &base), 2).await,
+      vec!["mod-1", "mod-2", "mod-3", "mod-4", "mod-5", "mod-6"]
+    );
+  }
+
+  #[tokio::test]
+  async fn test_pages_after_count_changed() {
+    // Mods were added after the first page was read.
+    let base = serve_pages(|number, base| page(number, 4, base)).await;
+    assert_eq!(
+      walk(page(1, 2, &base), 2).await,
+      vec!["mod-1", "mod-2", "mod-3", "mod-4", "mod-5", "mod-6", "mod-7", "mod-8"]
+    );
+
+    // Mods were removed after the first page was read, the pages requested ahead are dropped.
+    let base = serve_pages(|number, base| match number {
+      1 | 2 => page(number, 2, base),
+      _ => page(number, 4, base),
+    })
+    .await;
+    assert_eq!(walk(page(1, 4, &base), 3).await, vec!["mod-1", "mod-2", "mod-3", "mod-4"]);
+  }
+
+  #[test]
+  fn test_portal_url() {
+    let endpoints = |url: &str| {
+      Context::builder()
+        .portal_url(Url::parse(url).unwrap())
+        .unwrap()
+        .endpoints
+    };
+    assert_eq!(endpoints("https://mirror/").mods_api.as_str(), "https://mirror/api");
+    assert_eq!(endpoints("https://mirror/factorio/").mods_api.as_str(), "https://mirror/factorio/api");
+    assert_eq!(endpoints("https://mirror/?x=1#top").mods_api.as_str(), "https://mirror/api?x=1#top");
+
+    let release = FModRelease {
+      download_url: "/download/Squeak%20Through/5ecac7e4".to_string(),
+      ..Default::default()
+    };
+    let ctx = Context::builder()
+      .portal_url(Url::parse("https://mirror/?x=1").unwrap())
+      .unwrap()
+      .credentials("furr", "secret")
+      .build()
+      .unwrap();
+    assert_eq!(
+      release.download_request_url(&ctx).unwrap().as_str(),
+      "https://mirror/download/Squeak%20Through/5ecac7e4?x=1&username=furr&token=secret"
+    );
+
+    assert!(matches!(
+      Context::builder().portal_url(Url::parse("mailto:furr@example.com").unwrap()),
+      Err(Error::InvalidUrl(_))
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_builder() {
+    let server = serve(|_, request| {
+      Response::json(&FModShort {
+        name: request.url.path().trim_start_matches("/mirror/api/mods/").to_string(),
+        owner: request.header("user-agent").unwrap_or_default().to_string(),
+        ..Default::default()
+      })
+    })
+    .await;
+    let portal = server.url.join("mirror").unwrap();
+
+    let ctx = Context::builder()
+      .portal_url(portal.clone())
+      .unwrap()
+      .user_agent("furrctorio-test")
+      .timeout(Duration::from_secs(5))
+      .credentials("user", "secret")
+      .build()
+      .unwrap();
+    assert_eq!(ctx.endpoints().mods_api.as_str(), format!("{}/api", portal));
+    assert_eq!(ctx.endpoints().auth, Endpoints::default().auth);
+
+    let fmod = ctx.get_mod_info("flib").await.unwrap();
+    assert_eq!(fmod.name, "flib");
+    assert_eq!(fmod.owner, "furrctorio-test");
+
+    let release = crate::model::fmod::FModRelease {
+      download_url: "/download/flib/5ecac7e44d121d000cd77c76".to_string(),
+      ..Default::default()
+    };
+    assert_eq!(
+      release.download_request_url(&ctx).unwrap().as_str(),
+      format!("{}/download/flib/5ecac7e44d121d000cd77c76?username=user&token=secret", portal)
+    );
+  }
+
+  #[tokio::test]
+  async fn test_get_mods_by_name() {
+    let ctx = Context::anonymous().unwrap();
+    let mods = ctx
+      .get_mods_by_name(&["flib", "stdlib", "furrctorio-missing-mod"])
+      .await
+      .unwrap();
+
+    assert_eq!(mods.len(), 2);
+    assert!(!mods["flib"].releases.is_empty());
+    assert!(mods["stdlib"].latest_release.is_none());
+  }
+
   #[tokio::test]
   async fn test_get_mods_page() {
     dotenv::dotenv().ok();
-    let ctx = Context::new_from_env();
-    let mods = ctx.get_mods(69, Some(FactorioVersions::V0_18)).await.unwrap_or_else(|e| panic!("{:?}", e));
+    let ctx = Context::new_from_env().unwrap();
+    let mods = ctx.get_mods(69, Some(GameVersion::V0_18)).await.unwrap_or_else(|e| panic!("{:?}", e));
 
     assert_eq!(mods.pagination.page, 69);
     assert_eq!(
@@ -317,4 +1069,119 @@ use super::*;
       mods.pagination.links.prev.unwrap()
     );
   }
+
+  /// Answers each request with the next status and JSON body.
+  async fn serve_auth(responses: Vec<(&'static str, &'static str)>) -> Url {
+    let server = serve(move |index, _| {
+      let (status, body) = responses[index];
+      Response::new(status).json_body(body)
+    })
+    .await;
+    server.url
+  }
+
+  #[tokio::test]
+  async fn test_login() {
+    let auth = serve_auth(vec![
+      (
+        "401 Unauthorized",
+        r#"{"error": "email-authentication-required", "message": "Please check your email"}"#,
+      ),
+      ("200 OK", r#"{"username": "Furr", "token": "abc123"}"#),
+      ("401 Unauthorized", r#"{"error": "login-failed", "message": "Wrong password"}"#),
+      ("403 Forbidden", r#"{"error": "missing-game-ownership", "message": ""}"#),
+      ("400 Bad Request", r#"{"error": "unknown-error", "message": "Something"}"#),
+      ("200 OK", r#"{"unexpected": true}"#),
+      ("429 Too Many Requests", ""),
+    ])
+    .await;
+    let login = |code: Option<&str>| {
+      Context::builder()
+        .auth_url(auth.clone())
+        .retry_policy(RetryPolicy::none())
+        .login("furr".to_string(), "password".to_string(), code.map(str::to_string))
+    };
+
+    assert!(matches!(login(None).await, Err(Error::LoginError(LoginError::EmailCodeRequired))));
+    let ctx = login(Some("123456")).await.unwrap();
+    assert_eq!(ctx.credentials(), Credentials::new("Furr", "abc123"));
+    assert!(matches!(
+      login(None).await,
+      Err(Error::LoginError(LoginError::InvalidCredentials(message))) if message == "Wrong password"
+    ));
+    assert!(matches!(login(None).await, Err(Error::LoginError(LoginError::MissingGameOwnership))));
+    assert!(matches!(
+      login(None).await,
+      Err(Error::LoginError(LoginError::Rejected { error, .. })) if error == "unknown-error"
+    ));
+    assert!(matches!(login(None).await, Err(Error::LoginError(LoginError::UnexpectedResponse(_)))));
+    assert!(matches!(login(None).await, Err(Error::LoginError(LoginError::TooManyAttempts))));
+  }
+
+  #[tokio::test]
+  async fn test_http_error() {
+    let api = serve_auth(vec![
+      ("404 Not Found", r#"{"message": "Mod not found"}"#),
+      ("503 Service Unavailable", r#"{"message": "Under maintenance"}"#),
+    ])
+    .await;
+    let ctx = Context::builder()
+      .mods_api_url(api)
+      .retry_policy(RetryPolicy::none())
+      .build()
+      .unwrap();
+
+    let error = ctx.get_mod_info("missing").await.unwrap_err();
+    let Error::HttpError { status, api, .. } = &error else {
+      panic!("{}", error);
+    };
+    assert_eq!(*status, StatusCode::NOT_FOUND);
+    assert_eq!(api.as_ref().unwrap().message, "Mod not found");
+    assert!(error.to_string().ends_with("answered 404 Not Found: Mod not found"), "{}", error);
+
+    // A server error still failing after the last retry.
+    let error = ctx.get_mod_info("flib").await.unwrap_err();
+    assert!(
+      error.to_string().ends_with("answered 503 Service Unavailable: Under maintenance"),
+      "{}",
+      error
+    );
+  }
+
+  #[tokio::test]
+  async fn test_check_token() {
+    // The portal sends unknown tokens to its login page.
+    let server = serve(|_, request| {
+      if request.url.path() == "/login" || request.query("token").as_deref() == Some("good") {
+        Response::new("200 OK")
+      } else {
+        Response::new("302 Found").header("Location", "/login?next=/download")
+      }
+    })
+    .await;
+    let portal = server.url;
+
+    let release = FModRelease {
+      download_url: "/download/flib/5ecac7e44d121d000cd77c76".to_string(),
+      ..Default::default()
+    };
+    let context = |token: &str| {
+      Context::builder()
+        .portal_url(portal.clone())
+        .unwrap()
+        .credentials("furr", token)
+        .build()
+        .unwrap()
+    };
+
+    context("good").check_token(&release).await.unwrap();
+    assert!(matches!(
+      context("expired").check_token(&release).await,
+      Err(Error::LoginError(LoginError::InvalidToken))
+    ));
+    assert!(matches!(
+      Context::anonymous().unwrap().check_token(&release).await,
+      Err(Error::LoginError(LoginError::InvalidToken))
+    ));
+  }
 }
@@ -0,0 +1,605 @@
+//! The dependencies of `info.json`, like `? flib >= 0.12.0`.
+//!
+//! A dependency is an optional prefix, a mod name and an optional version constraint:
+//!
+//! ```text
+//! dependency = [prefix] name [operator version]
+//! prefix     = "!" | "?" | "(?)" | "~"
+//! operator   = "<" | "<=" | "=" | ">=" | ">"
+//! version    = number "." number ["." number]
+//! ```
+//!
+//! Spaces are optional between the parts, and names may contain spaces. The text a dependency
+//! was parsed from is kept, so that it is written back unchanged until one of its parts is set. Unlike the requirements of
+//! the configuration, a dependency has at most one comparison, which is all Factorio reads.
+
+use super::version::{FactorioVersion, VersionComparator, VersionOp};
+use crate::error::Error;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::{fmt::Display, ops::Range, str::FromStr};
+
+/// Represents a dependency for a Factorio mod.
+#[derive(Debug, Default, Clone)]
+pub struct FModDependecies {
+  /// The name of the dependency.
+  name: String,
+  /// The required version of the dependency.
+  required_version: Option<VersionComparator>,
+  /// The prefix for the dependency.
+  preffix: FModPreffix,
+  /// The parsed text, when it differs from the canonical form. Cleared by the setters.
+  text: Option<String>,
+}
+
+impl FModDependecies {
+  /// Creates a dependency, written in the canonical form.
+  pub fn new(
+    preffix: FModPreffix,
+    name: impl Into<String>,
+    required_version: Option<VersionComparator>,
+  ) -> Self {
+    Self {
+      name: name.into(),
+      required_version,
+      preffix,
+      text: None,
+    }
+  }
+
+  /// Returns the name of the dependency.
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// Returns the required version of the dependency.
+  pub fn required_version(&self) -> Option<&VersionComparator> {
+    self.required_version.as_ref()
+  }
+
+  /// Returns the prefix of the dependency.
+  pub fn preffix(&self) -> FModPreffix {
+    self.preffix
+  }
+
+  pub fn set_name(&mut self, name: impl Into<String>) {
+    self.name = name.into();
+    self.text = None;
+  }
+
+  pub fn set_required_version(&mut self, required_version: Option<VersionComparator>) {
+    self.required_version = required_version;
+    self.text = None;
+  }
+
+  pub fn set_preffix(&mut self, preffix: FModPreffix) {
+    self.preffix = preffix;
+    self.text = None;
+  }
+
+  /// Writes the dependency as `[prefix ]name[ operator version]`.
+  fn fmt_canonical(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.preffix != FModPreffix::Required {
+      write!(f, "{} ", self.preffix)?;
+    }
+    write!(f, "{}", self.name)?;
+
+    match &self.required_version {
+      Some(req) => write!(f, " {}", req),
+      None => Ok(()),
+    }
+  }
+}
+
+impl PartialEq for FModDependecies {
+  fn eq(&self, other: &Self) -> bool {
+    self.name == other.name
+      && self.required_version == other.required_version
+      && self.preffix == other.preffix
+  }
+}
+
+impl Eq for FModDependecies {}
+
+impl Display for FModDependecies {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match &self.text {
+      Some(text) => write!(f, "{}", text),
+      None => self.fmt_canonical(f),
+    }
+  }
+}
+
+impl<'de> Deserialize<'de> for FModDependecies {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let s = String::deserialize(deserializer)?;
+    FromStr::from_str(&s).map_err(|e: Error| de::Error::custom(e))
+  }
+}
+
+impl Serialize for FModDependecies {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: serde::Serializer,
+  {
+    serializer.serialize_str(&self.to_string())
+  }
+}
+
+impl FromStr for FModDependecies {
+  type Err = Error;
+
+  /// Parses a string into a FModDependecies.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut dependency = Parser::new(s).parse()?;
+    if dependency.to_string() != s {
+      dependency.text = Some(s.to_string());
+    }
+    Ok(dependency)
+  }
+}
+
+/// Represents the prefix for a Factorio mod dependency.
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq, Eq, Copy)]
+pub enum FModPreffix {
+  /// The dependency is required.
+  #[default]
+  Required,
+  /// The dependency is incompatible.
+  #[serde(rename = "!")]
+  Incompatible,
+  /// The dependency is optional.
+  #[serde(rename = "?")]
+  Optional,
+  /// The dependency is hidden optional.
+  #[serde(rename = "(?)")]
+  HiddenOptional,
+  /// The dependency is non-changing.
+  #[serde(rename = "~")]
+  NonChanging,
+}
+
+impl FromStr for FModPreffix {
+  type Err = Error;
+
+  /// Parses a string into a FModPreffix.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "!" => Ok(FModPreffix::Incompatible),
+      "?" => Ok(FModPreffix::Optional),
+      "(?)" => Ok(FModPreffix::HiddenOptional),
+      "~" => Ok(FModPreffix::NonChanging),
+      "" => Ok(FModPreffix::Required),
+      _ => Err(Error::InvalidPreffix(s.to_string())),
+    }
+  }
+}
+
+impl Display for FModPreffix {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      FModPreffix::Incompatible => write!(f, "!"),
+      FModPreffix::Optional => write!(f, "?"),
+      FModPreffix::HiddenOptional => write!(f, "(?)"),
+      FModPreffix::NonChanging => write!(f, "~"),
+      FModPreffix::Required => write!(f, ""),
+    }
+  }
+}
+
+/// What is wrong with a dependency string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencyErrorKind {
+  /// The string is empty or only has spaces.
+  Empty,
+  /// There is no mod name after the prefix.
+  MissingName,
+  /// A character that can't appear here, like a second prefix.
+  UnexpectedCharacter(char),
+  /// There is no version after the operator.
+  MissingVersion,
+  /// The version is not two or three numbers from 0 to 65535.
+  InvalidVersion(String),
+  /// Something follows the version.
+  TrailingText(String),
+}
+
+impl Display for DependencyErrorKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      DependencyErrorKind::Empty => write!(f, "empty dependency"),
+      DependencyErrorKind::MissingName => write!(f, "missing mod name"),
+      DependencyErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected '{}'", c),
+      DependencyErrorKind::MissingVersion => write!(f, "missing version after the operator"),
+      DependencyErrorKind::InvalidVersion(version) => write!(f, "invalid version '{}'", version),
+      DependencyErrorKind::TrailingText(text) => write!(f, "unexpected '{}' after the version", text),
+    }
+  }
+}
+
+/// A dependency string could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyError {
+  /// The string that was parsed.
+  pub input: String,
+  pub kind: DependencyErrorKind,
+  /// The bytes of the input the error is about, empty when something is missing.
+  pub span: Range<usize>,
+}
+
+impl Display for DependencyError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "Invalid dependency '{}': {} (bytes {}..{})",
+      self.input, self.kind, self.span.start, self.span.end
+    )
+  }
+}
+
+impl std::error::Error for DependencyError {}
+
+/// The characters that are part of the grammar, and so can't be part of a name.
+///
+/// `^` is not part of the grammar, but is rejected so that semver-style ranges aren't read as
+/// part of the name.
+const RESERVED: [char; 9] = ['!', '?', '~', '(', ')', '<', '>', '=', '^'];
+
+/// Reads a dependency string from left to right.
+struct Parser<'a> {
+  input: &'a str,
+  pos: usize,
+}
+
+impl<'a> Parser<'a> {
+  fn new(input: &'a str) -> Self {
+    Self { input, pos: 0 }
+  }
+
+  fn error(&self, kind: DependencyErrorKind, span: Range<usize>) -> DependencyError {
+    DependencyError {
+      input: self.input.to_string(),
+      kind,
+      span,
+    }
+  }
+
+  fn rest(&self) -> &'a str {
+    &self.input[self.pos..]
+  }
+
+  fn skip_spaces(&mut self) {
+    let rest = self.rest();
+    self.pos += rest.len() - rest.trim_start().len();
+  }
+
+  /// Consumes `token` if the rest of the input starts with it.
+  fn eat(&mut self, token: &str) -> bool {
+    let found = self.rest().starts_with(token);
+    if found {
+      self.pos += token.len();
+    }
+    found
+  }
+
+  fn parse(mut self) -> Result<FModDependecies, DependencyError> {
+    if self.input.trim().is_empty() {
+      return Err(self.error(DependencyErrorKind::Empty, 0..self.input.len()));
+    }
+
+    self.skip_spaces();
+    let preffix = self.prefix();
+    self.skip_spaces();
+    let name = self.name()?;
+    let required_version = match self.operator() {
+      Some(op) => {
+        self.skip_spaces();
+        Some(self.version(op)?)
+      }
+      None => None,
+    };
+
+    self.skip_spaces();
+    if !self.rest().is_empty() {
+      let kind = DependencyErrorKind::TrailingText(self.rest().to_string());
+      return Err(self.error(kind, self.pos..self.input.len()));
+    }
+
+    Ok(FModDependecies::new(preffix, name, required_version))
+  }
+
+  fn prefix(&mut self) -> FModPreffix {
+    if self.eat("(?)") {
+      FModPreffix::HiddenOptional
+    } else if self.eat("!") {
+      FModPreffix::Incompatible
+    } else if self.eat("?") {
+      FModPreffix::Optional
+    } else if self.eat("~") {
+      FModPreffix::NonChanging
+    } else {
+      FModPreffix::Required
+    }
+  }
+
+  /// Reads the name up to the operator or the end, without its trailing spaces.
+  fn name(&mut self) -> Result<String, DependencyError> {
+    let start = self.pos;
+    for (offset, c) in self.rest().char_indices() {
+      if matches!(c, '<' | '>' | '=') {
+        break;
+      }
+      if RESERVED.contains(&c) || (c.is_control() && !c.is_whitespace()) {
+        let at = start + offset;
+        return Err(self.error(
+          DependencyErrorKind::UnexpectedCharacter(c),
+          at..at + c.len_utf8(),
+        ));
+      }
+      self.pos = start + offset + c.len_utf8();
+    }
+
+    let name = self.input[start..self.pos].trim_end();
+    if name.is_empty() {
+      return Err(self.error(DependencyErrorKind::MissingName, start..start));
+    }
+    self.pos = start + name.len();
+    self.skip_spaces();
+    Ok(name.to_string())
+  }
+
+  fn operator(&mut self) -> Option<VersionOp> {
+    ["<=", ">=", "<", ">", "="]
+      .into_iter()
+      .find(|token| self.eat(token))
+      .map(|token| match token {
+        "<=" => VersionOp::LessEq,
+        ">=" => VersionOp::GreaterEq,
+        "<" => VersionOp::Less,
+        ">" => VersionOp::Greater,
+        _ => VersionOp::Exact,
+      })
+  }
+
+  fn version(&mut self, op: VersionOp) -> Result<VersionComparator, DependencyError> {
+    let start = self.pos;
+    let len = self
+      .rest()
+      .find(|c: char| c.is_whitespace())
+      .unwrap_or(self.rest().len());
+    let token = &self.rest()[..len];
+    if token.is_empty() {
+      return Err(self.error(DependencyErrorKind::MissingVersion, start..start));
+    }
+
+    let invalid = || self.error(DependencyErrorKind::InvalidVersion(token.to_string()), start..start + len);
+    let parts = token
+      .split('.')
+      .map(|part| match !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()) {
+        true => part.parse::<u16>().map_err(|_| invalid()),
+        false => Err(invalid()),
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+    let (major, minor, patch) = match parts[..] {
+      [major, minor] => (major, minor, 0),
+      [major, minor, patch] => (major, minor, patch),
+      _ => return Err(invalid()),
+    };
+
+    self.pos += len;
+    Ok(VersionComparator {
+      op,
+      version: FactorioVersion::new(major, minor, patch),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::{rngs::StdRng, Rng, SeedableRng};
+
+  /// Dependency strings found in the `info.json` of releases on the mod portal.
+  const PORTAL_DEPENDENCIES: &str = include_str!("../../tests/fixtures/dependencies.txt");
+
+  fn required(dep: &FModDependecies) -> Option<String> {
+    dep.required_version.as_ref().map(ToString::to_string)
+  }
+
+  fn parse_error(s: &str) -> DependencyError {
+    match FModDependecies::from_str(s) {
+      Err(Error::InvalidDependency(e)) => e,
+      other => panic!("{:?}", other),
+    }
+  }
+
+  #[test]
+  fn test_fmod_dependencies_from_str() {
+    let dep = FModDependecies::from_str("base >= 0.18.27").unwrap();
+    assert_eq!(dep.name, "base");
+    assert_eq!(required(&dep).as_deref(), Some(">= 0.18.27"));
+    assert_eq!(dep.preffix, FModPreffix::Required);
+
+    let dep = FModDependecies::from_str("! other_mod").unwrap();
+    assert_eq!(dep.name, "other_mod");
+    assert_eq!(dep.preffix, FModPreffix::Incompatible);
+
+    let dep = FModDependecies::from_str("? optional_mod").unwrap();
+    assert_eq!(dep.name, "optional_mod");
+    assert_eq!(dep.preffix, FModPreffix::Optional);
+
+    let dep = FModDependecies::from_str("(?) hidden_optional_mod < 8.1").unwrap();
+    assert_eq!(dep.name, "hidden_optional_mod");
+    assert_eq!(dep.preffix, FModPreffix::HiddenOptional);
+    assert_eq!(required(&dep).as_deref(), Some("< 8.1.0"));
+
+    let dep = FModDependecies::from_str("flib").unwrap();
+    assert_eq!(dep.name, "flib");
+    assert_eq!(dep.required_version, None);
+    assert_eq!(dep.preffix, FModPreffix::Required);
+
+    let dep = FModDependecies::from_str("~ non_changing_mod").unwrap();
+    assert_eq!(dep.name, "non_changing_mod");
+    assert_eq!(dep.preffix, FModPreffix::NonChanging);
+
+    assert!(FModDependecies::from_str("invalid format >= coco uwu").is_err());
+  }
+
+  #[test]
+  fn test_dependency_spacing() {
+    let dep = FModDependecies::from_str("base>=1.1").unwrap();
+    assert_eq!(dep.name, "base");
+    assert_eq!(required(&dep).as_deref(), Some(">= 1.1.0"));
+
+    let dep = FModDependecies::from_str("? mod>=0.2.0").unwrap();
+    assert_eq!((dep.preffix, dep.name.as_str()), (FModPreffix::Optional, "mod"));
+
+    let dep = FModDependecies::from_str("?Bottleneck Lite").unwrap();
+    assert_eq!((dep.preffix, dep.name.as_str()), (FModPreffix::Optional, "Bottleneck Lite"));
+
+    let dep = FModDependecies::from_str("(?)Squeak Through = 1.8.02").unwrap();
+    assert_eq!(dep.preffix, FModPreffix::HiddenOptional);
+    assert_eq!(dep.name, "Squeak Through");
+    assert_eq!(required(&dep).as_deref(), Some("= 1.8.2"));
+
+    let ops = [
+      ("a<1.0", VersionOp::Less),
+      ("a<=1.0", VersionOp::LessEq),
+      ("a=1.0", VersionOp::Exact),
+      ("a >=1.0", VersionOp::GreaterEq),
+      ("a> 1.0", VersionOp::Greater),
+    ];
+    for (s, op) in ops {
+      let dep = FModDependecies::from_str(s).unwrap();
+      assert_eq!(dep.required_version.unwrap().op, op, "{}", s);
+    }
+  }
+
+  #[test]
+  fn test_dependency_errors() {
+    let cases = [
+      ("", DependencyErrorKind::Empty, 0..0),
+      ("  ", DependencyErrorKind::Empty, 0..2),
+      ("? ", DependencyErrorKind::MissingName, 2..2),
+      ("!! base", DependencyErrorKind::UnexpectedCharacter('!'), 1..2),
+      (">= 1.0", DependencyErrorKind::MissingName, 0..0),
+      ("base >=", DependencyErrorKind::MissingVersion, 7..7),
+      ("base >= 1.x", DependencyErrorKind::InvalidVersion("1.x".to_string()), 8..11),
+      ("base >= 1", DependencyErrorKind::InvalidVersion("1".to_string()), 8..9),
+      ("base == 1.0", DependencyErrorKind::InvalidVersion("=".to_string()), 6..7),
+      ("base >= 1.0 beta", DependencyErrorKind::TrailingText("beta".to_string()), 12..16),
+      ("bäse (x)", DependencyErrorKind::UnexpectedCharacter('('), 6..7),
+      ("base ^1.1", DependencyErrorKind::UnexpectedCharacter('^'), 5..6),
+    ];
+    for (s, kind, span) in cases {
+      let error = parse_error(s);
+      assert_eq!((&error.kind, &error.span), (&kind, &span), "{}", s);
+    }
+
+    assert_eq!(
+      parse_error("base >= 1.x").to_string(),
+      "Invalid dependency 'base >= 1.x': invalid version '1.x' (bytes 8..11)"
+    );
+  }
+
+  #[test]
+  fn test_dependency_round_trip() {
+    for s in ["base >= 1.1", "? mod>=0.2.0", "  ~ flib  ", "(?) a = 0.18.05"] {
+      let dep = FModDependecies::from_str(s).unwrap();
+      assert_eq!(dep.to_string(), s);
+      assert_eq!(serde_json::to_value(&dep).unwrap(), s);
+    }
+
+    // Setting a part writes the dependency back in the canonical form, which Factorio reads.
+    let mut dep = FModDependecies::from_str("? mod>=0.2.0").unwrap();
+    dep.set_preffix(FModPreffix::Required);
+    assert_eq!(dep.to_string(), "mod >= 0.2.0");
+    dep.set_required_version(None);
+    assert_eq!(dep.to_string(), "mod");
+    let mut dep = FModDependecies::from_str("(?)Squeak Through").unwrap();
+    dep.set_name("Squeak Through 2");
+    assert_eq!(dep.to_string(), "(?) Squeak Through 2");
+
+    let dep = FModDependecies::new(
+      FModPreffix::Required,
+      "base",
+      Some(VersionComparator {
+        op: VersionOp::Less,
+        version: FactorioVersion::new(2, 0, 0),
+      }),
+    );
+    assert_eq!(dep.to_string(), "base < 2.0.0");
+    assert_eq!(dep.to_string().parse::<FModDependecies>().unwrap(), dep);
+  }
+
+  #[test]
+  fn test_portal_dependencies() {
+    for line in PORTAL_DEPENDENCIES.lines().filter(|l| !l.is_empty()) {
+      let dep = FModDependecies::from_str(line).unwrap_or_else(|e| panic!("{}", e));
+      assert_eq!(dep.to_string(), line);
+      assert_eq!(dep.to_string().parse::<FModDependecies>().unwrap(), dep);
+    }
+  }
+
+  /// Mutates the portal strings and checks that the parser never panics, that what it accepts
+  /// round-trips and that its errors point inside the input.
+  #[test]
+  fn test_fuzz_dependencies() {
+    const ALPHABET: &[char] = &[
+      ' ', '!', '?', '~', '(', ')', '<', '>', '=', '.', '0', '5', '9', 'a', '-', '_', '\t', 'é',
+    ];
+    let corpus: Vec<&str> = PORTAL_DEPENDENCIES.lines().filter(|l| !l.is_empty()).collect();
+    let mut rng = StdRng::seed_from_u64(0x5eed);
+
+    for _ in 0..20_000 {
+      let mut chars: Vec<char> = corpus[rng.gen_range(0..corpus.len())].chars().collect();
+      for _ in 0..rng.gen_range(1..4) {
+        let at = rng.gen_range(0..=chars.len());
+        match rng.gen_range(0..3) {
+          0 => chars.insert(at, ALPHABET[rng.gen_range(0..ALPHABET.len())]),
+          1 if at < chars.len() => {
+            chars.remove(at);
+          }
+          _ if at < chars.len() => chars[at] = ALPHABET[rng.gen_range(0..ALPHABET.len())],
+          _ => (),
+        }
+      }
+      let s: String = chars.into_iter().collect();
+
+      match FModDependecies::from_str(&s) {
+        Ok(dep) => {
+          assert_eq!(dep.to_string(), s);
+          let canonical =
+            FModDependecies::new(dep.preffix(), dep.name(), dep.required_version().cloned());
+          assert_eq!(canonical.to_string().parse::<FModDependecies>().unwrap(), dep, "{}", s);
+        }
+        Err(Error::InvalidDependency(e)) => {
+          assert!(e.span.start <= e.span.end && e.span.end <= s.len(), "{:?}", e);
+          assert!(s.is_char_boundary(e.span.start) && s.is_char_boundary(e.span.end), "{:?}", e);
+        }
+        Err(e) => panic!("{}", e),
+      }
+    }
+  }
+
+  #[test]
+  fn test_fmod_preffix_from_str() {
+    assert_eq!(
+      FModPreffix::from_str("!").unwrap(),
+      FModPreffix::Incompatible
+    );
+    assert_eq!(FModPreffix::from_str("?").unwrap(), FModPreffix::Optional);
+    assert_eq!(
+      FModPreffix::from_str("(?)").unwrap(),
+      FModPreffix::HiddenOptional
+    );
+    assert_eq!(
+      FModPreffix::from_str("~").unwrap(),
+      FModPreffix::NonChanging
+    );
+    assert_eq!(FModPreffix::from_str("").unwrap(), FModPreffix::Required);
+    assert!(FModPreffix::from_str("invalid").is_err());
+  }
+}
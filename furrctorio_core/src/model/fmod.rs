@@ -1,15 +1,34 @@
-use crate::error::Error;
+use crate::{
+  download::Transfer,
+  error::{error_for_status, redact_url, Error},
+};
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
-use semver::{Version, VersionReq};
-use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use reqwest::{header::RANGE, StatusCode};
+use serde::{Deserialize, Serialize};
 use sha1::{Digest, Sha1};
-use std::{cmp::Ordering, fmt::Display, str::FromStr, sync::Arc};
+use std::{
+  cmp::Ordering,
+  fmt::Display,
+  path::{Path, PathBuf},
+  str::FromStr,
+  sync::Arc,
+};
+use tokio::{
+  fs::{File, OpenOptions},
+  io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+};
+use tracing::{debug, instrument};
 use url::Url;
 
-use super::context::Context;
+use super::{
+  context::{Context, Endpoints},
+  dependency::FModDependecies,
+  version::{FactorioVersion, FactorioVersionReq, GameVersion},
+};
 
 /// Represents a Factorio mod, which can be either short or full.
+#[allow(clippy::large_enum_variant)]
 pub enum FMod {
   Short(FModShort),
   Full(FModFull),
@@ -34,6 +53,7 @@ impl FMod {
         summary: m.summary.clone(),
         category: m.category.clone(),
         thumbnail: m.thumbnail.clone(),
+        score: m.score,
         downloads_count: m.downloads_count,
         latest_release: m.releases.first().cloned(),
         releases: m.releases.clone(),
@@ -41,10 +61,11 @@ impl FMod {
     }
   }
 
-  pub async fn full(&self, ctx: &Context) -> FModFull {
+  /// Returns the full information of the mod, fetching it from the portal if needed.
+  pub async fn full(&self, ctx: &Context) -> Result<FModFull, Error> {
     match self {
-      Self::Full(m) => m.clone(),
-      Self::Short(m) => ctx.get_mod_info_full(m.name.as_str()).await.unwrap(),
+      Self::Full(m) => Ok(m.clone()),
+      Self::Short(m) => ctx.get_mod_info_full(m.name.as_str()).await,
     }
   }
 }
@@ -71,19 +92,27 @@ pub struct FModFull {
   pub title: String,
 
   /// A single category describing the mod. See Mod_details_API#Category.
-  pub category: String,
+  #[serde(default)]
+  pub category: FModCategory,
 
   /// The relative path to the thumbnail of the mod.
   /// For mods that have no thumbnail, this may be absent or default to "/assets/.thumb.png".
   /// Prepend "assets-mod.factorio.com" to get the full URL.
   pub thumbnail: Option<String>,
 
-  /// A string describing the recent changes to the mod.
-  pub changelog: String,
+  /// A string describing the recent changes to the mod, in the format of `changelog.txt`.
+  /// Absent when the mod has no changelog. See [`FModFull::changelog_entries`].
+  pub changelog: Option<String>,
 
   /// The ISO 8601 timestamp for when the mod was created.
   pub created_at: DateTime<Utc>,
 
+  /// The ISO 8601 timestamp for when the mod was last updated.
+  pub updated_at: Option<DateTime<Utc>>,
+
+  /// The ISO 8601 timestamp for when the mod was last highlighted by the Factorio team.
+  pub last_highlighted_at: Option<DateTime<Utc>>,
+
   /// A longer description of the mod, in text only format.
   pub description: Option<String>,
 
@@ -96,11 +125,20 @@ pub struct FModFull {
   pub github_path: String,
 
   /// Usually a URL to the mod's main project page, but can be any string.
-  pub homepage: Option<Url>,
+  pub homepage: Option<String>,
 
   /// A list of tag names that categorize the mod. See #Tags.
+  #[serde(default)]
   pub tags: Vec<FModTag>,
 
+  /// The images of the mod's gallery.
+  #[serde(default)]
+  pub images: Vec<FModImage>,
+
+  /// The score of the mod, used to sort mods by trending.
+  #[serde(default)]
+  pub score: f64,
+
   /// The license that applies to the mod. See #License.
   pub license: License,
 
@@ -108,6 +146,21 @@ pub struct FModFull {
   pub deprecated: Option<bool>,
 }
 
+impl FModFull {
+  /// Parses the changelog of the mod.
+  ///
+  /// # Returns
+  ///
+  /// * `Vec<ChangelogEntry>` - The versions described in the changelog, in the order they appear. Empty when the mod has no changelog.
+  pub fn changelog_entries(&self) -> Vec<ChangelogEntry> {
+    self
+      .changelog
+      .as_deref()
+      .map(ChangelogEntry::parse)
+      .unwrap_or_default()
+  }
+}
+
 /// Represents the license that applies to a Factorio mod.
 #[derive(Debug, Deserialize, Serialize, Default, Clone)]
 pub struct License {
@@ -124,12 +177,27 @@ pub struct License {
   pub title: String,
 
   /// Usually a URL to the full license text, but can be any string.
-  pub url: Option<Url>,
+  pub url: Option<String>,
+}
+
+/// Represents an image of the gallery of a Factorio mod.
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq, Eq)]
+pub struct FModImage {
+  /// The unique id of the image.
+  pub id: String,
+
+  /// The URL to the thumbnail of the image.
+  pub thumbnail: String,
+
+  /// The URL to the full size image.
+  pub url: String,
 }
 
 /// Represents the tags that categorize a Factorio mod.
-#[derive(Debug, Deserialize, Serialize, Clone)]
-#[serde(rename_all = "kebab-case")]
+///
+/// Tags the portal adds later are kept as [`FModTag::Unknown`].
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(from = "String", into = "String")]
 pub enum FModTag {
   /// Transportation of the player, be it vehicles or teleporters.
   Transportation,
@@ -141,6 +209,8 @@ pub enum FModTag {
   Combat,
   /// Armors or armor equipment.
   Armor,
+  /// Changes to the player's character.
+  Character,
   /// Changes to enemies or entirely new enemies to deal with.
   Enemies,
   /// Map generation and terrain modification.
@@ -163,6 +233,71 @@ pub enum FModTag {
   Blueprints,
   /// Play it your way.
   Cheats,
+  /// A tag this version of the crate doesn't know about.
+  Unknown(String),
+}
+
+impl FModTag {
+  /// Returns the name of the tag used by the API.
+  pub fn as_str(&self) -> &str {
+    match self {
+      FModTag::Transportation => "transportation",
+      FModTag::Logistics => "logistics",
+      FModTag::Trains => "trains",
+      FModTag::Combat => "combat",
+      FModTag::Armor => "armor",
+      FModTag::Character => "character",
+      FModTag::Enemies => "enemies",
+      FModTag::Environment => "environment",
+      FModTag::Mining => "mining",
+      FModTag::Fluids => "fluids",
+      FModTag::LogisticNetwork => "logistic-network",
+      FModTag::CircuitNetwork => "circuit-network",
+      FModTag::Manufacturing => "manufacturing",
+      FModTag::Power => "power",
+      FModTag::Storage => "storage",
+      FModTag::Blueprints => "blueprints",
+      FModTag::Cheats => "cheats",
+      FModTag::Unknown(tag) => tag,
+    }
+  }
+}
+
+impl From<String> for FModTag {
+  fn from(s: String) -> Self {
+    match s.as_str() {
+      "transportation" => FModTag::Transportation,
+      "logistics" => FModTag::Logistics,
+      "trains" => FModTag::Trains,
+      "combat" => FModTag::Combat,
+      "armor" => FModTag::Armor,
+      "character" => FModTag::Character,
+      "enemies" => FModTag::Enemies,
+      "environment" => FModTag::Environment,
+      "mining" => FModTag::Mining,
+      "fluids" => FModTag::Fluids,
+      "logistic-network" => FModTag::LogisticNetwork,
+      "circuit-network" => FModTag::CircuitNetwork,
+      "manufacturing" => FModTag::Manufacturing,
+      "power" => FModTag::Power,
+      "storage" => FModTag::Storage,
+      "blueprints" => FModTag::Blueprints,
+      "cheats" => FModTag::Cheats,
+      _ => FModTag::Unknown(s),
+    }
+  }
+}
+
+impl From<FModTag> for String {
+  fn from(tag: FModTag) -> Self {
+    tag.as_str().to_string()
+  }
+}
+
+impl Display for FModTag {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.as_str())
+  }
 }
 
 /// Represents a short Factorio mod with basic information.
@@ -193,20 +328,27 @@ pub struct FModShort {
   pub title: String,
 
   /// A single category describing the mod. See Mod_details_API#Category.
-  pub category: String,
+  #[serde(default)]
+  pub category: FModCategory,
 
   /// The relative path to the thumbnail of the mod.
   /// For mods that have no thumbnail, this may be absent or default to "/assets/.thumb.png".
   /// Prepend "assets-mod.factorio.com" to get the full URL.
   pub thumbnail: Option<String>,
+
+  /// The score of the mod, used to sort mods by trending.
+  #[serde(default)]
+  pub score: f64,
 }
 
 /// Represents the category of a Factorio mod.
 /// The category helps users to understand the purpose and scope of the mod.
-#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+///
+/// Categories the portal adds later are kept as [`FModCategory::Unknown`].
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq, Eq)]
+#[serde(from = "String", into = "String")]
 pub enum FModCategory {
   /// No category.
-  #[serde(rename = "no-category")]
   #[default]
   NoCategory,
   /// Mods introducing new content into the game.
@@ -225,6 +367,133 @@ pub enum FModCategory {
   Localizations,
   /// Lua libraries for use by other mods and submods that are parts of a larger mod.
   Internal,
+  /// A category this version of the crate doesn't know about.
+  Unknown(String),
+}
+
+impl FModCategory {
+  /// Returns the name of the category used by the API.
+  pub fn as_str(&self) -> &str {
+    match self {
+      FModCategory::NoCategory => "no-category",
+      FModCategory::Content => "content",
+      FModCategory::Overhaul => "overhaul",
+      FModCategory::Tweaks => "tweaks",
+      FModCategory::Utilities => "utilities",
+      FModCategory::Scenarios => "scenarios",
+      FModCategory::ModPacks => "mod-packs",
+      FModCategory::Localizations => "localizations",
+      FModCategory::Internal => "internal",
+      FModCategory::Unknown(category) => category,
+    }
+  }
+}
+
+impl From<String> for FModCategory {
+  fn from(s: String) -> Self {
+    match s.as_str() {
+      "" | "no-category" => FModCategory::NoCategory,
+      "content" => FModCategory::Content,
+      "overhaul" => FModCategory::Overhaul,
+      "tweaks" => FModCategory::Tweaks,
+      "utilities" => FModCategory::Utilities,
+      "scenarios" => FModCategory::Scenarios,
+      "mod-packs" => FModCategory::ModPacks,
+      "localizations" => FModCategory::Localizations,
+      "internal" => FModCategory::Internal,
+      _ => FModCategory::Unknown(s),
+    }
+  }
+}
+
+impl From<FModCategory> for String {
+  fn from(category: FModCategory) -> Self {
+    category.as_str().to_string()
+  }
+}
+
+impl Display for FModCategory {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.as_str())
+  }
+}
+
+/// A version section of a mod's changelog.
+///
+/// The format is the one of `changelog.txt`, see <https://wiki.factorio.com/Tutorial:Mod_changelog_format>.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangelogEntry {
+  /// The version the section describes, as written.
+  pub version: String,
+  /// The date of the release, as written.
+  pub date: Option<String>,
+  /// The categories of changes, such as "Features" or "Bugfixes".
+  pub categories: Vec<ChangelogCategory>,
+}
+
+/// A category of changes in a [`ChangelogEntry`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangelogCategory {
+  /// The name of the category, without the trailing colon.
+  pub name: String,
+  /// The changes, with their continuation lines joined by newlines.
+  pub entries: Vec<String>,
+}
+
+impl ChangelogEntry {
+  /// Parses a changelog.
+  ///
+  /// The parser is lenient: lines before the first version and lines it doesn't understand are
+  /// skipped instead of failing, since the portal doesn't validate changelogs.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The content of the changelog.
+  ///
+  /// # Returns
+  ///
+  /// * `Vec<ChangelogEntry>` - The versions described in the changelog, in the order they appear.
+  pub fn parse(text: &str) -> Vec<ChangelogEntry> {
+    let mut entries: Vec<ChangelogEntry> = Vec::new();
+    for line in text.lines() {
+      let trimmed = line.trim();
+      if let Some(version) = trimmed.strip_prefix("Version:") {
+        entries.push(ChangelogEntry {
+          version: version.trim().to_string(),
+          ..Default::default()
+        });
+        continue;
+      }
+      let Some(entry) = entries.last_mut() else {
+        continue;
+      };
+
+      if trimmed.is_empty() || trimmed.starts_with("---") {
+        continue;
+      } else if let Some(date) = trimmed.strip_prefix("Date:") {
+        entry.date = Some(date.trim().to_string());
+      } else if let Some(change) = trimmed.strip_prefix('-') {
+        if entry.categories.is_empty() {
+          entry.categories.push(ChangelogCategory::default());
+        }
+        let category = entry.categories.last_mut().unwrap();
+        category.entries.push(change.trim().to_string());
+      } else if let Some(name) = trimmed.strip_suffix(':') {
+        entry.categories.push(ChangelogCategory {
+          name: name.to_string(),
+          entries: Vec::new(),
+        });
+      } else if let Some(change) = entry
+        .categories
+        .last_mut()
+        .and_then(|category| category.entries.last_mut())
+      {
+        change.push('\n');
+        change.push_str(trimmed);
+      }
+    }
+    entries
+  }
 }
 
 /// Represents a release of a Factorio mod.
@@ -239,51 +508,11 @@ pub struct FModRelease {
   /// The ISO 8601 timestamp for when the mod release was released.
   pub released_at: DateTime<Utc>,
   /// The version of the mod release.
-  pub version: VersionEncapsulate,
+  pub version: FactorioVersion,
   /// The SHA1 hash of the mod release.
   pub sha1: String,
 }
 
-#[derive(Debug, Clone)]
-pub enum VersionEncapsulate {
-  Version(Version),
-  String(String),
-}
-
-impl ToString for VersionEncapsulate {
-  fn to_string(&self) -> String {
-    match self {
-      VersionEncapsulate::Version(version) => version.to_string(),
-      VersionEncapsulate::String(string) => string.clone(),
-    }
-  }
-}
-
-impl<'de> Deserialize<'de> for VersionEncapsulate {
-  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-  where
-    D: Deserializer<'de>,
-  {
-    let value = String::deserialize(deserializer)?;
-    match Version::parse(&value) {
-      Ok(version) => Ok(VersionEncapsulate::Version(version)),
-      Err(_) => Ok(VersionEncapsulate::String(value)),
-    }
-  }
-}
-
-impl Serialize for VersionEncapsulate {
-  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-  where
-    S: Serializer,
-  {
-    match self {
-      VersionEncapsulate::Version(version) => version.to_string().serialize(serializer),
-      VersionEncapsulate::String(string) => string.serialize(serializer),
-    }
-  }
-}
-
 impl Default for FModRelease {
   fn default() -> Self {
     Self {
@@ -291,48 +520,165 @@ impl Default for FModRelease {
       file_name: String::new(),
       info_json: InfoJSON::default(),
       released_at: DateTime::from_str("2023-01-01T00:00:00Z").unwrap(),
-      version: VersionEncapsulate::Version(Version::new(0, 0, 0)),
+      version: FactorioVersion::default(),
       sha1: String::new(),
     }
   }
 }
 
 impl FModRelease {
-  pub async fn download(&self, ctx: Arc<Context>) -> Result<(Bytes, String), reqwest::Error> {
-    let req_url = Url::parse_with_params(
-      &format!("https://mods.factorio.com/{}", self.download_url),
-      &[
-        ("username", ctx.username.clone()),
-        ("token", ctx.token.clone()),
-      ],
-    )
-    .unwrap();
+  /// Downloads the whole archive of the release into memory.
+  ///
+  /// Large mods are better downloaded with [`FModRelease::download_to`], which streams to disk.
+  pub async fn download(&self, ctx: Arc<Context>) -> Result<(Bytes, String), Error> {
+    let request = ctx.client().get(self.download_request_url(&ctx)?);
+    let response = error_for_status(ctx.send(request).await?).await?;
+    Ok((response.bytes().await?, self.file_name.clone()))
+  }
 
-    reqwest::get(req_url)
-      .await?
-      .bytes()
+  /// Downloads the release into a folder, without holding the archive in memory.
+  ///
+  /// The archive is streamed to `<file_name>.part` while its SHA1 is computed, then renamed to
+  /// `<file_name>` only once the hash matches. A `.part` file left by an interrupted download
+  /// is resumed with an HTTP range request. On a hash mismatch, the `.part` file is deleted so
+  /// that the next attempt starts over.
+  ///
+  /// # Arguments
+  ///
+  /// * `ctx` - The context used to authenticate the download.
+  /// * `folder` - The folder to download the release into, usually the mod folder.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<PathBuf, Error>` - Returns the path of the downloaded archive.
+  pub async fn download_to(&self, ctx: &Context, folder: &Path) -> Result<PathBuf, Error> {
+    let url = self.download_request_url(ctx).map_err(Error::InvalidUrl)?;
+    self
+      .download_url_to(ctx, url, folder, &mut Transfer::new(&self.file_name))
       .await
-      .map(|ok| (ok, self.file_name.clone()))
+  }
+
+  #[instrument(skip(self, ctx, url, transfer), fields(file_name = %self.file_name))]
+  pub(crate) async fn download_url_to(
+    &self,
+    ctx: &Context,
+    url: Url,
+    folder: &Path,
+    transfer: &mut Transfer<'_>,
+  ) -> Result<PathBuf, Error> {
+    if ctx.is_offline() {
+      return Err(Error::NotCached(redact_url(&url).to_string()));
+    }
+
+    let path = folder.join(&self.file_name);
+    let part = folder.join(format!("{}.part", self.file_name));
+
+    let (mut hasher, mut offset) = match File::open(&part).await {
+      Ok(file) => hash_reader(file).await.map_err(Error::IoError)?,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => (Sha1::new(), 0),
+      Err(e) => return Err(Error::IoError(e)),
+    };
+
+    let mut request = ctx.client().get(url);
+    if offset > 0 {
+      request = request.header(RANGE, format!("bytes={}-", offset));
+    }
+    let mut response = ctx.send(request).await?;
+
+    // The server answers 416 when the part already holds the whole archive.
+    if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+      transfer.start(offset, Some(offset));
+    } else {
+      response = error_for_status(response).await?;
+
+      let mut options = OpenOptions::new();
+      if response.status() == StatusCode::PARTIAL_CONTENT {
+        debug!("Resuming '{}' after {} bytes", self.file_name, offset);
+        options.append(true);
+      } else {
+        if offset > 0 {
+          debug!("The server does not support resuming, restarting '{}'", self.file_name);
+        }
+        hasher = Sha1::new();
+        offset = 0;
+        options.write(true).create(true).truncate(true);
+      }
+      transfer.start(offset, response.content_length().map(|len| offset + len));
+
+      let mut file = options.open(&part).await.map_err(Error::IoError)?;
+      while let Some(chunk) = response.chunk().await? {
+        hasher.update(&chunk);
+        file.write_all(&chunk).await.map_err(Error::IoError)?;
+        transfer.advance(chunk.len() as u64).await;
+      }
+      file.flush().await.map_err(Error::IoError)?;
+    }
+
+    if !self.matches_digest(hasher) {
+      tokio::fs::remove_file(&part).await.map_err(Error::IoError)?;
+      return Err(Error::ChecksumMismatch(self.file_name.clone()));
+    }
+
+    debug!("Downloaded '{}'", self.file_name);
+    tokio::fs::rename(&part, &path).await.map_err(Error::IoError)?;
+    Ok(path)
+  }
+
+  pub(crate) fn download_request_url(&self, ctx: &Context) -> Result<Url, url::ParseError> {
+    let mut url = Endpoints::join(&ctx.endpoints().download, &self.download_url)?;
+    url
+      .query_pairs_mut()
+      .append_pair("username", &ctx.username)
+      .append_pair("token", &ctx.token);
+    Ok(url)
   }
 
   pub fn validate(&self, data: &Bytes) -> bool {
     let mut hasher = Sha1::new();
     hasher.update(data);
+    self.matches_digest(hasher)
+  }
+
+  /// Checks the SHA1 of a file without loading it in memory.
+  pub async fn validate_file(&self, path: &Path) -> Result<bool, Error> {
+    let file = File::open(path).await.map_err(Error::IoError)?;
+    let (hasher, _) = hash_reader(file).await.map_err(Error::IoError)?;
+    Ok(self.matches_digest(hasher))
+  }
+
+  fn matches_digest(&self, hasher: Sha1) -> bool {
     format!("{:x}", hasher.finalize()).to_lowercase() == self.sha1
   }
 
-  pub fn match_version(&self, version_req: &VersionReq) -> bool {
-    match &self.version {
-      VersionEncapsulate::Version(version) => version_req.matches(version),
-      VersionEncapsulate::String(version_str) => {
-        if version_str.starts_with("0.0.") {
-          let req =
-            VersionReq::parse(&version_req.clone().to_string().replace("0.0.", "0.1.")).unwrap();
+  /// Returns true if the version of the release matches the requirement.
+  pub fn match_version(&self, version_req: &FactorioVersionReq) -> bool {
+    version_req.matches(&self.version)
+  }
 
-          req.matches(&Version::parse(&version_str.replace("0.0.", "0.1.")).unwrap())
-        } else {
-          panic!("VersionReq cannot be parsed")
-        }
+  /// Returns the version of the game the release is made for.
+  ///
+  /// Like Factorio, a release that doesn't declare a `factorio_version` is made for 0.12.
+  pub fn factorio_version(&self) -> GameVersion {
+    self.info_json.factorio_version.unwrap_or(GameVersion::V0_12)
+  }
+
+  /// Returns true if the given version of the game loads the release.
+  pub fn loads_on(&self, game_version: GameVersion) -> bool {
+    game_version.loads(self.factorio_version())
+  }
+}
+
+/// Hashes everything a reader returns, and counts the bytes read.
+async fn hash_reader<R: AsyncRead + Unpin>(mut reader: R) -> std::io::Result<(Sha1, u64)> {
+  let mut hasher = Sha1::new();
+  let mut buffer = vec![0; 64 * 1024];
+  let mut len = 0;
+  loop {
+    match reader.read(&mut buffer).await? {
+      0 => return Ok((hasher, len)),
+      read => {
+        hasher.update(&buffer[..read]);
+        len += read as u64;
       }
     }
   }
@@ -354,46 +700,7 @@ impl PartialOrd for FModRelease {
 
 impl Ord for FModRelease {
   fn cmp(&self, other: &Self) -> Ordering {
-    match &self.version {
-      VersionEncapsulate::Version(version) => {match &other.version {
-        VersionEncapsulate::Version(other_version) => version.cmp(other_version),
-        VersionEncapsulate::String(other_str) => {
-          let ver_vec: Vec<usize> = version.to_string().split(".").map(|part| part.parse::<usize>().unwrap()).collect();
-          let oth_vec: Vec<usize> = other_str.split(".").map(|part| part.parse::<usize>().unwrap()).collect();
-
-          if ver_vec.len() != oth_vec.len() {
-            panic!("Versions are weird!!!");
-          }
-
-          let mut res = Ordering::Equal;
-
-          for i in 0..ver_vec.len() {
-            res = ver_vec[i].cmp(&oth_vec[i])
-          };
-
-          res
-        },
-      }},
-      VersionEncapsulate::String(other) => {
-        let ver_vec: Vec<usize> = match &self.version{
-          VersionEncapsulate::Version(ver) => ver.to_string(),
-          VersionEncapsulate::String(str) => str.clone(),
-        }.split(".").map(|part| part.parse::<usize>().unwrap()).collect();  
-        let oth_vec: Vec<usize> = other.split(".").map(|part| part.parse::<usize>().unwrap()).collect();
-
-        if ver_vec.len() != oth_vec.len() {
-          panic!("Versions are weird!!!");
-        }
-
-        let mut res = Ordering::Equal;
-
-        for i in 0..ver_vec.len() {
-          res = ver_vec[i].cmp(&oth_vec[i])
-        };
-
-        res
-      },
-    }
+    self.version.cmp(&other.version)
   }
 }
 
@@ -403,201 +710,49 @@ pub struct InfoJSON {
   /// The name of the mod.
   pub name: Option<String>,
   /// The version of the mod.
-  pub version: Option<Version>,
+  pub version: Option<FactorioVersion>,
   /// The human-readable title of the mod.
   pub title: Option<String>,
   /// The author of the mod.
   pub author: Option<String>,
-  /// The version of Factorio that the mod is compatible with.
-  pub factorio_version: Option<String>,
+  /// The version of Factorio that the mod is made for.
+  pub factorio_version: Option<GameVersion>,
   /// The list of dependencies for the mod.
   #[serde(default)]
   pub dependencies: Vec<FModDependecies>,
 }
 
-/// Represents a dependency for a Factorio mod.
-#[derive(Debug, Default, Clone)]
-pub struct FModDependecies {
-  /// The name of the dependency.
-  pub name: String,
-  /// The required version of the dependency.
-  pub required_version: Option<VersionReq>,
-  /// The prefix for the dependency.
-  pub preffix: FModPreffix,
-}
-
-impl<'de> Deserialize<'de> for FModDependecies {
-  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-  where
-    D: Deserializer<'de>,
-  {
-    let s = String::deserialize(deserializer)?;
-    FromStr::from_str(&s).map_err(|e| de::Error::custom(format!("{:?}", e)))
-  }
-}
-
-impl Serialize for FModDependecies {
-  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-  where
-    S: serde::Serializer,
-  {
-    match (self.required_version.clone(), self.preffix) {
-      (Some(version), FModPreffix::Required) => {
-        serializer.serialize_str(&format!("{} {}", self.name, version))
-      }
-      (Some(version), preffix) => {
-        serializer.serialize_str(&format!("{} {} {}", preffix, self.name, version))
-      }
-      (None, FModPreffix::Required) => serializer.serialize_str(&self.name),
-      (None, preffix) => serializer.serialize_str(&format!("{} {}", preffix, self.name)),
-    }
-  }
-}
-
-impl FromStr for FModDependecies {
-  type Err = Error;
-
-  /// Parses a string into a FModDependecies.
-  fn from_str(s: &str) -> Result<Self, Self::Err> {
-    match s.split_whitespace().collect::<Vec<&str>>().as_slice() {
-      [prefix, name] => Ok(FModDependecies {
-        preffix: prefix.parse::<FModPreffix>()?,
-        name: name.to_string(),
-        ..Default::default()
-      }),
-      [prefix, name, version1, version2] => {
-        let vers = VersionReq::parse(&format!("{}{}", version1, version2));
-        if vers.is_err() {
-          return Err(Error::ParcingError(
-            "Invalid dependecies format: ".to_string() + s,
-          ));
-        }
-        Ok(FModDependecies {
-          preffix: prefix.parse::<FModPreffix>()?,
-          name: name.to_string(),
-          required_version: vers.ok(),
-        })
-      }
-      [name, version1, version2] => {
-        let vers = VersionReq::parse(&format!("{}{}", version1, version2));
-        if vers.is_err() {
-          return Err(Error::ParcingError(
-            "Invalid dependecies format: ".to_string() + s,
-          ));
-        }
-        Ok(FModDependecies {
-          name: name.to_string(),
-          required_version: vers.ok(),
-          ..Default::default()
-        })
-      }
-      _ => Err(Error::ParcingError(
-        "Invalid dependecies format: ".to_string() + s,
-      )),
-    }
-  }
-}
-
-/// Represents the prefix for a Factorio mod dependency.
-#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq, Copy)]
-pub enum FModPreffix {
-  /// The dependency is required.
-  #[default]
-  Required,
-  /// The dependency is incompatible.
-  #[serde(rename = "!")]
-  Incompatible,
-  /// The dependency is optional.
-  #[serde(rename = "?")]
-  Optional,
-  /// The dependency is hidden optional.
-  #[serde(rename = "(?)")]
-  HiddenOptional,
-  /// The dependency is non-changing.
-  #[serde(rename = "~")]
-  NonChanging,
-}
-
-impl FromStr for FModPreffix {
-  type Err = Error;
-
-  /// Parses a string into a FModPreffix.
-  fn from_str(s: &str) -> Result<Self, Self::Err> {
-    match s {
-      "!" => Ok(FModPreffix::Incompatible),
-      "?" => Ok(FModPreffix::Optional),
-      "(?)" => Ok(FModPreffix::HiddenOptional),
-      "~" => Ok(FModPreffix::NonChanging),
-      "" => Ok(FModPreffix::Required),
-      _ => Err(Error::InvalidPreffix(s.to_string())),
-    }
-  }
-}
-
-impl Display for FModPreffix {
-  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    match self {
-      FModPreffix::Incompatible => write!(f, "!"),
-      FModPreffix::Optional => write!(f, "?"),
-      FModPreffix::HiddenOptional => write!(f, "(?)"),
-      FModPreffix::NonChanging => write!(f, "~"),
-      FModPreffix::Required => write!(f, ""),
-    }
-  }
-}
-
 #[cfg(test)]
 mod tests {
   use super::*;
-  use semver::VersionReq;
+  use crate::test_util::{self, Request, Response, Server};
   use serde_json::from_str;
 
   #[test]
-  fn test_fmod_dependencies_from_str() {
-    let dep = FModDependecies::from_str("base >= 0.18.27").unwrap();
-    assert_eq!(dep.name, "base");
-    assert_eq!(
-      dep.required_version,
-      Some(VersionReq::parse(">=0.18.27").unwrap())
-    );
-    assert_eq!(dep.preffix, FModPreffix::Required);
-
-    let dep = FModDependecies::from_str("! other_mod").unwrap();
-    assert_eq!(dep.name, "other_mod");
-    assert_eq!(dep.preffix, FModPreffix::Incompatible);
-
-    let dep = FModDependecies::from_str("? optional_mod").unwrap();
-    assert_eq!(dep.name, "optional_mod");
-    assert_eq!(dep.preffix, FModPreffix::Optional);
-
-    let dep = FModDependecies::from_str("(?) hidden_optional_mod < 8.1").unwrap();
-    assert_eq!(dep.name, "hidden_optional_mod");
-    assert_eq!(dep.preffix, FModPreffix::HiddenOptional);
-
-    let dep = FModDependecies::from_str("~ non_changing_mod").unwrap();
-    assert_eq!(dep.name, "non_changing_mod");
-    assert_eq!(dep.preffix, FModPreffix::NonChanging);
-
-    assert!(FModDependecies::from_str("invalid_format coco uwu").is_err());
-  }
-
-  #[test]
-  fn test_fmod_preffix_from_str() {
-    assert_eq!(
-      FModPreffix::from_str("!").unwrap(),
-      FModPreffix::Incompatible
-    );
-    assert_eq!(FModPreffix::from_str("?").unwrap(), FModPreffix::Optional);
-    assert_eq!(
-      FModPreffix::from_str("(?)").unwrap(),
-      FModPreffix::HiddenOptional
-    );
-    assert_eq!(
-      FModPreffix::from_str("~").unwrap(),
-      FModPreffix::NonChanging
-    );
-    assert_eq!(FModPreffix::from_str("").unwrap(), FModPreffix::Required);
-    assert!(FModPreffix::from_str("invalid").is_err());
+  fn test_release_version() {
+    let release: FModRelease = from_str(
+      r#"{"download_url": "/download/a/1", "file_name": "a_0.18.05.zip", "info_json": {"factorio_version": "0.18"},
+        "released_at": "2020-01-21T16:26:37.000Z", "version": "0.18.05", "sha1": "0"}"#,
+    )
+    .unwrap();
+    assert!(release.match_version(&">= 0.18.5".parse().unwrap()));
+    assert!(!release.match_version(&">= 0.18.6".parse().unwrap()));
+    assert!(release.match_version(&"> 0.18".parse().unwrap()));
+    assert_eq!(serde_json::to_value(&release).unwrap()["version"], "0.18.05");
+
+    let newer = FModRelease {
+      version: "0.18.6".parse().unwrap(),
+      ..Default::default()
+    };
+    assert!(release < newer);
+
+    assert_eq!(release.info_json.factorio_version, Some(GameVersion::V0_18));
+    assert!(release.loads_on(GameVersion::V1_0));
+    assert!(!release.loads_on(GameVersion::V1_1));
+    // Without a factorio_version, the release is made for 0.12 like in Factorio.
+    assert_eq!(newer.factorio_version(), GameVersion::V0_12);
+    assert!(newer.loads_on(GameVersion::V0_12));
+    assert!(!newer.loads_on(GameVersion::V2_0));
   }
 
   #[tokio::test]
@@ -637,7 +792,145 @@ mod tests {
       Some("/assets/84109a73b35230d21599ed5939d01090329ee5b6.thumb.png".to_string())
     );
     assert_eq!(fmod.title, "infinite research (0.15 like)");
-    assert_eq!(fmod.category, "content");
+    assert_eq!(fmod.category, FModCategory::Content);
+  }
+
+  #[test]
+  fn test_full_fixture() {
+    let fmod: FModFull = from_str(include_str!("../../tests/fixtures/mod_full.json")).unwrap();
+    assert_eq!(fmod.category, FModCategory::Internal);
+    assert_eq!(
+      fmod.tags,
+      [
+        FModTag::CircuitNetwork,
+        FModTag::Unknown("planets".to_string())
+      ]
+    );
+    assert_eq!(fmod.images.len(), 1);
+    assert_eq!(fmod.score, 1290.5);
+    assert!(fmod.updated_at.is_some());
+    assert!(fmod.last_highlighted_at.is_none());
+    assert_eq!(fmod.license.url.as_deref(), Some("https://opensource.org/licenses/MIT"));
+
+    let changelog = fmod.changelog_entries();
+    assert_eq!(changelog.len(), 2);
+    assert_eq!(changelog[0].version, "0.15.0");
+    assert_eq!(changelog[0].date.as_deref(), Some("2024-10-21"));
+    assert_eq!(
+      changelog[0].categories[0],
+      ChangelogCategory {
+        name: "Features".to_string(),
+        entries: vec![
+          "Added flib_position.to_tile_area".to_string(),
+          "Added support for Factorio 2.0".to_string()
+        ],
+      }
+    );
+    assert_eq!(
+      changelog[1].categories[0].entries,
+      ["Fixed a crash when the GUI was destroyed\nwhile it was being updated"]
+    );
+
+    let json = serde_json::to_value(&fmod).unwrap();
+    assert_eq!(json["category"], "internal");
+    assert_eq!(json["tags"][1], "planets");
+    assert_eq!(from_str::<FModFull>(&json.to_string()).unwrap().tags, fmod.tags);
+  }
+
+  /// Serves `data` on a local port, answering `Range: bytes=<start>-` requests with the end of
+  /// the data.
+  async fn serve(data: &'static [u8]) -> Server {
+    test_util::serve(move |_, request| match range_start(request) {
+      Some(start) => Response::new("206 Partial Content").body(&data[start..]),
+      None => Response::new("200 OK").body(data),
+    })
+    .await
+  }
+
+  fn range_start(request: &Request) -> Option<usize> {
+    let range = request.header("range")?.strip_prefix("bytes=")?;
+    range.trim_end_matches('-').parse().ok()
+  }
+
+  #[tokio::test]
+  async fn test_download_url_to() {
+    const DATA: &[u8] = b"PK not really a zip, but long enough to be split in two parts.";
+    let server = serve(DATA).await;
+    let url = server.url.join("download").unwrap();
+    let folder = std::env::temp_dir().join(format!("furrctorio-download-{}", std::process::id()));
+    tokio::fs::create_dir_all(&folder).await.unwrap();
+
+    let mut hasher = Sha1::new();
+    hasher.update(DATA);
+    let mut release = FModRelease {
+      file_name: "flib_0.1.0.zip".to_string(),
+      sha1: format!("{:x}", hasher.finalize()),
+      ..Default::default()
+    };
+    let part = folder.join("flib_0.1.0.zip.part");
+    let ctx = Context::anonymous().unwrap();
+
+    // Resume an interrupted download.
+    tokio::fs::write(&part, &DATA[..20]).await.unwrap();
+    let path = release
+      .download_url_to(&ctx, url.clone(), &folder, &mut Transfer::new(&release.file_name))
+      .await
+      .unwrap();
+    assert_eq!(tokio::fs::read(&path).await.unwrap(), DATA);
+    assert!(release.validate_file(&path).await.unwrap());
+    assert!(!part.exists());
+
+    tokio::fs::remove_file(&path).await.unwrap();
+    release.sha1 = "55f7bbcfc0c0e831008b57c321db509bf3a25285".to_string();
+    let res = release
+      .download_url_to(&ctx, url, &folder, &mut Transfer::new(&release.file_name))
+      .await;
+    let exists = (path.exists(), part.exists());
+    tokio::fs::remove_dir_all(&folder).await.unwrap();
+
+    assert!(matches!(res, Err(Error::ChecksumMismatch(_))));
+    assert_eq!(exists, (false, false));
+    let starts: Vec<usize> = server
+      .requests()
+      .iter()
+      .map(|request| range_start(request).unwrap_or(0))
+      .collect();
+    assert_eq!(starts, vec![20, 0]);
+  }
+
+  #[tokio::test]
+  async fn test_download_error_hides_token() {
+    let server = test_util::serve(|_, _| {
+      Response::new("403 Forbidden").json_body(r#"{"message": "Forbidden"}"#)
+    })
+    .await;
+    let ctx = Context::builder()
+      .portal_url(server.url.clone())
+      .unwrap()
+      .credentials("furr", "secret-token")
+      .build()
+      .unwrap();
+    let release = FModRelease {
+      download_url: "/download/flib/5ecac7e4".to_string(),
+      file_name: "flib_0.1.0.zip".to_string(),
+      ..Default::default()
+    };
+
+    let error = release.download_to(&ctx, &std::env::temp_dir()).await.unwrap_err();
+    assert!(matches!(error, Error::HttpError { status: StatusCode::FORBIDDEN, .. }));
+    let message = error.to_string();
+    assert!(!message.contains("secret-token"), "{}", message);
+    assert!(message.contains("/download/flib/5ecac7e4?username=***&token=***"), "{}", message);
+    assert_eq!(server.requests()[0].query("token").as_deref(), Some("secret-token"));
+
+    let offline = Context::builder()
+      .credentials("furr", "secret-token")
+      .offline(true)
+      .build()
+      .unwrap();
+    let error = release.download_to(&offline, &std::env::temp_dir()).await.unwrap_err();
+    assert!(matches!(error, Error::NotCached(_)));
+    assert!(!error.to_string().contains("secret-token"), "{}", error);
   }
 
   #[tokio::test]
@@ -660,7 +953,7 @@ mod tests {
     .unwrap();
     dotenv::dotenv().ok();
 
-    let ctx = Arc::new(Context::new_from_env());
+    let ctx = Arc::new(Context::new_from_env().unwrap());
 
     let res = release.download(ctx).await.unwrap();
 
@@ -1,4 +1,10 @@
 pub mod fmod;
+pub mod dependency;
 pub mod modlist;
 pub mod context;
-pub mod pagination;
\ No newline at end of file
+pub mod pagination;
+pub mod property_tree;
+pub mod mod_settings;
+pub mod save;
+pub mod query;
+pub mod version;
\ No newline at end of file
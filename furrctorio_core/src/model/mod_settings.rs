@@ -0,0 +1,231 @@
+use super::property_tree::PropertyTree;
+use crate::{
+  binary::{ApplicationVersion, BinaryReader, BinaryWriter},
+  error::Error,
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, path::Path};
+use tracing::instrument;
+
+/// The sections of `mod-settings.dat`, one per setting type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SettingsSection {
+  /// Settings applied when the game starts, which must match between server and players.
+  Startup,
+  /// Map settings, changeable by admins while the game runs.
+  RuntimeGlobal,
+  /// Settings of each player.
+  RuntimePerUser,
+}
+
+impl SettingsSection {
+  /// Returns the key of the section in the property tree.
+  pub fn key(&self) -> &'static str {
+    match self {
+      SettingsSection::Startup => "startup",
+      SettingsSection::RuntimeGlobal => "runtime-global",
+      SettingsSection::RuntimePerUser => "runtime-per-user",
+    }
+  }
+}
+
+impl Display for SettingsSection {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.key())
+  }
+}
+
+/// The content of Factorio's `mod-settings.dat`.
+///
+/// The file stores the version of the game that wrote it, followed by a property tree with
+/// one dictionary per section. Each setting is itself a dictionary with a `value` key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModSettings {
+  /// The version of the game that wrote the file.
+  pub version: ApplicationVersion,
+  /// The settings of every section.
+  pub root: PropertyTree,
+}
+
+impl ModSettings {
+  /// The name of the file in the mod folder.
+  pub const FILE_NAME: &'static str = "mod-settings.dat";
+
+  /// Creates an empty file, as written by the given version of the game.
+  pub fn new(version: ApplicationVersion) -> Self {
+    let mut root = PropertyTree::Dictionary(Vec::new());
+    for section in [
+      SettingsSection::Startup,
+      SettingsSection::RuntimeGlobal,
+      SettingsSection::RuntimePerUser,
+    ] {
+      root.insert(section.key(), PropertyTree::Dictionary(Vec::new()));
+    }
+
+    Self { version, root }
+  }
+
+  /// Since 0.17, the version is followed by a byte that is always zero.
+  fn has_header_flag(version: &ApplicationVersion) -> bool {
+    (version.major, version.minor) >= (0, 17)
+  }
+
+  /// Parses the content of a `mod-settings.dat` file.
+  pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+    let mut reader = BinaryReader::new(data);
+    let version = reader.version()?;
+    if Self::has_header_flag(&version) {
+      reader.u8()?;
+    }
+
+    let root = PropertyTree::read(&mut reader)?;
+    if !reader.is_empty() {
+      return Err(Error::ParcingError(format!(
+        "Unexpected data after the settings at offset {}",
+        reader.position()
+      )));
+    }
+
+    Ok(Self { version, root })
+  }
+
+  /// Serializes the settings in the format of `mod-settings.dat`.
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut writer = BinaryWriter::default();
+    writer.version(&self.version);
+    if Self::has_header_flag(&self.version) {
+      writer.u8(0);
+    }
+
+    self.root.write(&mut writer);
+    writer.into_bytes()
+  }
+
+  /// Reads the `mod-settings.dat` of a mod folder.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<Option<Self>, Error>` - Returns None if the file does not exist.
+  #[instrument]
+  pub async fn load(folder: &Path) -> Result<Option<Self>, Error> {
+    match tokio::fs::read(folder.join(Self::FILE_NAME)).await {
+      Ok(data) => Self::from_bytes(&data).map(Some),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+      Err(e) => Err(Error::IoError(e)),
+    }
+  }
+
+  /// Writes the settings to the `mod-settings.dat` of a mod folder.
+  #[instrument(skip(self))]
+  pub async fn save(&self, folder: &Path) -> Result<(), Error> {
+    tokio::fs::write(folder.join(Self::FILE_NAME), self.to_bytes())
+      .await
+      .map_err(Error::IoError)
+  }
+
+  /// Returns the value of a setting.
+  pub fn get(&self, section: SettingsSection, name: &str) -> Option<&PropertyTree> {
+    self.root.get(section.key())?.get(name)?.get("value")
+  }
+
+  /// Sets the value of a setting, adding it if needed.
+  pub fn set(&mut self, section: SettingsSection, name: &str, value: PropertyTree) {
+    if self.root.get(section.key()).is_none() {
+      self.root.insert(section.key(), PropertyTree::Dictionary(Vec::new()));
+    }
+
+    if let Some(settings) = self.root.get_mut(section.key()) {
+      match settings.get_mut(name) {
+        Some(setting) => setting.insert("value", value),
+        None => settings.insert(
+          name,
+          PropertyTree::Dictionary(vec![("value".to_string(), value)]),
+        ),
+      }
+    }
+  }
+
+  /// Lists the settings of a section with their value.
+  pub fn settings(&self, section: SettingsSection) -> Vec<(&str, &PropertyTree)> {
+    match self.root.get(section.key()) {
+      Some(PropertyTree::Dictionary(entries)) => entries
+        .iter()
+        .filter_map(|(name, setting)| Some((name.as_str(), setting.get("value")?)))
+        .collect(),
+      _ => Vec::new(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A file written by Factorio 1.1.110 with one startup and one runtime-global setting.
+  const FILE: &[u8] = &[
+    1, 0, 1, 0, 110, 0, 0, 0, 0, // version and header flag
+    5, 0, 3, 0, 0, 0, // root dictionary
+    0, 7, b's', b't', b'a', b'r', b't', b'u', b'p', 5, 0, 1, 0, 0, 0, //
+    0, 11, b'r', b's', b'o', b'-', b'r', b'e', b'g', b'i', b'o', b'n', b's', 5, 0, 1, 0, 0, 0, //
+    0, 5, b'v', b'a', b'l', b'u', b'e', 2, 0, 0, 0, 0, 0, 0, 0, 20, 64, // 5.0
+    0, 14, b'r', b'u', b'n', b't', b'i', b'm', b'e', b'-', b'g', b'l', b'o', b'b', b'a', b'l', 5,
+    0, 1, 0, 0, 0, //
+    0, 8, b'f', b'l', b'i', b'b', b'-', b'g', b'u', b'i', 5, 0, 1, 0, 0, 0, //
+    0, 5, b'v', b'a', b'l', b'u', b'e', 3, 0, 0, 4, b'b', b'l', b'u', b'e', //
+    0, 16, b'r', b'u', b'n', b't', b'i', b'm', b'e', b'-', b'p', b'e', b'r', b'-', b'u', b's',
+    b'e', b'r', 5, 0, 0, 0, 0, 0,
+  ];
+
+  #[test]
+  fn test_from_bytes() {
+    let settings = ModSettings::from_bytes(FILE).unwrap();
+
+    assert_eq!(settings.version, ApplicationVersion::new(1, 1, 110, 0));
+    assert_eq!(
+      settings.get(SettingsSection::Startup, "rso-regions"),
+      Some(&PropertyTree::Number(5.0))
+    );
+    assert_eq!(
+      settings.settings(SettingsSection::RuntimeGlobal),
+      vec![("flib-gui", &PropertyTree::String("blue".to_string()))]
+    );
+    assert!(settings.settings(SettingsSection::RuntimePerUser).is_empty());
+    assert_eq!(settings.to_bytes(), FILE);
+
+    assert!(ModSettings::from_bytes(&FILE[..FILE.len() - 1]).is_err());
+    assert!(ModSettings::from_bytes(&[FILE, &[0]].concat()).is_err());
+  }
+
+  #[test]
+  fn test_set() {
+    let mut settings = ModSettings::new(ApplicationVersion::new(2, 0, 28, 0));
+    settings.set(SettingsSection::Startup, "rso-regions", PropertyTree::Number(3.0));
+    settings.set(SettingsSection::Startup, "rso-regions", PropertyTree::Number(4.0));
+    settings.set(SettingsSection::RuntimeGlobal, "flib-gui", PropertyTree::Bool(false));
+
+    let read = ModSettings::from_bytes(&settings.to_bytes()).unwrap();
+    assert_eq!(read, settings);
+    assert_eq!(
+      read.get(SettingsSection::Startup, "rso-regions"),
+      Some(&PropertyTree::Number(4.0))
+    );
+    assert_eq!(read.settings(SettingsSection::Startup).len(), 1);
+  }
+
+  #[tokio::test]
+  async fn test_load_save() {
+    let folder = std::env::temp_dir().join(format!("furrctorio-settings-{}", std::process::id()));
+    tokio::fs::create_dir_all(&folder).await.unwrap();
+    assert!(ModSettings::load(&folder).await.unwrap().is_none());
+
+    let settings = ModSettings::from_bytes(FILE).unwrap();
+    settings.save(&folder).await.unwrap();
+    let data = tokio::fs::read(folder.join(ModSettings::FILE_NAME)).await.unwrap();
+    let loaded = ModSettings::load(&folder).await.unwrap();
+    tokio::fs::remove_dir_all(&folder).await.unwrap();
+
+    assert_eq!(data, FILE);
+    assert_eq!(loaded, Some(settings));
+  }
+}
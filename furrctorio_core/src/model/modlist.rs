@@ -1,35 +1,253 @@
 use super::{context::Context, fmod::{FModFull, FModShort}};
-use futures::{stream, StreamExt};
+use crate::{constants::BUILTIN_MODS, download::DEFAULT_PARALLELISM, error::Error};
+use futures::{stream, StreamExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use serde_json::{Map, Value};
+use std::{
+  fmt::Display,
+  path::{Path, PathBuf},
+  sync::Arc,
+};
+use tracing::{debug, instrument};
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The content of Factorio's `mod-list.json`, which stores which mods are enabled.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct ModList {
   pub mods: Vec<ModEntry>,
+
+  /// Fields unknown to furrctorio, kept as is when saving.
+  #[serde(flatten)]
+  pub extra: Map<String, Value>,
+
+  /// The mod folder the list was loaded from.
+  #[serde(skip)]
+  folder: Option<PathBuf>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct ModEntry {
   pub name: String,
   pub enabled: bool,
+
+  /// The version to load when several releases of the mod are installed.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>,
+
+  /// Fields unknown to furrctorio, kept as is when saving.
+  #[serde(flatten)]
+  pub extra: Map<String, Value>,
+}
+
+impl ModEntry {
+  /// Creates an entry without a version.
+  pub fn new(name: &str, enabled: bool) -> Self {
+    Self {
+      name: name.to_string(),
+      enabled,
+      ..Default::default()
+    }
+  }
+}
+
+/// A mod found in a mod folder, either as a zip file or as a folder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstalledMod {
+  /// The name of the mod.
+  pub name: String,
+  /// The version of the release, absent for unversioned folders like mods under development.
+  pub version: Option<String>,
+  /// The zip file or folder of the release.
+  pub path: PathBuf,
+}
+
+impl Display for InstalledMod {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match &self.version {
+      Some(version) => write!(f, "{} {}", self.name, version),
+      None => write!(f, "{}", self.name),
+    }
+  }
+}
+
+/// Lists the mods installed in a mod folder.
+///
+/// Releases are zip files named `<name>_<version>.zip` or folders named `<name>_<version>`.
+/// Other folders are considered unversioned mods. A missing folder contains no mods.
+///
+/// # Arguments
+///
+/// * `folder` - The mod folder.
+///
+/// # Returns
+///
+/// * `Result<Vec<InstalledMod>, Error>` - Returns the installed mods, in no particular order.
+pub async fn installed_mods(folder: &Path) -> Result<Vec<InstalledMod>, Error> {
+  let mut mods = Vec::new();
+  let mut dir = match tokio::fs::read_dir(folder).await {
+    Ok(dir) => dir,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(mods),
+    Err(e) => return Err(Error::IoError(e)),
+  };
+
+  while let Some(file) = dir.next_entry().await.map_err(Error::IoError)? {
+    let file_name = file.file_name().to_string_lossy().to_string();
+    let is_dir = file.file_type().await.map_err(Error::IoError)?.is_dir();
+    let stem = match file_name.strip_suffix(".zip") {
+      Some(stem) if !is_dir => stem,
+      None if is_dir => file_name.as_str(),
+      _ => continue,
+    };
+
+    let versioned = stem.rsplit_once('_').filter(|(_, version)| {
+      !version.is_empty() && version.chars().all(|c| c.is_ascii_digit() || c == '.')
+    });
+    let (name, version) = match versioned {
+      Some((name, version)) => (name, Some(version.to_string())),
+      None if is_dir => (stem, None),
+      None => continue,
+    };
+
+    mods.push(InstalledMod {
+      name: name.to_string(),
+      version,
+      path: file.path(),
+    });
+  }
+  Ok(mods)
 }
 
 impl ModList {
+  /// The name of the file in the mod folder.
+  pub const FILE_NAME: &'static str = "mod-list.json";
+
+  /// Creates a list that is not bound to any mod folder.
+  pub fn new(mods: Vec<ModEntry>) -> Self {
+    Self {
+      mods,
+      ..Default::default()
+    }
+  }
+
+  /// Reads the `mod-list.json` of a mod folder.
+  ///
+  /// Like Factorio, a missing file is treated as a list containing only the enabled base mod.
+  ///
+  /// # Arguments
+  ///
+  /// * `folder` - The mod folder, which the returned list is bound to.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<Self, Error>` - Returns the list or an Error if it cannot be read or parsed.
+  #[instrument]
+  pub async fn load(folder: &Path) -> Result<Self, Error> {
+    let path = folder.join(Self::FILE_NAME);
+    let mut list = match tokio::fs::read_to_string(&path).await {
+      Ok(content) => serde_json::from_str(&content).map_err(|e| {
+        Error::ParcingError(format!("Invalid '{}': {}", path.display(), e))
+      })?,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+        debug!("'{}' does not exist", path.display());
+        Self::new(vec![ModEntry::new("base", true)])
+      }
+      Err(e) => return Err(Error::IoError(e)),
+    };
+
+    list.folder = Some(folder.to_path_buf());
+    Ok(list)
+  }
+
+  /// Writes the list to the `mod-list.json` of the mod folder it was loaded from.
+  pub async fn save(&self) -> Result<(), Error> {
+    match &self.folder {
+      Some(folder) => self.save_to(folder).await,
+      None => Err(Error::IoError(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "The mod list is not bound to a mod folder",
+      ))),
+    }
+  }
+
+  /// Writes the list to the `mod-list.json` of a mod folder.
+  #[instrument(skip(self))]
+  pub async fn save_to(&self, folder: &Path) -> Result<(), Error> {
+    let content = serde_json::to_string_pretty(self)
+      .map_err(|e| Error::ParcingError(e.to_string()))?;
+    tokio::fs::write(folder.join(Self::FILE_NAME), content)
+      .await
+      .map_err(Error::IoError)
+  }
+
+  /// Returns the mod folder the list is bound to.
+  pub fn folder(&self) -> Option<&Path> {
+    self.folder.as_deref()
+  }
+
+  /// Returns the entry of a mod.
+  pub fn get(&self, name: &str) -> Option<&ModEntry> {
+    self.mods.iter().find(|m| m.name == name)
+  }
+
+  /// Enables or disables a mod, adding it to the list if needed.
+  pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+    match self.mods.iter_mut().find(|m| m.name == name) {
+      Some(entry) => entry.enabled = enabled,
+      None => self.mods.push(ModEntry::new(name, enabled)),
+    }
+  }
+
+  /// Makes the list match the installed mods.
+  ///
+  /// Installed mods without an entry are added as enabled, like Factorio does, entries of
+  /// mods that are not installed anymore are dropped, and versions that are not installed
+  /// are unpinned. The entries of the base mod and of the DLCs are always kept.
+  ///
+  /// # Arguments
+  ///
+  /// * `installed` - The mods of the mod folder, see [`installed_mods`].
+  pub fn reconcile(&mut self, installed: &[InstalledMod]) {
+    self.mods.retain(|entry| {
+      BUILTIN_MODS.contains(&entry.name.as_str()) || installed.iter().any(|m| m.name == entry.name)
+    });
+
+    for entry in &mut self.mods {
+      let pinned_installed = entry.version.as_ref().is_none_or(|version| {
+        installed
+          .iter()
+          .any(|m| m.name == entry.name && m.version.as_ref() == Some(version))
+      });
+      if !pinned_installed && !BUILTIN_MODS.contains(&entry.name.as_str()) {
+        debug!("Unpinning '{}', its version is not installed", entry.name);
+        entry.version = None;
+      }
+    }
+
+    if self.get("base").is_none() {
+      self.mods.insert(0, ModEntry::new("base", true));
+    }
+    for installed in installed {
+      if self.get(&installed.name).is_none() {
+        self.mods.push(ModEntry::new(&installed.name, true));
+      }
+    }
+  }
+
   /// This function returns a vector of `FModShort` objects which contain short information about each mod.
   ///
+  /// The mods are fetched in batches, see [`Context::get_mods_by_name`]. Mods that are not on
+  /// the portal, like the base mod, are left out.
+  ///
   /// # Arguments
   ///
   /// * `ctx` - A reference to an `Arc<Context>` object which is used to get the mod information.
   ///
   /// # Returns
   ///
-  /// * `Vec<FModShort>` - A vector of `FModShort` objects which contain short information about each mod.
-  pub async fn get_mods_info(&self, ctx: &Arc<Context>) -> Vec<FModShort> {
-    stream::iter(&self.mods)
-      .then(|m| ctx.get_mod_info(&m.name))
-      .map(|i| i.unwrap())
-      .collect()
-      .await
+  /// * `Result<Vec<FModShort>, Error>` - A vector of `FModShort` objects which contain short information about each mod.
+  pub async fn get_mods_info(&self, ctx: &Arc<Context>) -> Result<Vec<FModShort>, Error> {
+    let names: Vec<&str> = self.mods.iter().map(|m| m.name.as_str()).collect();
+    let mut mods = ctx.get_mods_by_name(&names).await?;
+    Ok(self.mods.iter().filter_map(|m| mods.remove(&m.name)).collect())
   }
 
   /// This function returns a vector of `FModFull` objects which contain full information about each mod.
@@ -40,12 +258,12 @@ impl ModList {
   ///
   /// # Returns
   ///
-  /// * `Vec<FModFull>` - A vector of `FModFull` objects which contain full information about each mod.
-  pub async fn get_mods_info_full(&self, ctx: &Arc<Context>) -> Vec<FModFull> {
+  /// * `Result<Vec<FModFull>, Error>` - A vector of `FModFull` objects which contain full information about each mod, or the first error.
+  pub async fn get_mods_info_full(&self, ctx: &Arc<Context>) -> Result<Vec<FModFull>, Error> {
     stream::iter(&self.mods)
-      .then(|m| ctx.get_mod_info_full(&m.name))
-      .map(|i| i.unwrap())
-      .collect()
+      .map(|m| ctx.get_mod_info_full(&m.name))
+      .buffered(DEFAULT_PARALLELISM)
+      .try_collect()
       .await
   }
 }
@@ -58,28 +276,14 @@ mod tests {
 
   #[tokio::test]
   async fn test_get_mods_info() {
-    let ctx = Arc::new(Context {
-      token: "".to_string(),
-      username: "".to_string(),
-    });
-    let mlist = ModList {
-      mods: vec![
-        ModEntry {
-          name: "fcpu".to_string(),
-          enabled: true,
-        },
-        ModEntry {
-          name: "flib".to_string(),
-          enabled: true,
-        },
-        ModEntry {
-          name: "helmod".to_string(),
-          enabled: true,
-        }
-      ],
-    };
+    let ctx = Arc::new(Context::anonymous().unwrap());
+    let mlist = ModList::new(vec![
+      ModEntry::new("fcpu", true),
+      ModEntry::new("flib", true),
+      ModEntry::new("helmod", true),
+    ]);
 
-    let mods = mlist.get_mods_info(&ctx).await;
+    let mods = mlist.get_mods_info(&ctx).await.unwrap();
     assert_eq!(mods.len(), 3);
     let names = mods.iter().map(|m| m.name.clone()).collect::<Vec<String>>();
     assert!(names.contains(&"fcpu".to_string()));
@@ -89,32 +293,117 @@ mod tests {
 
   #[tokio::test]
   async fn test_get_mods_info_full() {
-    let ctx = Arc::new(Context {
-      token: "".to_string(),
-      username: "".to_string(),
-    });
-    let mlist = ModList {
-      mods: vec![
-        ModEntry {
-          name: "RealisticReactorGlow".to_string(),
-          enabled: true,
-        },
-        ModEntry {
-          name: "RealisticReactors".to_string(),
-          enabled: true,
-        },
-        ModEntry {
-          name: "stdlib".to_string(),
-          enabled: true,
-        }
-      ],
-    };
+    let ctx = Arc::new(Context::anonymous().unwrap());
+    let mlist = ModList::new(vec![
+      ModEntry::new("RealisticReactorGlow", true),
+      ModEntry::new("RealisticReactors", true),
+      ModEntry::new("stdlib", true),
+    ]);
 
-    let mods = mlist.get_mods_info(&ctx).await;
+    let mods = mlist.get_mods_info(&ctx).await.unwrap();
     assert_eq!(mods.len(), 3);
     let names = mods.iter().map(|m| m.name.clone()).collect::<Vec<String>>();
     assert!(names.contains(&"RealisticReactorGlow".to_string()));
     assert!(names.contains(&"RealisticReactors".to_string()));
     assert!(names.contains(&"stdlib".to_string()));
   }
-}
\ No newline at end of file
+
+  fn installed(name: &str, version: Option<&str>) -> InstalledMod {
+    InstalledMod {
+      name: name.to_string(),
+      version: version.map(|v| v.to_string()),
+      path: PathBuf::new(),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_load_save() {
+    let folder = std::env::temp_dir().join(format!("furrctorio-modlist-{}", std::process::id()));
+    tokio::fs::create_dir_all(&folder).await.unwrap();
+
+    let missing = ModList::load(&folder).await.unwrap();
+    assert_eq!(missing.mods, vec![ModEntry::new("base", true)]);
+
+    tokio::fs::write(
+      folder.join(ModList::FILE_NAME),
+      r#"{
+  "mods": [
+    {"name": "base", "enabled": true},
+    {"name": "flib", "enabled": false, "version": "0.12.9", "comment": "pinned"}
+  ],
+  "future_field": 42
+}"#,
+    )
+    .await
+    .unwrap();
+
+    let mut list = ModList::load(&folder).await.unwrap();
+    assert_eq!(list.folder(), Some(folder.as_path()));
+    assert_eq!(list.get("flib").unwrap().version.as_deref(), Some("0.12.9"));
+    list.set_enabled("flib", true);
+    list.set_enabled("stdlib", false);
+    list.save().await.unwrap();
+
+    let saved: Value =
+      serde_json::from_str(&tokio::fs::read_to_string(folder.join(ModList::FILE_NAME)).await.unwrap())
+        .unwrap();
+    tokio::fs::remove_dir_all(&folder).await.unwrap();
+
+    assert_eq!(saved["future_field"], 42);
+    assert_eq!(saved["mods"][1]["comment"], "pinned");
+    assert_eq!(saved["mods"][1]["enabled"], true);
+    assert_eq!(saved["mods"][2]["name"], "stdlib");
+    assert!(saved["mods"][2].get("version").is_none());
+  }
+
+  #[tokio::test]
+  async fn test_installed_mods() {
+    let folder = std::env::temp_dir().join(format!("furrctorio-installed-{}", std::process::id()));
+    tokio::fs::create_dir_all(folder.join("my_dev_mod")).await.unwrap();
+    tokio::fs::create_dir_all(folder.join("stdlib_1.0.8")).await.unwrap();
+    for file in ["flib_0.12.9.zip", "Squeak Through_1.8.2.zip", "mod-list.json", "broken.zip"] {
+      tokio::fs::write(folder.join(file), b"").await.unwrap();
+    }
+
+    let mut mods = installed_mods(&folder).await.unwrap();
+    mods.sort_by(|a, b| a.name.cmp(&b.name));
+    tokio::fs::remove_dir_all(&folder).await.unwrap();
+
+    let names: Vec<String> = mods.iter().map(|m| m.to_string()).collect();
+    assert_eq!(
+      names,
+      vec!["Squeak Through 1.8.2", "flib 0.12.9", "my_dev_mod", "stdlib 1.0.8"]
+    );
+  }
+
+  #[test]
+  fn test_reconcile() {
+    let mut list = ModList::new(vec![
+      ModEntry::new("space-age", false),
+      ModEntry::new("flib", true),
+      ModEntry {
+        version: Some("1.0.7".to_string()),
+        ..ModEntry::new("stdlib", false)
+      },
+      ModEntry::new("removed", true),
+    ]);
+
+    list.reconcile(&[
+      installed("flib", Some("0.12.9")),
+      installed("stdlib", Some("1.0.8")),
+      installed("helmod", Some("2.0.0")),
+      installed("helmod", Some("1.9.0")),
+    ]);
+
+    assert_eq!(
+      list.mods,
+      vec![
+        ModEntry::new("base", true),
+        ModEntry::new("space-age", false),
+        ModEntry::new("flib", true),
+        ModEntry::new("stdlib", false),
+        ModEntry::new("helmod", true),
+      ]
+    );
+  }
+}
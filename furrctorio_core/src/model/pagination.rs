@@ -17,7 +17,7 @@ pub struct Pagination {
   /// The total number of items in the list.
   pub count: usize,
   /// Links for navigating through the paginated list.
-  pub(crate) links: PaginationLinks,
+  pub links: PaginationLinks,
   /// The current page number.
   pub page: usize,
   /// The total number of pages in the list.
@@ -26,6 +26,36 @@ pub struct Pagination {
   pub page_size: usize,
 }
 
+impl Pagination {
+  /// Returns the URL of a page after this one.
+  ///
+  /// The page right after this one is the `next` link itself. The URLs of the following pages
+  /// are guessed from it, so they keep the sorting and filters of the listing. There are none
+  /// on the last page, nor past the number of pages.
+  pub fn page_url(&self, page: usize) -> Option<Url> {
+    let next = self.links.next.as_ref()?;
+    if page == self.page + 1 {
+      return Some(next.clone());
+    }
+    if page <= self.page || page > self.page_count {
+      return None;
+    }
+
+    let params: Vec<(String, String)> = next
+      .query_pairs()
+      .filter(|(key, _)| key != "page")
+      .map(|(key, value)| (key.into_owned(), value.into_owned()))
+      .collect();
+    let mut url = next.clone();
+    url
+      .query_pairs_mut()
+      .clear()
+      .extend_pairs(&params)
+      .append_pair("page", &page.to_string());
+    Some(url)
+  }
+}
+
 /// Represents links for navigating through a paginated list.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PaginationLinks {
@@ -38,3 +68,50 @@ pub struct PaginationLinks {
   /// The URL for the previous page of the list.
   pub prev: Option<Url>,
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::model::fmod::FModCategory;
+
+  fn pagination(page: usize, next: Option<&str>) -> Pagination {
+    Pagination {
+      count: 70,
+      links: PaginationLinks {
+        first: None,
+        last: None,
+        next: next.map(|next| Url::parse(next).unwrap()),
+        prev: None,
+      },
+      page,
+      page_count: 3,
+      page_size: 25,
+    }
+  }
+
+  #[test]
+  fn test_page_url() {
+    let first = pagination(1, Some("https://mods.factorio.com/api/mods?page=2&sort=name"));
+    let url = |page| first.page_url(page).map(String::from);
+    assert_eq!(url(2).as_deref(), Some("https://mods.factorio.com/api/mods?page=2&sort=name"));
+    assert_eq!(url(3).as_deref(), Some("https://mods.factorio.com/api/mods?sort=name&page=3"));
+    assert_eq!(url(1), None);
+    assert_eq!(url(4), None);
+
+    assert!(pagination(3, None).page_url(4).is_none());
+  }
+
+  #[test]
+  fn test_list_fixture() {
+    let list: FModList =
+      serde_json::from_str(include_str!("../../tests/fixtures/mod_list.json")).unwrap();
+    assert_eq!(list.pagination.count, 2);
+    assert_eq!(list.results[0].category, FModCategory::Content);
+    assert_eq!(
+      list.results[1].category,
+      FModCategory::Unknown("planets".to_string())
+    );
+    assert_eq!(list.results[1].score, -1.25);
+    assert!(list.results[1].thumbnail.is_none());
+  }
+}
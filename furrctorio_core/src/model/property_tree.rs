@@ -0,0 +1,238 @@
+use crate::{
+  binary::{BinaryReader, BinaryWriter},
+  error::Error,
+};
+use std::fmt::Display;
+
+/// Factorio's property tree, the generic structure stored in `mod-settings.dat`.
+///
+/// Factorio writes every node with its "any type" flag unset, list items with empty keys and
+/// empty strings with their empty flag set. Files following these rules, which are all the
+/// files written by the game, are read and written back byte for byte.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum PropertyTree {
+  #[default]
+  None,
+  Bool(bool),
+  Number(f64),
+  String(String),
+  List(Vec<PropertyTree>),
+  /// Key-value pairs, in file order.
+  Dictionary(Vec<(String, PropertyTree)>),
+  /// Only written by Factorio 2.0 and later.
+  SignedInteger(i64),
+  /// Only written by Factorio 2.0 and later.
+  UnsignedInteger(u64),
+}
+
+impl PropertyTree {
+  /// Returns the value of a key if this tree is a dictionary.
+  pub fn get(&self, key: &str) -> Option<&PropertyTree> {
+    match self {
+      PropertyTree::Dictionary(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+      _ => None,
+    }
+  }
+
+  /// Returns a mutable reference to the value of a key if this tree is a dictionary.
+  pub fn get_mut(&mut self, key: &str) -> Option<&mut PropertyTree> {
+    match self {
+      PropertyTree::Dictionary(entries) => {
+        entries.iter_mut().find(|(k, _)| k == key).map(|(_, v)| v)
+      }
+      _ => None,
+    }
+  }
+
+  /// Sets the value of a key, keeping its position if it already exists.
+  ///
+  /// A tree that is not a dictionary is replaced by an empty dictionary first.
+  pub fn insert(&mut self, key: &str, value: PropertyTree) {
+    if !matches!(self, PropertyTree::Dictionary(_)) {
+      *self = PropertyTree::Dictionary(Vec::new());
+    }
+
+    if let PropertyTree::Dictionary(entries) = self {
+      match entries.iter_mut().find(|(k, _)| k == key) {
+        Some((_, v)) => *v = value,
+        None => entries.push((key.to_string(), value)),
+      }
+    }
+  }
+
+  pub(crate) fn read(reader: &mut BinaryReader) -> Result<Self, Error> {
+    let kind = reader.u8()?;
+    // The "any type" flag has no meaning outside of the game.
+    reader.bool()?;
+
+    Ok(match kind {
+      0 => PropertyTree::None,
+      1 => PropertyTree::Bool(reader.bool()?),
+      2 => PropertyTree::Number(reader.f64()?),
+      3 => PropertyTree::String(Self::read_string(reader)?),
+      4 => {
+        let count = reader.u32()?;
+        let mut items = Vec::new();
+        for _ in 0..count {
+          Self::read_string(reader)?;
+          items.push(Self::read(reader)?);
+        }
+        PropertyTree::List(items)
+      }
+      5 => {
+        let count = reader.u32()?;
+        let mut entries = Vec::new();
+        for _ in 0..count {
+          let key = Self::read_string(reader)?;
+          entries.push((key, Self::read(reader)?));
+        }
+        PropertyTree::Dictionary(entries)
+      }
+      6 => PropertyTree::SignedInteger(reader.i64()?),
+      7 => PropertyTree::UnsignedInteger(reader.u64()?),
+      other => {
+        return Err(Error::ParcingError(format!(
+          "Unknown property tree type {} at offset {}",
+          other,
+          reader.position() - 2
+        )))
+      }
+    })
+  }
+
+  pub(crate) fn write(&self, writer: &mut BinaryWriter) {
+    let kind = match self {
+      PropertyTree::None => 0,
+      PropertyTree::Bool(_) => 1,
+      PropertyTree::Number(_) => 2,
+      PropertyTree::String(_) => 3,
+      PropertyTree::List(_) => 4,
+      PropertyTree::Dictionary(_) => 5,
+      PropertyTree::SignedInteger(_) => 6,
+      PropertyTree::UnsignedInteger(_) => 7,
+    };
+    writer.u8(kind);
+    writer.bool(false);
+
+    match self {
+      PropertyTree::None => (),
+      PropertyTree::Bool(value) => writer.bool(*value),
+      PropertyTree::Number(value) => writer.f64(*value),
+      PropertyTree::String(value) => Self::write_string(writer, value),
+      PropertyTree::List(items) => {
+        writer.u32(items.len() as u32);
+        for item in items {
+          Self::write_string(writer, "");
+          item.write(writer);
+        }
+      }
+      PropertyTree::Dictionary(entries) => {
+        writer.u32(entries.len() as u32);
+        for (key, value) in entries {
+          Self::write_string(writer, key);
+          value.write(writer);
+        }
+      }
+      PropertyTree::SignedInteger(value) => writer.i64(*value),
+      PropertyTree::UnsignedInteger(value) => writer.u64(*value),
+    }
+  }
+
+  /// Strings of property trees start with a flag telling if they are empty.
+  fn read_string(reader: &mut BinaryReader) -> Result<String, Error> {
+    if reader.bool()? {
+      Ok(String::new())
+    } else {
+      reader.string()
+    }
+  }
+
+  fn write_string(writer: &mut BinaryWriter, value: &str) {
+    writer.bool(value.is_empty());
+    if !value.is_empty() {
+      writer.string(value);
+    }
+  }
+}
+
+impl Display for PropertyTree {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      PropertyTree::None => write!(f, "none"),
+      PropertyTree::Bool(value) => write!(f, "{}", value),
+      PropertyTree::Number(value) => write!(f, "{}", value),
+      PropertyTree::String(value) => write!(f, "{:?}", value),
+      PropertyTree::List(items) => {
+        write!(f, "[")?;
+        for (i, item) in items.iter().enumerate() {
+          write!(f, "{}{}", if i > 0 { ", " } else { "" }, item)?;
+        }
+        write!(f, "]")
+      }
+      PropertyTree::Dictionary(entries) => {
+        write!(f, "{{")?;
+        for (i, (key, value)) in entries.iter().enumerate() {
+          write!(f, "{}{}: {}", if i > 0 { ", " } else { "" }, key, value)?;
+        }
+        write!(f, "}}")
+      }
+      PropertyTree::SignedInteger(value) => write!(f, "{}", value),
+      PropertyTree::UnsignedInteger(value) => write!(f, "{}", value),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_round_trip() {
+    let tree = PropertyTree::Dictionary(vec![
+      ("none".to_string(), PropertyTree::None),
+      ("flag".to_string(), PropertyTree::Bool(true)),
+      ("radius".to_string(), PropertyTree::Number(2.5)),
+      ("empty".to_string(), PropertyTree::String(String::new())),
+      ("long".to_string(), PropertyTree::String("x".repeat(300))),
+      (
+        "list".to_string(),
+        PropertyTree::List(vec![PropertyTree::SignedInteger(-3), PropertyTree::UnsignedInteger(7)]),
+      ),
+    ]);
+
+    let mut writer = BinaryWriter::default();
+    tree.write(&mut writer);
+    let data = writer.into_bytes();
+
+    let read = PropertyTree::read(&mut BinaryReader::new(&data)).unwrap();
+    assert_eq!(read, tree);
+
+    let mut rewritten = BinaryWriter::default();
+    read.write(&mut rewritten);
+    assert_eq!(rewritten.into_bytes(), data);
+  }
+
+  #[test]
+  fn test_read_bytes() {
+    // {"value": true}, as written by Factorio.
+    let data = [
+      5, 0, 1, 0, 0, 0, 0, 5, b'v', b'a', b'l', b'u', b'e', 1, 0, 1,
+    ];
+
+    let tree = PropertyTree::read(&mut BinaryReader::new(&data)).unwrap();
+    assert_eq!(tree.get("value"), Some(&PropertyTree::Bool(true)));
+
+    assert!(PropertyTree::read(&mut BinaryReader::new(&[9, 0])).is_err());
+    assert!(PropertyTree::read(&mut BinaryReader::new(&data[..10])).is_err());
+  }
+
+  #[test]
+  fn test_insert() {
+    let mut tree = PropertyTree::None;
+    tree.insert("a", PropertyTree::Number(1.0));
+    tree.insert("b", PropertyTree::Number(2.0));
+    tree.insert("a", PropertyTree::Number(3.0));
+
+    assert_eq!(tree.to_string(), "{a: 3, b: 2}");
+  }
+}
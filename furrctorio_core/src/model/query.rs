@@ -0,0 +1,210 @@
+use crate::{error::Error, model::version::GameVersion};
+use std::{fmt::Display, str::FromStr};
+
+/// The field the mod list is sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortField {
+  Name,
+  CreatedAt,
+  UpdatedAt,
+}
+
+impl Display for SortField {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      SortField::Name => write!(f, "name"),
+      SortField::CreatedAt => write!(f, "created_at"),
+      SortField::UpdatedAt => write!(f, "updated_at"),
+    }
+  }
+}
+
+impl FromStr for SortField {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "name" => Ok(SortField::Name),
+      "created_at" => Ok(SortField::CreatedAt),
+      "updated_at" => Ok(SortField::UpdatedAt),
+      _ => Err(Error::ParcingError(format!("Invalid sort field: {}", s))),
+    }
+  }
+}
+
+/// The direction the mod list is sorted in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+  Asc,
+  Desc,
+}
+
+impl Display for SortOrder {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      SortOrder::Asc => write!(f, "asc"),
+      SortOrder::Desc => write!(f, "desc"),
+    }
+  }
+}
+
+impl FromStr for SortOrder {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "asc" => Ok(SortOrder::Asc),
+      "desc" => Ok(SortOrder::Desc),
+      _ => Err(Error::ParcingError(format!("Invalid sort order: {}", s))),
+    }
+  }
+}
+
+/// How many mods are returned per page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageSize {
+  Count(usize),
+  /// Every matching mod on a single page.
+  Max,
+}
+
+impl Display for PageSize {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      PageSize::Count(count) => write!(f, "{}", count),
+      PageSize::Max => write!(f, "max"),
+    }
+  }
+}
+
+/// The parameters of a request to the mod list of the portal, see [`Context::search`].
+///
+/// Parameters that are not set are left to the portal defaults.
+///
+/// [`Context::search`]: super::context::Context::search
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModQuery {
+  namelist: Vec<String>,
+  page: Option<usize>,
+  page_size: Option<PageSize>,
+  sort: Option<SortField>,
+  sort_order: Option<SortOrder>,
+  hide_deprecated: bool,
+  factorio_version: Option<GameVersion>,
+}
+
+impl ModQuery {
+  /// Creates a query for the first page of every mod.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Only returns the mods with the given names.
+  pub fn namelist<I, S>(mut self, names: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    self.namelist = names.into_iter().map(Into::into).collect();
+    self
+  }
+
+  /// Sets the page to return, starting at 1.
+  pub fn page(mut self, page: usize) -> Self {
+    self.page = Some(page);
+    self
+  }
+
+  pub fn page_size(mut self, page_size: PageSize) -> Self {
+    self.page_size = Some(page_size);
+    self
+  }
+
+  pub fn sort(mut self, sort: SortField) -> Self {
+    self.sort = Some(sort);
+    self
+  }
+
+  pub fn sort_order(mut self, sort_order: SortOrder) -> Self {
+    self.sort_order = Some(sort_order);
+    self
+  }
+
+  /// Leaves out the mods marked as deprecated by their owner.
+  pub fn hide_deprecated(mut self, hide_deprecated: bool) -> Self {
+    self.hide_deprecated = hide_deprecated;
+    self
+  }
+
+  /// Only returns the mods with a release for the given Factorio version.
+  pub fn factorio_version(mut self, factorio_version: GameVersion) -> Self {
+    self.factorio_version = Some(factorio_version);
+    self
+  }
+
+  /// Returns the query parameters of the request.
+  pub fn params(&self) -> Vec<(&'static str, String)> {
+    let mut params = Vec::new();
+    if !self.namelist.is_empty() {
+      params.push(("namelist", self.namelist.join(",")));
+    }
+    if let Some(page) = self.page {
+      params.push(("page", page.to_string()));
+    }
+    if let Some(page_size) = self.page_size {
+      params.push(("page_size", page_size.to_string()));
+    }
+    if let Some(sort) = self.sort {
+      params.push(("sort", sort.to_string()));
+    }
+    if let Some(sort_order) = self.sort_order {
+      params.push(("sort_order", sort_order.to_string()));
+    }
+    if self.hide_deprecated {
+      params.push(("hide_deprecated", "true".to_string()));
+    }
+    if let Some(factorio_version) = &self.factorio_version {
+      params.push(("version", factorio_version.to_string()));
+    }
+    params
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_params() {
+    assert!(ModQuery::new().params().is_empty());
+
+    let query = ModQuery::new()
+      .page(3)
+      .page_size(PageSize::Max)
+      .sort(SortField::UpdatedAt)
+      .sort_order(SortOrder::Desc)
+      .hide_deprecated(true)
+      .factorio_version(GameVersion::V1_1)
+      .namelist(["flib", "stdlib"]);
+
+    assert_eq!(
+      query.params(),
+      vec![
+        ("namelist", "flib,stdlib".to_string()),
+        ("page", "3".to_string()),
+        ("page_size", "max".to_string()),
+        ("sort", "updated_at".to_string()),
+        ("sort_order", "desc".to_string()),
+        ("hide_deprecated", "true".to_string()),
+        ("version", "1.1".to_string()),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_from_str() {
+    assert_eq!(SortField::from_str("created_at").unwrap(), SortField::CreatedAt);
+    assert_eq!(SortOrder::from_str("asc").unwrap(), SortOrder::Asc);
+    assert!(SortField::from_str("downloads").is_err());
+  }
+}
@@ -0,0 +1,423 @@
+use crate::{
+  binary::{ApplicationVersion, BinaryReader},
+  error::Error,
+};
+use chrono::{DateTime, Utc};
+use flate2::read::ZlibDecoder;
+use serde::{Deserialize, Serialize};
+use std::{
+  fmt::Display,
+  io::{Read, Seek},
+  path::{Path, PathBuf},
+};
+use tracing::{instrument, warn};
+use zip::{result::ZipError, ZipArchive};
+
+/// How many bytes of a `level.dat` are read before parsing the header, doubled each time the
+/// header is still incomplete.
+const HEADER_STEP: u64 = 4096;
+
+/// A mod the save was made with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedMod {
+  /// The name of the mod.
+  pub name: String,
+  /// The version of the mod.
+  pub version: String,
+  /// The checksum the game computed for the mod, zero for the base mod and the DLCs.
+  pub crc: u32,
+}
+
+impl Display for SavedMod {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} {}", self.name, self.version)
+  }
+}
+
+/// The header of the `level.dat` of a save, which tells how to load it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveHeader {
+  /// The version of the game that wrote the save.
+  pub version: ApplicationVersion,
+  /// The campaign the save belongs to, empty for freeplay.
+  pub campaign: String,
+  /// The name of the level, like `freeplay`.
+  pub level_name: String,
+  /// The mod providing the level, usually `base`.
+  pub base_mod: String,
+  /// The mods the save was made with, including the base mod and the DLCs.
+  pub mods: Vec<SavedMod>,
+}
+
+impl SaveHeader {
+  /// Parses the start of a decompressed `level.dat`.
+  ///
+  /// Only the header is read, the rest of the map is ignored. Saves written before 0.16
+  /// are not supported.
+  pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+    let mut reader = BinaryReader::new(data);
+    let version = reader.version()?;
+    if (version.major, version.minor) < (0, 16) {
+      return Err(Error::ParcingError(format!(
+        "Saves written by Factorio {} are not supported",
+        version
+      )));
+    }
+    // Like in `mod-settings.dat`, 0.17 added a byte that is always zero.
+    if (version.major, version.minor) >= (0, 17) {
+      reader.u8()?;
+    }
+
+    let campaign = reader.string()?;
+    let level_name = reader.string()?;
+    let base_mod = reader.string()?;
+
+    // Difficulty, finished, player won, next level, can continue, finished but continuing,
+    // saving replay and allow non-admin debug options.
+    reader.u8()?;
+    reader.bytes(2)?;
+    reader.string()?;
+    reader.bytes(4)?;
+    // The version that loaded the save, on three bytes and a u16 build, and the allowed commands.
+    reader.bytes(6)?;
+
+    let count = reader.optimized_u32()?;
+    let mut mods = Vec::new();
+    for _ in 0..count {
+      let name = reader.string()?;
+      let version = format!(
+        "{}.{}.{}",
+        reader.optimized_u16()?,
+        reader.optimized_u16()?,
+        reader.optimized_u16()?
+      );
+      mods.push(SavedMod {
+        name,
+        version,
+        crc: reader.u32()?,
+      });
+    }
+
+    Ok(Self {
+      version,
+      campaign,
+      level_name,
+      base_mod,
+      mods,
+    })
+  }
+
+  /// Reads the header of a save zip.
+  ///
+  /// The header is read from `level.dat` when the save has one, or else from the chunks
+  /// `level.dat0`, `level.dat1`, ... Each file is decompressed a few KiB at a time, only until
+  /// the header is complete, so that the rest of the map is never read. Any other parse error is
+  /// returned right away.
+  pub fn from_zip<R: Read + Seek>(reader: R) -> Result<Self, Error> {
+    let mut archive = ZipArchive::new(reader).map_err(zip_error)?;
+
+    let mut plain = None;
+    let mut chunks = Vec::new();
+    for name in archive.file_names() {
+      let file = name.rsplit('/').next().unwrap_or(name);
+      match file.strip_prefix("level.dat") {
+        Some("") => plain = Some(name.to_string()),
+        Some(index) => {
+          if let Ok(index) = index.parse::<usize>() {
+            chunks.push((index, name.to_string()));
+          }
+        }
+        None => (),
+      }
+    }
+    chunks.sort();
+    let chunks: Vec<String> = match plain {
+      Some(name) => vec![name],
+      None => chunks.into_iter().map(|(_, name)| name).collect(),
+    };
+
+    let mut data = Vec::new();
+    let mut header = Err(Error::ParcingError("The save has no level.dat".to_string()));
+    for name in chunks {
+      let mut file = archive.by_name(&name).map_err(zip_error)?;
+      let mut first = [0; 1];
+      let len = file.read(&mut first).map_err(Error::IoError)?;
+      let start = &first[..len];
+
+      // The version at the start of an uncompressed header never starts with a zlib header.
+      let mut reader: Box<dyn Read + '_> = if start == [0x78] {
+        Box::new(ZlibDecoder::new(start.chain(file)))
+      } else {
+        Box::new(start.chain(file))
+      };
+
+      let mut step = HEADER_STEP;
+      loop {
+        let read = reader
+          .by_ref()
+          .take(step)
+          .read_to_end(&mut data)
+          .map_err(Error::IoError)?;
+        header = Self::from_bytes(&data);
+        if !matches!(header, Err(Error::UnexpectedEnd { .. })) {
+          return header;
+        }
+        if read == 0 {
+          break;
+        }
+        step *= 2;
+      }
+    }
+    header
+  }
+
+  /// Returns the mod with the given name.
+  pub fn get(&self, name: &str) -> Option<&SavedMod> {
+    self.mods.iter().find(|m| m.name == name)
+  }
+}
+
+/// A save found on disk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveFile {
+  /// The name of the save, which is the name of the zip without its extension.
+  pub name: String,
+  /// The zip file of the save.
+  pub path: PathBuf,
+  /// When the save was last written.
+  pub modified: DateTime<Utc>,
+  /// The header of the save.
+  pub header: SaveHeader,
+}
+
+impl SaveFile {
+  /// Reads the header of a save zip.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - The zip file of the save.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<Self, Error>` - Returns the save or an Error if it cannot be read or parsed.
+  #[instrument]
+  pub async fn load(path: &Path) -> Result<Self, Error> {
+    let modified = tokio::fs::metadata(path)
+      .await
+      .and_then(|metadata| metadata.modified())
+      .map_err(Error::IoError)?;
+
+    let file = std::fs::File::open(path).map_err(Error::IoError)?;
+    let header = tokio::task::spawn_blocking(move || SaveHeader::from_zip(file))
+      .await
+      .map_err(|e| Error::IoError(std::io::Error::other(e)))??;
+
+    Ok(Self {
+      name: path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default(),
+      path: path.to_path_buf(),
+      modified: modified.into(),
+      header,
+    })
+  }
+}
+
+impl Display for SaveFile {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{} (Factorio {}, {} mods, {})",
+      self.name,
+      self.header.version,
+      self.header.mods.len(),
+      self.modified.format("%Y-%m-%d %H:%M")
+    )
+  }
+}
+
+/// Lists the saves of a save folder, the most recent first.
+///
+/// Zip files that cannot be read as a save are skipped. A missing folder contains no saves.
+///
+/// # Arguments
+///
+/// * `folder` - The save folder, usually the `saves` folder next to the mod folder.
+///
+/// # Returns
+///
+/// * `Result<Vec<SaveFile>, Error>` - Returns the saves or an Error if the folder cannot be read.
+pub async fn list_saves(folder: &Path) -> Result<Vec<SaveFile>, Error> {
+  let mut saves = Vec::new();
+  let mut dir = match tokio::fs::read_dir(folder).await {
+    Ok(dir) => dir,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(saves),
+    Err(e) => return Err(Error::IoError(e)),
+  };
+
+  while let Some(file) = dir.next_entry().await.map_err(Error::IoError)? {
+    let path = file.path();
+    if path.extension().is_none_or(|extension| extension != "zip") {
+      continue;
+    }
+
+    match SaveFile::load(&path).await {
+      Ok(save) => saves.push(save),
+      Err(e) => warn!("Skipping '{}': {}", path.display(), e),
+    }
+  }
+
+  saves.sort_by_key(|save| std::cmp::Reverse(save.modified));
+  Ok(saves)
+}
+
+fn zip_error(error: ZipError) -> Error {
+  match error {
+    ZipError::Io(e) => Error::IoError(e),
+    e => Error::ParcingError(format!("Invalid save file: {}", e)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::binary::BinaryWriter;
+  use flate2::{write::ZlibEncoder, Compression};
+  use std::io::{Cursor, Write};
+  use zip::{write::SimpleFileOptions, ZipWriter};
+
+  /// The header of a freeplay save written by Factorio 1.1.110 with two mods.
+  fn header() -> Vec<u8> {
+    let mut writer = BinaryWriter::default();
+    writer.version(&ApplicationVersion::new(1, 1, 110, 0));
+    writer.u8(0);
+    writer.string("");
+    writer.string("freeplay");
+    writer.string("base");
+    writer.u8(1);
+    for _ in 0..2 {
+      writer.bool(false);
+    }
+    writer.string("");
+    for _ in 0..4 {
+      writer.bool(false);
+    }
+    for byte in [1, 1, 110, 0, 0, 1] {
+      writer.u8(byte);
+    }
+    writer.optimized_u32(2);
+    for (name, version, crc) in [("base", [1, 1, 110], 0), ("flib", [0, 12, 9], 0xdeadbeef)] {
+      writer.string(name);
+      for part in version {
+        writer.u8(part);
+      }
+      writer.u32(crc);
+    }
+    // The rest of the map.
+    writer.u64(0);
+    writer.into_bytes()
+  }
+
+  fn save_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, data) in files {
+      zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+      zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+  }
+
+  #[test]
+  fn test_from_bytes() {
+    let save = SaveHeader::from_bytes(&header()).unwrap();
+
+    assert_eq!(save.version, ApplicationVersion::new(1, 1, 110, 0));
+    assert_eq!(save.level_name, "freeplay");
+    assert_eq!(save.base_mod, "base");
+    assert_eq!(
+      save.get("flib"),
+      Some(&SavedMod {
+        name: "flib".to_string(),
+        version: "0.12.9".to_string(),
+        crc: 0xdeadbeef,
+      })
+    );
+    assert_eq!(save.mods[0].to_string(), "base 1.1.110");
+
+    assert!(SaveHeader::from_bytes(&header()[..60]).is_err());
+    assert!(SaveHeader::from_bytes(&[0, 0, 15, 0, 0, 0, 0, 0]).is_err());
+  }
+
+  #[test]
+  fn test_from_zip() {
+    let data = header();
+    let plain = save_zip(&[("my-save/control.lua", b""), ("my-save/level.dat", &data)]);
+    assert_eq!(SaveHeader::from_zip(Cursor::new(plain)).unwrap().mods.len(), 2);
+
+    // Split the header over two compressed chunks, listed out of order.
+    let compress = |data: &[u8]| {
+      let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+      encoder.write_all(data).unwrap();
+      encoder.finish().unwrap()
+    };
+    let chunked = save_zip(&[
+      ("my-save/level.dat1", &compress(&data[40..])),
+      ("my-save/level.dat0", &compress(&data[..40])),
+      ("my-save/level-init.dat", b""),
+    ]);
+    assert_eq!(
+      SaveHeader::from_zip(Cursor::new(chunked)).unwrap(),
+      SaveHeader::from_bytes(&data).unwrap()
+    );
+
+    // An unsupported header fails on the first chunk, the next one is never decompressed.
+    let old = save_zip(&[
+      ("my-save/level.dat0", &compress(&[0, 0, 15, 0, 0, 0, 0, 0])),
+      ("my-save/level.dat1", &[0x78, 0xff, 0xff]),
+    ]);
+    assert!(matches!(
+      SaveHeader::from_zip(Cursor::new(old)),
+      Err(Error::ParcingError(message)) if message.contains("not supported")
+    ));
+
+    // Only the start of a large map is decompressed, the corrupt end is never reached.
+    let mut map = data.clone();
+    let mut seed = 0x5eed_u32;
+    map.extend((0..1 << 20).map(|_| {
+      seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+      (seed >> 16) as u8
+    }));
+    let compressed = compress(&map);
+    let large = save_zip(&[("my-save/level.dat", &compressed[..compressed.len() / 2])]);
+    assert_eq!(
+      SaveHeader::from_zip(Cursor::new(large)).unwrap(),
+      SaveHeader::from_bytes(&data).unwrap()
+    );
+
+    let empty = save_zip(&[("my-save/control.lua", b"")]);
+    assert!(SaveHeader::from_zip(Cursor::new(empty)).is_err());
+    assert!(SaveHeader::from_zip(Cursor::new(b"not a zip".to_vec())).is_err());
+  }
+
+  #[tokio::test]
+  async fn test_list_saves() {
+    let folder = std::env::temp_dir().join(format!("furrctorio-saves-{}", std::process::id()));
+    tokio::fs::create_dir_all(&folder).await.unwrap();
+    assert!(list_saves(&folder.join("missing")).await.unwrap().is_empty());
+
+    let data = header();
+    tokio::fs::write(folder.join("my-save.zip"), save_zip(&[("my-save/level.dat", &data)]))
+      .await
+      .unwrap();
+    tokio::fs::write(folder.join("broken.zip"), b"").await.unwrap();
+    tokio::fs::write(folder.join("notes.txt"), b"").await.unwrap();
+
+    let saves = list_saves(&folder).await.unwrap();
+    tokio::fs::remove_dir_all(&folder).await.unwrap();
+
+    assert_eq!(saves.len(), 1);
+    assert_eq!(saves[0].name, "my-save");
+    assert!(saves[0].to_string().starts_with("my-save (Factorio 1.1.110, 2 mods, "));
+  }
+}
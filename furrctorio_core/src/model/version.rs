@@ -0,0 +1,581 @@
+use crate::{constants::SPACE_AGE_MODS, error::Error};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+  cmp::Ordering,
+  fmt::Display,
+  hash::{Hash, Hasher},
+  str::FromStr,
+};
+
+/// A version as Factorio reads it: three numbers from 0 to 65535, like `1.1.110`.
+///
+/// Unlike semver, leading zeros are allowed, so `0.18.05` is the same version as `0.18.5`.
+/// The text a version was parsed from is kept, so that it is written back unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct FactorioVersion {
+  major: u16,
+  minor: u16,
+  patch: u16,
+  /// The parsed text, when it differs from the canonical form.
+  text: Option<String>,
+}
+
+impl FactorioVersion {
+  pub const fn new(major: u16, minor: u16, patch: u16) -> Self {
+    Self {
+      major,
+      minor,
+      patch,
+      text: None,
+    }
+  }
+
+  pub fn major(&self) -> u16 {
+    self.major
+  }
+
+  pub fn minor(&self) -> u16 {
+    self.minor
+  }
+
+  pub fn patch(&self) -> u16 {
+    self.patch
+  }
+
+  /// Returns the same version as semver, to match it against a `semver::VersionReq`.
+  pub fn to_semver(&self) -> semver::Version {
+    semver::Version::new(self.major.into(), self.minor.into(), self.patch.into())
+  }
+
+  fn parts(&self) -> (u16, u16, u16) {
+    (self.major, self.minor, self.patch)
+  }
+}
+
+impl FromStr for FactorioVersion {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = || Error::ParcingError(format!("Invalid version: '{}'", s));
+    let parts = s
+      .trim()
+      .split('.')
+      .map(|part| match part.bytes().all(|b| b.is_ascii_digit()) {
+        true => part.parse::<u16>().map_err(|_| invalid()),
+        false => Err(invalid()),
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let [major, minor, patch] = parts[..] else {
+      return Err(invalid());
+    };
+    let mut version = Self::new(major, minor, patch);
+    if version.to_string() != s {
+      version.text = Some(s.to_string());
+    }
+    Ok(version)
+  }
+}
+
+impl Display for FactorioVersion {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match &self.text {
+      Some(text) => write!(f, "{}", text),
+      None => write!(f, "{}.{}.{}", self.major, self.minor, self.patch),
+    }
+  }
+}
+
+impl PartialEq for FactorioVersion {
+  fn eq(&self, other: &Self) -> bool {
+    self.parts() == other.parts()
+  }
+}
+
+impl Eq for FactorioVersion {}
+
+impl PartialOrd for FactorioVersion {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for FactorioVersion {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.parts().cmp(&other.parts())
+  }
+}
+
+impl Hash for FactorioVersion {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.parts().hash(state);
+  }
+}
+
+impl<'de> Deserialize<'de> for FactorioVersion {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let s = String::deserialize(deserializer)?;
+    FromStr::from_str(&s).map_err(|e: Error| de::Error::custom(e))
+  }
+}
+
+impl Serialize for FactorioVersion {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    self.to_string().serialize(serializer)
+  }
+}
+
+impl From<&semver::Version> for FactorioVersion {
+  /// Converts a semver version, numbers above 65535 are clamped.
+  fn from(version: &semver::Version) -> Self {
+    let part = |n: u64| u16::try_from(n).unwrap_or(u16::MAX);
+    Self::new(part(version.major), part(version.minor), part(version.patch))
+  }
+}
+
+/// A comparison operator of a version requirement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VersionOp {
+  Less,
+  LessEq,
+  Exact,
+  GreaterEq,
+  Greater,
+}
+
+impl Display for VersionOp {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      VersionOp::Less => write!(f, "<"),
+      VersionOp::LessEq => write!(f, "<="),
+      VersionOp::Exact => write!(f, "="),
+      VersionOp::GreaterEq => write!(f, ">="),
+      VersionOp::Greater => write!(f, ">"),
+    }
+  }
+}
+
+/// A single comparison, like `>= 1.1.0`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VersionComparator {
+  pub op: VersionOp,
+  pub version: FactorioVersion,
+}
+
+impl VersionComparator {
+  /// Returns true if the version passes the comparison.
+  pub fn matches(&self, version: &FactorioVersion) -> bool {
+    let ordering = version.cmp(&self.version);
+    match self.op {
+      VersionOp::Less => ordering == Ordering::Less,
+      VersionOp::LessEq => ordering != Ordering::Greater,
+      VersionOp::Exact => ordering == Ordering::Equal,
+      VersionOp::GreaterEq => ordering != Ordering::Less,
+      VersionOp::Greater => ordering == Ordering::Greater,
+    }
+  }
+}
+
+impl Display for VersionComparator {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} {}", self.op, self.version)
+  }
+}
+
+impl From<VersionComparator> for FactorioVersionReq {
+  fn from(comparator: VersionComparator) -> Self {
+    Self::new(comparator.op, comparator.version)
+  }
+}
+
+/// A version requirement with the semantics of Factorio: plain comparisons that must all hold.
+///
+/// Comparisons are written like in `info.json`, `<`, `<=`, `=`, `>=` or `>` followed by a
+/// version where missing numbers are 0, so `> 1.1` matches `1.1.1`. Several comparisons are
+/// separated by commas, like `>= 1.1, < 2.0`, and `*` matches every version.
+///
+/// For convenience in the configuration, semver-style ranges are also read, and turned into
+/// comparisons:
+///
+/// * A bare version is exact: `1.2.3` is `= 1.2.3`, unlike semver where it means `^1.2.3`.
+/// * `^1.2.3` is `>= 1.2.3, < 2.0.0`, with the semver rule for 0.x: `^0.2.3` is `< 0.3.0`
+///   and `^0.0.3` is `< 0.0.4`.
+/// * `~1.2.3` and `~1.2` are `>= 1.2.x, < 1.3.0`, `~1` is `>= 1.0.0, < 2.0.0`.
+/// * `1.*` is `>= 1.0.0, < 2.0.0` and `1.2.*` is `>= 1.2.0, < 1.3.0`.
+///
+/// Unlike semver, there are no pre-release versions, so no comparison treats them apart.
+/// The text a requirement was parsed from is kept, so that it is written back unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct FactorioVersionReq {
+  comparators: Vec<VersionComparator>,
+  /// The parsed text, when it differs from the canonical form.
+  text: Option<String>,
+}
+
+impl FactorioVersionReq {
+  /// Matches every version.
+  pub const STAR: Self = Self {
+    comparators: Vec::new(),
+    text: None,
+  };
+
+  /// Creates a requirement with a single comparison.
+  pub fn new(op: VersionOp, version: FactorioVersion) -> Self {
+    Self {
+      comparators: vec![VersionComparator { op, version }],
+      text: None,
+    }
+  }
+
+  pub fn comparators(&self) -> &[VersionComparator] {
+    &self.comparators
+  }
+
+  /// Returns true if the version passes every comparison.
+  pub fn matches(&self, version: &FactorioVersion) -> bool {
+    self.comparators.iter().all(|c| c.matches(version))
+  }
+
+  /// Parses one comparison or range, like `>= 1.1` or `^0.12`, into comparisons.
+  fn parse_part(part: &str) -> Option<Vec<VersionComparator>> {
+    if matches!(part, "*" | "x" | "X") {
+      return Some(Vec::new());
+    }
+
+    let (op, rest) = ["<=", ">=", "<", ">", "=", "^", "~"]
+      .into_iter()
+      .find_map(|op| part.strip_prefix(op).map(|rest| (op, rest.trim_start())))
+      .unwrap_or(("", part));
+
+    let mut numbers = Vec::new();
+    let mut wildcard = false;
+    for number in rest.split('.') {
+      match number {
+        "*" | "x" | "X" if !wildcard && !numbers.is_empty() => wildcard = true,
+        _ if !wildcard && !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) => {
+          numbers.push(number.parse::<u16>().ok()?)
+        }
+        _ => return None,
+      }
+    }
+    if numbers.len() + usize::from(wildcard) > 3 {
+      return None;
+    }
+
+    let at = |i: usize| numbers.get(i).copied().unwrap_or(0);
+    let lower = FactorioVersion::new(at(0), at(1), at(2));
+    let comparator = |op, version| VersionComparator { op, version };
+    // The first version above the range, when it exists.
+    let bump = |index: usize| {
+      let mut parts = [at(0), at(1), at(2)];
+      parts[index] = parts[index].checked_add(1)?;
+      parts[index + 1..].fill(0);
+      Some(comparator(VersionOp::Less, FactorioVersion::new(parts[0], parts[1], parts[2])))
+    };
+    let range = |index: usize| {
+      let lower = comparator(VersionOp::GreaterEq, lower.clone());
+      std::iter::once(lower).chain(bump(index)).collect::<Vec<_>>()
+    };
+
+    match (op, wildcard) {
+      ("", true) => Some(range(numbers.len() - 1)),
+      (_, true) => None,
+      ("" | "=", false) => Some(vec![comparator(VersionOp::Exact, lower)]),
+      ("<", false) => Some(vec![comparator(VersionOp::Less, lower)]),
+      ("<=", false) => Some(vec![comparator(VersionOp::LessEq, lower)]),
+      (">=", false) => Some(vec![comparator(VersionOp::GreaterEq, lower)]),
+      (">", false) => Some(vec![comparator(VersionOp::Greater, lower)]),
+      ("~", false) => Some(range(if numbers.len() == 1 { 0 } else { 1 })),
+      ("^", false) => {
+        // The first non-zero number given is the one that can't change.
+        let index = (0..numbers.len())
+          .find(|&i| numbers[i] != 0)
+          .unwrap_or(numbers.len() - 1);
+        Some(range(index))
+      }
+      _ => None,
+    }
+  }
+}
+
+impl PartialEq for FactorioVersionReq {
+  fn eq(&self, other: &Self) -> bool {
+    self.comparators == other.comparators
+  }
+}
+
+impl Eq for FactorioVersionReq {}
+
+impl FromStr for FactorioVersionReq {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = || Error::ParcingError(format!("Invalid version requirement: '{}'", s));
+    let mut comparators = Vec::new();
+    for part in s.split(',') {
+      comparators.extend(Self::parse_part(part.trim()).ok_or_else(invalid)?);
+    }
+
+    let mut req = Self {
+      comparators,
+      text: None,
+    };
+    if req.to_string() != s {
+      req.text = Some(s.to_string());
+    }
+    Ok(req)
+  }
+}
+
+impl Display for FactorioVersionReq {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if let Some(text) = &self.text {
+      return write!(f, "{}", text);
+    }
+    if self.comparators.is_empty() {
+      return write!(f, "*");
+    }
+    for (i, comparator) in self.comparators.iter().enumerate() {
+      if i > 0 {
+        write!(f, ", ")?;
+      }
+      write!(f, "{}", comparator)?;
+    }
+    Ok(())
+  }
+}
+
+impl<'de> Deserialize<'de> for FactorioVersionReq {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let s = String::deserialize(deserializer)?;
+    FromStr::from_str(&s).map_err(|e: Error| de::Error::custom(e))
+  }
+}
+
+impl Serialize for FactorioVersionReq {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    self.to_string().serialize(serializer)
+  }
+}
+
+/// A major version of the game, like `1.1` or `2.0`, as in the `factorio_version` of `info.json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GameVersion {
+  pub major: u16,
+  pub minor: u16,
+}
+
+impl GameVersion {
+  pub const V0_12: Self = Self::new(0, 12);
+  pub const V0_13: Self = Self::new(0, 13);
+  pub const V0_14: Self = Self::new(0, 14);
+  pub const V0_15: Self = Self::new(0, 15);
+  pub const V0_16: Self = Self::new(0, 16);
+  pub const V0_17: Self = Self::new(0, 17);
+  pub const V0_18: Self = Self::new(0, 18);
+  pub const V1_0: Self = Self::new(1, 0);
+  pub const V1_1: Self = Self::new(1, 1);
+  pub const V2_0: Self = Self::new(2, 0);
+
+  pub const fn new(major: u16, minor: u16) -> Self {
+    Self { major, minor }
+  }
+
+  /// Returns true if this version of the game loads mods made for `mod_version`.
+  ///
+  /// The game only loads mods made for its own major version, except that 1.0 still loads
+  /// 0.18 mods and 1.1 still loads 1.0 mods.
+  pub fn loads(&self, mod_version: GameVersion) -> bool {
+    *self == mod_version
+      || (*self == Self::V1_0 && mod_version == Self::V0_18)
+      || (*self == Self::V1_1 && mod_version == Self::V1_0)
+  }
+
+  /// Returns the mods that come with this version of the game.
+  ///
+  /// # Arguments
+  ///
+  /// * `space_age` - True if the Space Age expansion is owned, its mods only come with 2.0 and later.
+  pub fn builtin_mods(&self, space_age: bool) -> Vec<&'static str> {
+    let mut mods = vec!["base"];
+    if space_age && *self >= Self::V2_0 {
+      mods.extend(SPACE_AGE_MODS);
+    }
+    mods
+  }
+}
+
+impl From<&FactorioVersion> for GameVersion {
+  fn from(version: &FactorioVersion) -> Self {
+    Self::new(version.major, version.minor)
+  }
+}
+
+impl From<&semver::Version> for GameVersion {
+  /// Keeps the major and minor numbers of a full game version, like `1.1.110`.
+  fn from(version: &semver::Version) -> Self {
+    let part = |n: u64| u16::try_from(n).unwrap_or(u16::MAX);
+    Self::new(part(version.major), part(version.minor))
+  }
+}
+
+impl FromStr for GameVersion {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = || Error::ParcingError(format!("Invalid Factorio version: '{}'", s));
+    let (major, minor) = s.trim().split_once('.').ok_or_else(invalid)?;
+    let part = |part: &str| match part.bytes().all(|b| b.is_ascii_digit()) {
+      true => part.parse::<u16>().map_err(|_| invalid()),
+      false => Err(invalid()),
+    };
+    Ok(Self::new(part(major)?, part(minor)?))
+  }
+}
+
+impl Display for GameVersion {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}.{}", self.major, self.minor)
+  }
+}
+
+impl<'de> Deserialize<'de> for GameVersion {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let s = String::deserialize(deserializer)?;
+    FromStr::from_str(&s).map_err(|e: Error| de::Error::custom(e))
+  }
+}
+
+impl Serialize for GameVersion {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    self.to_string().serialize(serializer)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_from_str() {
+    let version: FactorioVersion = "0.18.05".parse().unwrap();
+    assert_eq!(version, FactorioVersion::new(0, 18, 5));
+    assert_eq!(version.to_string(), "0.18.05");
+    assert_eq!(serde_json::to_string(&version).unwrap(), "\"0.18.05\"");
+    assert_eq!("0.0.3".parse::<FactorioVersion>().unwrap().to_string(), "0.0.3");
+    assert_eq!("65535.0.1".parse::<FactorioVersion>().unwrap().major(), 65535);
+
+    for invalid in ["", "1.1", "1.1.1.1", "65536.0.0", "1.-1.0", "1.+1.0", "1.1.0-beta", "a.b.c"] {
+      assert!(invalid.parse::<FactorioVersion>().is_err(), "{}", invalid);
+    }
+  }
+
+  #[test]
+  fn test_ord() {
+    let mut versions: Vec<FactorioVersion> = ["1.1.110", "0.18.05", "0.18.10", "0.0.3", "1.1.9"]
+      .iter()
+      .map(|v| v.parse().unwrap())
+      .collect();
+    versions.sort();
+
+    let sorted: Vec<String> = versions.iter().map(ToString::to_string).collect();
+    assert_eq!(sorted, ["0.0.3", "0.18.05", "0.18.10", "1.1.9", "1.1.110"]);
+    assert_eq!(
+      "0.18.05".parse::<FactorioVersion>().unwrap().to_semver(),
+      semver::Version::new(0, 18, 5)
+    );
+  }
+
+  #[test]
+  fn test_game_version() {
+    let version: GameVersion = "2.0".parse().unwrap();
+    assert_eq!(version, GameVersion::V2_0);
+    assert_eq!(version.to_string(), "2.0");
+    assert!(GameVersion::V0_18 < GameVersion::V1_0);
+    assert!(GameVersion::V1_1 < GameVersion::V2_0);
+    assert!("0.9".parse::<GameVersion>().unwrap() < GameVersion::V0_13);
+    assert_eq!(GameVersion::from(&semver::Version::new(1, 1, 110)), GameVersion::V1_1);
+    for invalid in ["", "2", "2.x", "1.1.110", "70000.0"] {
+      assert!(invalid.parse::<GameVersion>().is_err(), "{}", invalid);
+    }
+
+    assert!(GameVersion::V1_0.loads(GameVersion::V0_18));
+    assert!(GameVersion::V1_1.loads(GameVersion::V1_0));
+    assert!(GameVersion::V1_1.loads(GameVersion::V1_1));
+    assert!(!GameVersion::V1_1.loads(GameVersion::V0_18));
+    assert!(!GameVersion::V2_0.loads(GameVersion::V1_1));
+    assert!(!GameVersion::V1_1.loads(GameVersion::V2_0));
+
+    assert_eq!(GameVersion::V1_1.builtin_mods(true), ["base"]);
+    assert_eq!(GameVersion::V2_0.builtin_mods(false), ["base"]);
+    assert_eq!(
+      GameVersion::V2_0.builtin_mods(true),
+      ["base", "elevated-rails", "quality", "space-age"]
+    );
+  }
+
+  #[test]
+  fn test_version_req() {
+    let matches = |req: &str, version: &str| {
+      let req: FactorioVersionReq = req.parse().unwrap();
+      req.matches(&version.parse().unwrap())
+    };
+
+    // Plain comparisons, where missing numbers are 0.
+    assert!(matches("> 1.1", "1.1.1"));
+    assert!(!matches("> 1.1", "1.1.0"));
+    assert!(matches("<= 1.1", "1.1.0"));
+    assert!(!matches("<= 1.1", "1.1.1"));
+    assert!(matches("=1.8.02", "1.8.2"));
+    assert!(matches(">= 1.1, < 2.0", "1.1.110"));
+    assert!(!matches(">= 1.1, < 2.0", "2.0.0"));
+    assert!(matches("*", "0.0.1"));
+
+    // Semver-style ranges.
+    assert!(matches("1.2.3", "1.2.3"));
+    assert!(!matches("1.2.3", "1.2.4"));
+    assert!(matches("^1.2", "1.9.0"));
+    assert!(!matches("^1.2", "2.0.0"));
+    assert!(matches("^0.12", "0.12.9"));
+    assert!(!matches("^0.12", "0.13.0"));
+    assert!(!matches("^0.0.3", "0.0.4"));
+    assert!(matches("~1.2.3", "1.2.9"));
+    assert!(!matches("~1.2.3", "1.3.0"));
+    assert!(matches("~1", "1.9.0"));
+    assert!(matches("1.*", "1.9.9"));
+    assert!(!matches("1.2.*", "1.3.0"));
+    assert!(matches("^65535", "65535.1.0"));
+
+    let req: FactorioVersionReq = "^0.12".parse().unwrap();
+    assert_eq!(req, ">= 0.12.0, < 0.13.0".parse().unwrap());
+    assert_eq!(req.to_string(), "^0.12");
+    assert_eq!(serde_json::to_string(&req).unwrap(), "\"^0.12\"");
+    assert_eq!(
+      FactorioVersionReq::new(VersionOp::GreaterEq, FactorioVersion::new(1, 1, 0)).to_string(),
+      ">= 1.1.0"
+    );
+    assert_eq!(FactorioVersionReq::STAR.to_string(), "*");
+
+    for invalid in ["", ">=", "1.2.3.4", "a", ">= 1.*", "1.*.3", "=> 1.0", "1.0,", "70000"] {
+      assert!(invalid.parse::<FactorioVersionReq>().is_err(), "{}", invalid);
+    }
+  }
+}
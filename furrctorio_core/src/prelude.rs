@@ -1,8 +1,20 @@
 pub use crate::{
-  error::Error,
+  binary::ApplicationVersion,
+  cache::{CachedEndpoint, MetadataCache},
+  credentials::{CredentialChain, CredentialError, CredentialSource, Credentials},
+  download::{DownloadEvent, Downloader},
+  error::{Error, LoginError},
+  retry::RetryPolicy,
+  selector::{NoCompatibleRelease, Rejection, ReleasePolicy, ReleaseSelector},
   model::{
-    context::Context,
+    context::{Context, ContextBuilder, Endpoints},
+    dependency::*,
     fmod::*,
     modlist::*,
+    mod_settings::{ModSettings, SettingsSection},
+    property_tree::PropertyTree,
+    query::{ModQuery, PageSize, SortField, SortOrder},
+    save::{list_saves, SaveFile, SaveHeader, SavedMod},
+    version::{FactorioVersion, FactorioVersionReq, GameVersion, VersionComparator, VersionOp},
   }
 };
\ No newline at end of file
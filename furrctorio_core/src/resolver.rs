@@ -0,0 +1,970 @@
+//! Dependency resolution over the `info.json` dependencies of mod releases.
+//!
+//! The [`Resolver`] picks exactly one release per mod so that every required dependency is
+//! present, every version range is respected and no incompatible mods are selected together.
+//! It explores releases from the newest to the oldest. When every release of a mod fails, it
+//! jumps back to the latest mod whose selection caused the failures, skipping the mods that
+//! have nothing to do with them. When no solution exists, the returned [`Conflict`] explains
+//! why, level by level.
+
+use crate::{
+  constants::BUILTIN_MODS,
+  error::Error,
+  model::{
+    context::Context,
+    dependency::{FModDependecies, FModPreffix},
+    fmod::FModRelease,
+    version::{FactorioVersion, FactorioVersionReq, GameVersion},
+  },
+};
+use std::{
+  collections::{BTreeMap, HashMap, HashSet},
+  fmt::Display,
+  future::Future,
+};
+use tracing::{debug, instrument, trace};
+
+/// Something that can list the releases of a mod.
+pub trait ReleaseSource {
+  /// Returns every release of a mod, in any order.
+  fn releases(&self, name: &str) -> impl Future<Output = Result<Vec<FModRelease>, Error>>;
+}
+
+impl ReleaseSource for Context {
+  async fn releases(&self, name: &str) -> Result<Vec<FModRelease>, Error> {
+    self.get_mod_info_full(name).await.map(|fmod| fmod.releases)
+  }
+}
+
+impl ReleaseSource for HashMap<String, Vec<FModRelease>> {
+  async fn releases(&self, name: &str) -> Result<Vec<FModRelease>, Error> {
+    Ok(self.get(name).cloned().unwrap_or_default())
+  }
+}
+
+/// What introduced a constraint.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cause {
+  /// The requirements given to the resolver.
+  Root,
+  /// The dependencies of a release.
+  Release { name: String, version: String },
+}
+
+impl Display for Cause {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Cause::Root => write!(f, "the configuration"),
+      Cause::Release { name, version } => write!(f, "{} {}", name, version),
+    }
+  }
+}
+
+/// The kind of a constraint on a mod.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstraintKind {
+  /// The mod must be selected, in a matching version.
+  Requires(Option<FactorioVersionReq>),
+  /// If the mod is selected, it must be in a matching version.
+  RequiresIfPresent(Option<FactorioVersionReq>),
+  /// The mod must not be selected in a matching version.
+  Incompatible(Option<FactorioVersionReq>),
+}
+
+/// A constraint on the release selected for a mod.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraint {
+  /// The name of the constrained mod.
+  pub target: String,
+  /// What the constraint requires.
+  pub kind: ConstraintKind,
+  /// What introduced the constraint.
+  pub cause: Cause,
+}
+
+impl Constraint {
+  /// Converts a dependency of a release into a constraint.
+  pub fn from_dependency(dep: &FModDependecies, cause: Cause) -> Self {
+    let req = dep.required_version().cloned().map(FactorioVersionReq::from);
+    Self {
+      target: dep.name().to_string(),
+      kind: match dep.preffix() {
+        FModPreffix::Required | FModPreffix::NonChanging => ConstraintKind::Requires(req),
+        FModPreffix::Optional | FModPreffix::HiddenOptional => {
+          ConstraintKind::RequiresIfPresent(req)
+        }
+        FModPreffix::Incompatible => ConstraintKind::Incompatible(req),
+      },
+      cause,
+    }
+  }
+
+  fn is_required(&self) -> bool {
+    matches!(self.kind, ConstraintKind::Requires(_))
+  }
+
+  /// Checks whether a selection of the target mod satisfies the constraint.
+  fn allows(&self, selected: &Selected) -> bool {
+    let matches = |req: &Option<FactorioVersionReq>| match (req, selected) {
+      (None, _) => true,
+      (Some(req), Selected::Release(release)) => release.match_version(req),
+      (Some(req), Selected::Provided(Some(version))) => req.matches(version),
+      (Some(_), Selected::Provided(None)) => true,
+    };
+
+    match &self.kind {
+      ConstraintKind::Requires(req) | ConstraintKind::RequiresIfPresent(req) => matches(req),
+      ConstraintKind::Incompatible(req) => !matches(req),
+    }
+  }
+
+  /// Describes what the constraint requires, without its cause.
+  fn describe(&self) -> String {
+    let (verb, req) = match &self.kind {
+      ConstraintKind::Requires(r) => ("requires", r),
+      ConstraintKind::RequiresIfPresent(r) => ("optionally requires", r),
+      ConstraintKind::Incompatible(r) => ("is incompatible with", r),
+    };
+
+    match req {
+      Some(req) => format!("{} {} {}", verb, self.target, req),
+      None => format!("{} {}", verb, self.target),
+    }
+  }
+}
+
+impl Display for Constraint {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} {}", self.cause, self.describe())
+  }
+}
+
+/// How a mod is currently satisfied.
+enum Selected<'a> {
+  Release(&'a FModRelease),
+  /// Provided by the game, with its version if known.
+  Provided(Option<&'a FactorioVersion>),
+}
+
+impl Display for Selected<'_> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Selected::Release(release) => write!(f, "{}", release.version),
+      Selected::Provided(Some(version)) => write!(f, "{} (provided by the game)", version),
+      Selected::Provided(None) => write!(f, "(provided by the game)"),
+    }
+  }
+}
+
+/// Why a release of a mod could not be selected.
+#[derive(Debug, Clone)]
+pub enum FailureReason {
+  /// One of its dependencies rejects a mod that is already selected.
+  Dependency {
+    constraint: Constraint,
+    selected: String,
+  },
+  /// It was selected, but its dependencies could not be resolved.
+  Conflict(Box<Conflict>),
+}
+
+/// A release that was tried and rejected.
+#[derive(Debug, Clone)]
+pub struct CandidateFailure {
+  pub version: String,
+  pub reason: FailureReason,
+}
+
+/// Explains why no release of a mod could be selected.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+  /// The name of the mod.
+  pub name: String,
+  /// The constraints on the mod when it was resolved.
+  pub constraints: Vec<Constraint>,
+  /// The number of known releases of the mod.
+  pub releases: usize,
+  /// The number of releases rejected by the constraints.
+  pub filtered: usize,
+  /// The version of the game the releases must load on, if known.
+  pub game_version: Option<GameVersion>,
+  /// The number of releases rejected because the game doesn't load them.
+  pub incompatible: usize,
+  /// The version provided by the game, if the mod comes with it.
+  pub provided: Option<Option<FactorioVersion>>,
+  /// True if the mod comes with the game, but not with the installed one, like the mods of an
+  /// expansion that isn't owned.
+  pub unavailable: bool,
+  /// The releases that matched the constraints but failed anyway.
+  pub failures: Vec<CandidateFailure>,
+}
+
+impl Conflict {
+  /// The number of tried releases shown before the rest are summarized.
+  const SHOWN_FAILURES: usize = 5;
+
+  fn render(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+    let pad = "  ".repeat(depth);
+
+    writeln!(f, "{}{} cannot be resolved:", pad, self.name)?;
+    for constraint in &self.constraints {
+      writeln!(f, "{}  - {}", pad, constraint)?;
+    }
+
+    match &self.provided {
+      Some(Some(version)) => writeln!(
+        f,
+        "{}  - but the game provides {} {}",
+        pad, self.name, version
+      )?,
+      Some(None) => writeln!(f, "{}  - but {} is provided by the game", pad, self.name)?,
+      None if self.unavailable => writeln!(
+        f,
+        "{}  - but {} only comes with Factorio 2.0 and the Space Age expansion, which the game doesn't have",
+        pad, self.name
+      )?,
+      None if self.releases == 0 => writeln!(f, "{}  - but there is no release of {}", pad, self.name)?,
+      None if self.incompatible == self.releases => writeln!(
+        f,
+        "{}  - but none of the {} releases of {} loads on Factorio {}",
+        pad,
+        self.releases,
+        self.name,
+        self.game_version.map_or("?".to_string(), |v| v.to_string())
+      )?,
+      None if self.filtered == self.releases && self.incompatible > 0 => writeln!(
+        f,
+        "{}  - but none of the {} releases of {} matches, {} of them don't load on Factorio {}",
+        pad,
+        self.releases,
+        self.name,
+        self.incompatible,
+        self.game_version.map_or("?".to_string(), |v| v.to_string())
+      )?,
+      None if self.filtered == self.releases => writeln!(
+        f,
+        "{}  - but none of the {} releases of {} matches",
+        pad, self.releases, self.name
+      )?,
+      None => (),
+    }
+
+    for failure in self.failures.iter().take(Self::SHOWN_FAILURES) {
+      match &failure.reason {
+        FailureReason::Dependency {
+          constraint,
+          selected,
+        } => writeln!(
+          f,
+          "{}  - {} {} {}, but {} {} is selected",
+          pad,
+          self.name,
+          failure.version,
+          constraint.describe(),
+          constraint.target,
+          selected
+        )?,
+        FailureReason::Conflict(conflict) => {
+          writeln!(f, "{}  - {} {} was tried, but", pad, self.name, failure.version)?;
+          conflict.render(f, depth + 2)?;
+        }
+      }
+    }
+
+    if self.failures.len() > Self::SHOWN_FAILURES {
+      writeln!(
+        f,
+        "{}  - and {} other releases of {} fail the same way",
+        pad,
+        self.failures.len() - Self::SHOWN_FAILURES,
+        self.name
+      )?;
+    }
+    Ok(())
+  }
+}
+
+impl Display for Conflict {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    self.render(f, 0)
+  }
+}
+
+/// Errors that can happen while resolving dependencies.
+#[derive(Debug)]
+pub enum ResolveError {
+  /// No set of releases satisfies every constraint.
+  NoSolution(Box<Conflict>),
+  /// The search was cut short after trying too many releases, see [`Resolver::max_steps`].
+  ///
+  /// Unlike [`ResolveError::NoSolution`], a solution may still exist.
+  TooComplex(usize),
+}
+
+impl Display for ResolveError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ResolveError::NoSolution(conflict) => write!(f, "No solution found.\n{}", conflict),
+      ResolveError::TooComplex(steps) => write!(
+        f,
+        "The search was cut short after trying {} releases, before finding a solution or \
+         proving that there is none",
+        steps
+      ),
+    }
+  }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// The releases selected by the resolver.
+#[derive(Debug, Clone, Default)]
+pub struct Resolution {
+  /// The selected release of every mod, by name.
+  pub releases: BTreeMap<String, FModRelease>,
+}
+
+/// A mod being resolved, with the releases left to try.
+struct Frame {
+  name: String,
+  candidates: Vec<FModRelease>,
+  next: usize,
+  constraints: Vec<Constraint>,
+  constraints_len: usize,
+  releases: usize,
+  incompatible: usize,
+  failures: Vec<CandidateFailure>,
+  /// The mods whose selected releases caused the failures of this frame.
+  culprits: HashSet<String>,
+}
+
+impl Frame {
+  fn into_conflict(self, game_version: Option<GameVersion>) -> Conflict {
+    Conflict {
+      name: self.name,
+      releases: self.releases,
+      filtered: self.releases - self.candidates.len(),
+      constraints: self.constraints,
+      provided: None,
+      unavailable: false,
+      game_version,
+      incompatible: self.incompatible,
+      failures: self.failures,
+    }
+  }
+}
+
+/// Selects one release per mod satisfying all dependencies.
+pub struct Resolver<'a, S: ReleaseSource> {
+  source: &'a S,
+  provided: HashMap<String, Option<FactorioVersion>>,
+  unavailable: HashSet<String>,
+  preferred: HashMap<String, String>,
+  game_version: Option<GameVersion>,
+  max_steps: usize,
+}
+
+impl<'a, S: ReleaseSource> Resolver<'a, S> {
+  /// Creates a resolver listing releases from the given source.
+  pub fn new(source: &'a S) -> Self {
+    Self {
+      source,
+      provided: HashMap::new(),
+      unavailable: HashSet::new(),
+      preferred: HashMap::new(),
+      game_version: None,
+      max_steps: 100_000,
+    }
+  }
+
+  /// Declares a mod that comes with the game, like `base`.
+  ///
+  /// Provided mods are never downloaded. When the version is unknown, every version
+  /// requirement on the mod is considered satisfied.
+  pub fn provide(mut self, name: &str, version: Option<FactorioVersion>) -> Self {
+    self.provided.insert(name.to_string(), version);
+    self
+  }
+
+  /// Declares the mods that come with the game, see [`GameVersion::builtin_mods`].
+  ///
+  /// The other built-in mods, like the mods of an expansion that isn't owned, can't be
+  /// selected: requiring them is a conflict instead of a lookup on the portal.
+  ///
+  /// # Arguments
+  ///
+  /// * `version` - The version of the game, if known. When it is unknown, the expansion mods are provided if it is owned.
+  /// * `space_age` - True if the Space Age expansion is owned.
+  pub fn builtin(mut self, version: Option<FactorioVersion>, space_age: bool) -> Self {
+    let available = match &version {
+      Some(version) => GameVersion::from(version).builtin_mods(space_age),
+      None => GameVersion::V2_0.builtin_mods(space_age),
+    };
+    for name in BUILTIN_MODS {
+      if available.contains(&name) {
+        self = self.provide(name, version.clone());
+      } else {
+        self.unavailable.insert(name.to_string());
+      }
+    }
+    self
+  }
+
+  /// Tries the release with the given SHA1 first for a mod, for example to keep a locked release.
+  pub fn prefer(mut self, name: &str, sha1: &str) -> Self {
+    self.preferred.insert(name.to_string(), sha1.to_string());
+    self
+  }
+
+  /// Only selects releases that the given version of the game loads.
+  pub fn game_version(mut self, game_version: GameVersion) -> Self {
+    self.game_version = Some(game_version);
+    self
+  }
+
+  /// Sets how many releases may be tried before giving up.
+  pub fn max_steps(mut self, max_steps: usize) -> Self {
+    self.max_steps = max_steps;
+    self
+  }
+
+  /// Resolves the given requirements and all of their dependencies.
+  ///
+  /// # Arguments
+  ///
+  /// * `requirements` - The mods to install, with their version requirement.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<Resolution, Error>` - Returns the selected releases, or `Error::ResolveError` explaining the conflict.
+  #[instrument(skip_all)]
+  pub async fn resolve(&self, requirements: &[(String, FactorioVersionReq)]) -> Result<Resolution, Error> {
+    let mut constraints: Vec<Constraint> = requirements
+      .iter()
+      .map(|(name, req)| Constraint {
+        target: name.clone(),
+        kind: ConstraintKind::Requires(Some(req.clone())),
+        cause: Cause::Root,
+      })
+      .collect();
+
+    if let Some(conflict) = self.check_provided(&constraints) {
+      return Err(ResolveError::NoSolution(Box::new(conflict)).into());
+    }
+
+    let mut cache: HashMap<String, Vec<FModRelease>> = HashMap::new();
+    let mut selected: HashMap<String, FModRelease> = HashMap::new();
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut steps = 0;
+
+    loop {
+      let next = constraints
+        .iter()
+        .filter(|c| c.is_required())
+        .map(|c| &c.target)
+        .find(|t| !selected.contains_key(*t) && !self.provided.contains_key(*t))
+        .cloned();
+
+      let Some(name) = next else {
+        return Ok(Resolution {
+          releases: selected.into_iter().collect(),
+        });
+      };
+
+      if self.unavailable.contains(&name) {
+        debug!("'{}' doesn't come with the game", name);
+        cache.entry(name.clone()).or_default();
+      } else if !cache.contains_key(&name) {
+        debug!("Listing releases of '{}'", name);
+        let releases = self
+          .source
+          .releases(&name)
+          .await?;
+        cache.insert(name.clone(), releases);
+      }
+      frames.push(self.frame(&name, &cache[&name], &constraints));
+
+      // Selects a release for the new frame, backtracking until one fits.
+      loop {
+        steps += 1;
+        if steps > self.max_steps {
+          return Err(ResolveError::TooComplex(self.max_steps).into());
+        }
+
+        let Some(frame) = frames.last_mut() else {
+          unreachable!("a frame is pushed before selecting a release");
+        };
+
+        if let Some(release) = self.next_candidate(frame, &selected) {
+          trace!("Trying {} {}", frame.name, release.version);
+          let cause = Cause::Release {
+            name: frame.name.clone(),
+            version: release.version.to_string(),
+          };
+          constraints.extend(
+            release
+              .info_json
+              .dependencies
+              .iter()
+              .map(|dep| Constraint::from_dependency(dep, cause.clone())),
+          );
+          selected.insert(frame.name.clone(), release);
+          break;
+        }
+
+        // Every release of this mod failed. Only another release of a mod that caused the
+        // failures can change that, so the mods selected after the latest of them are skipped.
+        let Some(frame) = frames.pop() else {
+          unreachable!("the frame was checked above");
+        };
+        let mut culprits = frame.culprits.clone();
+        culprits.remove(&frame.name);
+        let mut conflict = frame.into_conflict(self.game_version);
+        conflict.unavailable = self.unavailable.contains(&conflict.name);
+
+        let Some(index) = frames.iter().rposition(|f| culprits.contains(&f.name)) else {
+          return Err(ResolveError::NoSolution(Box::new(conflict)).into());
+        };
+        debug!("No release of '{}' fits, backtracking to '{}'", conflict.name, frames[index].name);
+        for skipped in frames.drain(index + 1..) {
+          trace!("Skipping '{}', which is not part of the conflict", skipped.name);
+          selected.remove(&skipped.name);
+        }
+
+        let parent = &mut frames[index];
+        constraints.truncate(parent.constraints_len);
+        if let Some(release) = selected.remove(&parent.name) {
+          parent.failures.push(CandidateFailure {
+            version: release.version.to_string(),
+            reason: FailureReason::Conflict(Box::new(conflict)),
+          });
+        }
+        parent.culprits.extend(culprits);
+      }
+    }
+  }
+
+  /// Checks the constraints of the requirements on the mods provided by the game.
+  fn check_provided(&self, constraints: &[Constraint]) -> Option<Conflict> {
+    self.provided.iter().find_map(|(name, version)| {
+      let on_name: Vec<Constraint> = constraints
+        .iter()
+        .filter(|c| &c.target == name)
+        .cloned()
+        .collect();
+      let selection = Selected::Provided(version.as_ref());
+
+      on_name.iter().any(|c| !c.allows(&selection)).then(|| Conflict {
+        name: name.clone(),
+        constraints: on_name,
+        releases: 0,
+        filtered: 0,
+        provided: Some(version.clone()),
+        unavailable: false,
+        game_version: self.game_version,
+        incompatible: 0,
+        failures: Vec::new(),
+      })
+    })
+  }
+
+  /// Creates a frame with the releases of a mod allowed by the current constraints.
+  fn frame(&self, name: &str, releases: &[FModRelease], constraints: &[Constraint]) -> Frame {
+    let on_name: Vec<Constraint> = constraints
+      .iter()
+      .filter(|c| c.target == name)
+      .cloned()
+      .collect();
+
+    let loadable: Vec<&FModRelease> = releases
+      .iter()
+      .filter(|r| self.game_version.is_none_or(|v| r.loads_on(v)))
+      .collect();
+
+    let mut candidates: Vec<FModRelease> = loadable
+      .iter()
+      .copied()
+      .filter(|r| on_name.iter().all(|c| c.allows(&Selected::Release(r))))
+      .cloned()
+      .collect();
+    candidates.sort_by(|a, b| b.cmp(a));
+
+    if let Some(sha1) = self.preferred.get(name) {
+      if let Some(index) = candidates.iter().position(|r| &r.sha1 == sha1) {
+        let preferred = candidates.remove(index);
+        candidates.insert(0, preferred);
+      }
+    }
+
+    let culprits = on_name
+      .iter()
+      .filter_map(|c| match &c.cause {
+        Cause::Release { name, .. } => Some(name.clone()),
+        Cause::Root => None,
+      })
+      .collect();
+
+    Frame {
+      name: name.to_string(),
+      candidates,
+      next: 0,
+      culprits,
+      constraints: on_name,
+      constraints_len: constraints.len(),
+      releases: releases.len(),
+      incompatible: releases.len() - loadable.len(),
+      failures: Vec::new(),
+    }
+  }
+
+  /// Returns the next release of the frame whose dependencies agree with the selected mods.
+  fn next_candidate(
+    &self,
+    frame: &mut Frame,
+    selected: &HashMap<String, FModRelease>,
+  ) -> Option<FModRelease> {
+    while let Some(release) = frame.candidates.get(frame.next) {
+      frame.next += 1;
+      let cause = Cause::Release {
+        name: frame.name.clone(),
+        version: release.version.to_string(),
+      };
+
+      let rejected = release.info_json.dependencies.iter().find_map(|dep| {
+        let selection = match (selected.get(dep.name()), self.provided.get(dep.name())) {
+          (Some(release), _) => Selected::Release(release),
+          (None, Some(version)) => Selected::Provided(version.as_ref()),
+          (None, None) => return None,
+        };
+        let constraint = Constraint::from_dependency(dep, cause.clone());
+
+        (!constraint.allows(&selection)).then(|| FailureReason::Dependency {
+          selected: selection.to_string(),
+          constraint,
+        })
+      });
+
+      match rejected {
+        Some(reason) => {
+          if let FailureReason::Dependency { constraint, .. } = &reason {
+            if selected.contains_key(&constraint.target) {
+              frame.culprits.insert(constraint.target.clone());
+            }
+          }
+          frame.failures.push(CandidateFailure {
+            version: release.version.to_string(),
+            reason,
+          });
+        }
+        None => return Some(release.clone()),
+      }
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::model::fmod::InfoJSON;
+  use std::str::FromStr;
+
+  fn release(name: &str, version: &str, dependencies: &[&str]) -> FModRelease {
+    FModRelease {
+      download_url: format!("/download/{}/{}", name, version),
+      file_name: format!("{}_{}.zip", name, version),
+      version: version.parse().unwrap(),
+      sha1: format!("{}-{}", name, version),
+      info_json: InfoJSON {
+        dependencies: dependencies
+          .iter()
+          .map(|d| FModDependecies::from_str(d).unwrap())
+          .collect(),
+        ..Default::default()
+      },
+      ..Default::default()
+    }
+  }
+
+  fn portal(releases: Vec<FModRelease>) -> HashMap<String, Vec<FModRelease>> {
+    let mut map: HashMap<String, Vec<FModRelease>> = HashMap::new();
+    for r in releases {
+      let name = r.file_name.rsplit_once('_').unwrap().0.to_string();
+      map.entry(name).or_default().push(r);
+    }
+    map
+  }
+
+  fn req(name: &str, req: &str) -> (String, FactorioVersionReq) {
+    (name.to_string(), req.parse().unwrap())
+  }
+
+  fn version(resolution: &Resolution, name: &str) -> String {
+    resolution.releases[name].version.to_string()
+  }
+
+  #[tokio::test]
+  async fn test_resolve_transitive() {
+    let source = portal(vec![
+      release("a", "1.0.0", &["base >= 1.1", "b >= 2.0.0"]),
+      release("b", "1.0.0", &[]),
+      release("b", "2.1.0", &["~ c"]),
+      release("c", "0.3.0", &[]),
+    ]);
+
+    let resolution = Resolver::new(&source)
+      .provide("base", Some(FactorioVersion::new(1, 1, 110)))
+      .resolve(&[req("a", "*")])
+      .await
+      .unwrap();
+
+    assert_eq!(resolution.releases.len(), 3);
+    assert_eq!(version(&resolution, "b"), "2.1.0");
+    assert_eq!(version(&resolution, "c"), "0.3.0");
+    assert!(!resolution.releases.contains_key("base"));
+  }
+
+  #[tokio::test]
+  async fn test_resolve_backtracks() {
+    let source = portal(vec![
+      release("a", "2.0.0", &["b < 1.0.0"]),
+      release("a", "1.0.0", &["b >= 1.0.0"]),
+      release("b", "0.9.0", &["! c"]),
+      release("b", "1.2.0", &[]),
+      release("c", "1.0.0", &[]),
+    ]);
+
+    let resolution = Resolver::new(&source)
+      .resolve(&[req("c", "*"), req("a", "*")])
+      .await
+      .unwrap();
+
+    assert_eq!(version(&resolution, "a"), "1.0.0");
+    assert_eq!(version(&resolution, "b"), "1.2.0");
+  }
+
+  #[tokio::test]
+  async fn test_resolve_optional() {
+    let source = portal(vec![
+      release("a", "1.0.0", &["? b >= 2.0.0", "(?) c"]),
+      release("b", "2.0.0", &[]),
+      release("b", "1.0.0", &[]),
+    ]);
+
+    // Optional dependencies are not installed on their own.
+    let resolution = Resolver::new(&source).resolve(&[req("a", "*")]).await.unwrap();
+    assert_eq!(resolution.releases.len(), 1);
+
+    // But they constrain the version when something else requires them.
+    let err = Resolver::new(&source)
+      .resolve(&[req("a", "*"), req("b", "<2.0.0")])
+      .await
+      .unwrap_err();
+    assert!(matches!(err, Error::ResolveError(ResolveError::NoSolution(_))));
+  }
+
+  #[tokio::test]
+  async fn test_resolve_prefer() {
+    let source = portal(vec![release("a", "1.0.0", &[]), release("a", "1.1.0", &[])]);
+
+    let resolution = Resolver::new(&source)
+      .prefer("a", "a-1.0.0")
+      .resolve(&[req("a", "*")])
+      .await
+      .unwrap();
+
+    assert_eq!(version(&resolution, "a"), "1.0.0");
+  }
+
+  #[tokio::test]
+  async fn test_resolve_conflict_explanation() {
+    let source = portal(vec![
+      release("a", "1.0.0", &["b >= 2.0.0"]),
+      release("b", "1.0.0", &[]),
+      release("b", "2.0.0", &["! c"]),
+      release("c", "1.0.0", &[]),
+    ]);
+
+    let err = Resolver::new(&source)
+      .resolve(&[req("c", "*"), req("a", "*")])
+      .await
+      .unwrap_err();
+
+    let Error::ResolveError(ResolveError::NoSolution(conflict)) = &err else {
+      panic!("{}", err);
+    };
+    // The configuration asks for c first, so it is the last choice the resolver revisits.
+    assert_eq!(conflict.name, "c");
+    let text = err.to_string();
+    assert!(text.contains("a 1.0.0 was tried, but"), "{}", text);
+    assert!(text.contains("b 2.0.0 is incompatible with c, but c 1.0.0 is selected"), "{}", text);
+    assert!(text.contains("a 1.0.0 requires b >= 2.0.0"), "{}", text);
+  }
+
+  #[tokio::test]
+  async fn test_resolve_provided_conflict() {
+    let source = portal(vec![release("a", "1.0.0", &["base >= 2.0"])]);
+
+    let err = Resolver::new(&source)
+      .provide("base", Some(FactorioVersion::new(1, 1, 110)))
+      .resolve(&[req("a", "*")])
+      .await
+      .unwrap_err();
+
+    let text = err.to_string();
+    assert!(text.contains("base >= 2.0.0, but base 1.1.110 (provided by the game) is selected"), "{}", text);
+  }
+
+  #[tokio::test]
+  async fn test_resolve_builtin() {
+    let source = portal(vec![
+      release("a", "1.1.0", &["base >= 1.1.80"]),
+      release("b", "2.0.0", &["base >= 2.0", "space-age", "? quality"]),
+      release("b", "1.0.0", &["base >= 2.0"]),
+    ]);
+    let game = || Some(FactorioVersion::new(2, 0, 28));
+
+    let resolution = Resolver::new(&source)
+      .builtin(game(), true)
+      .resolve(&[req("a", "*"), req("b", "*")])
+      .await
+      .unwrap();
+    assert_eq!(version(&resolution, "b"), "2.0.0");
+    assert_eq!(resolution.releases.len(), 2);
+
+    // Without the expansion, the release requiring it is skipped.
+    let resolution = Resolver::new(&source)
+      .builtin(game(), false)
+      .resolve(&[req("b", "*")])
+      .await
+      .unwrap();
+    assert_eq!(version(&resolution, "b"), "1.0.0");
+
+    let err = Resolver::new(&source)
+      .builtin(game(), false)
+      .resolve(&[req("b", ">= 2.0")])
+      .await
+      .unwrap_err();
+    let text = err.to_string();
+    assert!(text.contains("but space-age only comes with Factorio 2.0 and the Space Age expansion"), "{}", text);
+
+    let err = Resolver::new(&source)
+      .builtin(Some(FactorioVersion::new(1, 1, 70)), false)
+      .resolve(&[req("a", "*")])
+      .await
+      .unwrap_err();
+    let text = err.to_string();
+    assert!(text.contains("requires base >= 1.1.80, but base 1.1.70 (provided by the game) is selected"), "{}", text);
+  }
+
+  #[tokio::test]
+  async fn test_resolve_game_version() {
+    let mut old = release("a", "1.1.0", &[]);
+    old.info_json.factorio_version = Some(GameVersion::V1_0);
+    let mut new = release("a", "2.0.0", &[]);
+    new.info_json.factorio_version = Some(GameVersion::V2_0);
+    let source = portal(vec![old, new]);
+
+    let resolution = Resolver::new(&source)
+      .game_version(GameVersion::V1_1)
+      .resolve(&[req("a", "*")])
+      .await
+      .unwrap();
+    assert_eq!(version(&resolution, "a"), "1.1.0");
+
+    let err = Resolver::new(&source)
+      .game_version(GameVersion::V2_0)
+      .resolve(&[req("a", "<2.0.0")])
+      .await
+      .unwrap_err();
+    let text = err.to_string();
+    assert!(text.contains("none of the 2 releases of a matches, 1 of them don't load on Factorio 2.0"), "{}", text);
+
+    let err = Resolver::new(&source)
+      .game_version(GameVersion::V0_18)
+      .resolve(&[req("a", "*")])
+      .await
+      .unwrap_err();
+    let text = err.to_string();
+    assert!(text.contains("none of the 2 releases of a loads on Factorio 0.18"), "{}", text);
+  }
+
+  #[tokio::test]
+  async fn test_resolve_missing_mod() {
+    let source = portal(vec![release("a", "1.0.0", &["b"])]);
+
+    let err = Resolver::new(&source).resolve(&[req("a", "*")]).await.unwrap_err();
+
+    assert!(err.to_string().contains("there is no release of b"), "{}", err);
+  }
+
+  #[tokio::test]
+  async fn test_resolve_too_complex() {
+    let source = portal(vec![
+      release("a", "1.0.0", &["b"]),
+      release("a", "2.0.0", &["b"]),
+      release("b", "1.0.0", &["! a"]),
+    ]);
+
+    let err = Resolver::new(&source)
+      .max_steps(2)
+      .resolve(&[req("a", "*")])
+      .await
+      .unwrap_err();
+
+    assert!(matches!(err, Error::ResolveError(ResolveError::TooComplex(2))));
+    assert!(err.to_string().starts_with("The search was cut short after trying 2 releases"), "{}", err);
+  }
+
+  #[tokio::test]
+  async fn test_resolve_skips_unrelated_mods() {
+    // a and b have many releases, which have nothing to do with c needing a missing d.
+    let mut releases = vec![release("d", "1.0.0", &[])];
+    for minor in 0..50 {
+      let version = format!("1.{}.0", minor);
+      releases.push(release("a", &version, &[]));
+      releases.push(release("b", &version, &[]));
+      releases.push(release("c", &version, &["d >= 2.0"]));
+    }
+    let source = portal(releases);
+
+    let err = Resolver::new(&source)
+      .max_steps(1_000)
+      .resolve(&[req("a", "*"), req("b", "*"), req("c", "*")])
+      .await
+      .unwrap_err();
+
+    let Error::ResolveError(ResolveError::NoSolution(conflict)) = &err else {
+      panic!("{}", err);
+    };
+    assert_eq!(conflict.name, "c");
+    assert_eq!(
+      err.to_string(),
+      "No solution found.
+c cannot be resolved:
+  - the configuration requires c *
+  - c 1.49.0 was tried, but
+    d cannot be resolved:
+      - c 1.49.0 requires d >= 2.0.0
+      - but none of the 1 releases of d matches
+  - c 1.48.0 was tried, but
+    d cannot be resolved:
+      - c 1.48.0 requires d >= 2.0.0
+      - but none of the 1 releases of d matches
+  - c 1.47.0 was tried, but
+    d cannot be resolved:
+      - c 1.47.0 requires d >= 2.0.0
+      - but none of the 1 releases of d matches
+  - c 1.46.0 was tried, but
+    d cannot be resolved:
+      - c 1.46.0 requires d >= 2.0.0
+      - but none of the 1 releases of d matches
+  - c 1.45.0 was tried, but
+    d cannot be resolved:
+      - c 1.45.0 requires d >= 2.0.0
+      - but none of the 1 releases of d matches
+  - and 45 other releases of c fail the same way
+"
+    );
+  }
+}
@@ -0,0 +1,286 @@
+//! Retries of failed portal requests, and limits on how fast requests are sent.
+//!
+//! Every request of a [`Context`] goes through its [`RetryPolicy`]: connection errors,
+//! timeouts, `429 Too Many Requests` and server errors are retried after an exponential
+//! backoff, or after the delay asked by a `Retry-After` header, up to the longest backoff.
+//!
+//! [`Context`]: crate::model::context::Context
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::{header::RETRY_AFTER, RequestBuilder, Response, StatusCode};
+use std::{sync::Mutex, time::Duration};
+use tokio::time::Instant;
+use tracing::warn;
+
+/// When and how often failed requests are sent again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+  /// How many times a request is sent again after a failure, 0 disables retries.
+  pub max_retries: u32,
+  /// The delay before the first retry, doubled on each of the following ones.
+  pub initial_backoff: Duration,
+  /// The longest delay between two attempts, even when the server asks for more.
+  pub max_backoff: Duration,
+  /// Waits a random delay between half and all of the backoff, so that clients do not retry
+  /// all at once.
+  pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    Self {
+      max_retries: 3,
+      initial_backoff: Duration::from_millis(500),
+      max_backoff: Duration::from_secs(30),
+      jitter: true,
+    }
+  }
+}
+
+impl RetryPolicy {
+  /// A policy that never retries.
+  pub fn none() -> Self {
+    Self {
+      max_retries: 0,
+      ..Default::default()
+    }
+  }
+
+  /// Returns the delay before a retry.
+  ///
+  /// # Arguments
+  ///
+  /// * `retry` - The number of the retry, starting at 0.
+  pub fn backoff(&self, retry: u32) -> Duration {
+    let backoff = self
+      .initial_backoff
+      .saturating_mul(2u32.saturating_pow(retry))
+      .min(self.max_backoff);
+
+    if self.jitter && !backoff.is_zero() {
+      rand::thread_rng().gen_range(backoff / 2..=backoff)
+    } else {
+      backoff
+    }
+  }
+
+  /// Sends a request, and sends it again while it fails in a way worth retrying.
+  ///
+  /// The response of the last attempt is returned whatever its status, so callers turn error
+  /// statuses into `Error::HttpError` with [`error_for_status`](crate::error::error_for_status).
+  ///
+  /// # Arguments
+  ///
+  /// * `request` - The request, which must not have a streamed body.
+  /// * `limiter` - Waits for a free slot before each attempt.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<Response, reqwest::Error>` - Returns the response of the last attempt, or its error if it could not be sent.
+  pub(crate) async fn send(
+    &self,
+    request: RequestBuilder,
+    limiter: Option<&RateLimiter>,
+  ) -> Result<Response, reqwest::Error> {
+    let mut retry = 0;
+    loop {
+      if let Some(limiter) = limiter {
+        limiter.consume(1).await;
+      }
+      // A request with a streamed body cannot be sent twice.
+      let Some(attempt) = request.try_clone() else {
+        return request.send().await;
+      };
+      let last = retry >= self.max_retries;
+
+      let delay = match attempt.send().await {
+        Ok(response) if is_retryable_status(response.status()) => {
+          if last {
+            return Ok(response);
+          }
+          let delay = match retry_after(&response) {
+            Some(delay) => delay.min(self.max_backoff),
+            None => self.backoff(retry),
+          };
+          // Download URLs carry the token in their query, only the path is logged.
+          warn!(
+            path = response.url().path(),
+            status = %response.status(),
+            retry = retry + 1,
+            delay_ms = delay.as_millis() as u64,
+            "Retrying request"
+          );
+          delay
+        }
+        Ok(response) => return Ok(response),
+        Err(e) if !last && is_retryable_error(&e) => {
+          let delay = self.backoff(retry);
+          warn!(
+            error = %e.without_url(),
+            retry = retry + 1,
+            delay_ms = delay.as_millis() as u64,
+            "Retrying request"
+          );
+          delay
+        }
+        Err(e) => return Err(e),
+      };
+
+      tokio::time::sleep(delay).await;
+      retry += 1;
+    }
+  }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+  status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn is_retryable_error(error: &reqwest::Error) -> bool {
+  error.is_connect() || error.is_timeout() || error.is_request()
+}
+
+/// Reads the delay of a `Retry-After` header, given in seconds or as a date.
+fn retry_after(response: &Response) -> Option<Duration> {
+  let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+  if let Ok(seconds) = value.parse::<u64>() {
+    return Some(Duration::from_secs(seconds));
+  }
+
+  let date = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+  Some((date - Utc::now()).to_std().unwrap_or_default())
+}
+
+/// Spreads units, like bytes or requests, over time so that they do not exceed a rate.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+  per_second: f64,
+  next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+  pub(crate) fn new(per_second: f64) -> Self {
+    Self {
+      per_second,
+      next: Mutex::new(Instant::now()),
+    }
+  }
+
+  /// Waits until the given number of units fits in the rate.
+  pub(crate) async fn consume(&self, units: u64) {
+    let until = {
+      let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+      let start = (*next).max(Instant::now());
+      *next = start + Duration::from_secs_f64(units as f64 / self.per_second);
+      *next
+    };
+    tokio::time::sleep_until(until).await;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{self, Response, Server};
+
+  #[test]
+  fn test_backoff() {
+    let policy = RetryPolicy {
+      jitter: false,
+      ..Default::default()
+    };
+    assert_eq!(policy.backoff(0), Duration::from_millis(500));
+    assert_eq!(policy.backoff(2), Duration::from_secs(2));
+    assert_eq!(policy.backoff(20), Duration::from_secs(30));
+
+    let policy = RetryPolicy::default();
+    for retry in 0..5 {
+      let backoff = policy.backoff(retry);
+      let max = Duration::from_millis(500) * 2u32.pow(retry);
+      assert!(backoff >= max / 2 && backoff <= max, "{:?}", backoff);
+    }
+  }
+
+  /// Answers each request with the next status, then with 200 OK.
+  async fn serve(statuses: &'static [&'static str]) -> Server {
+    test_util::serve(|index, _| match statuses.get(index) {
+      Some(status) => Response::new(status),
+      None => Response::new("200 OK").body("ok"),
+    })
+    .await
+  }
+
+  fn policy(max_retries: u32) -> RetryPolicy {
+    RetryPolicy {
+      max_retries,
+      initial_backoff: Duration::from_millis(1),
+      max_backoff: Duration::from_millis(10),
+      jitter: true,
+    }
+  }
+
+  #[tokio::test]
+  async fn test_send() {
+    let server = test_util::serve(|index, _| match index {
+      0 => Response::new("503 Service Unavailable"),
+      1 => Response::new("429 Too Many Requests").header("Retry-After", "0"),
+      2 => Response::new("502 Bad Gateway"),
+      _ => Response::new("200 OK"),
+    })
+    .await;
+    let client = reqwest::Client::new();
+
+    let response = policy(3).send(client.get(server.url.clone()), None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(server.requests().len(), 4);
+  }
+
+  #[tokio::test]
+  async fn test_send_gives_up() {
+    let server = serve(&["500 Internal Server Error", "500 Internal Server Error"]).await;
+    let client = reqwest::Client::new();
+
+    let response = policy(1).send(client.get(server.url.clone()), None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(server.requests().len(), 2);
+
+    // Client errors are not retried.
+    let server = serve(&["404 Not Found"]).await;
+    let response = policy(3).send(client.get(server.url.clone()), None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(server.requests().len(), 1);
+  }
+
+  #[tokio::test]
+  async fn test_send_caps_retry_after() {
+    let server = test_util::serve(|index, _| match index {
+      0 => Response::new("429 Too Many Requests").header("Retry-After", "86400"),
+      1 => Response::new("503 Service Unavailable")
+        .header("Retry-After", "Fri, 31 Dec 2100 23:59:59 GMT"),
+      _ => Response::new("200 OK"),
+    })
+    .await;
+    let client = reqwest::Client::new();
+
+    let policy = policy(2);
+    let send = policy.send(client.get(server.url.clone()), None);
+    let response = tokio::time::timeout(Duration::from_secs(5), send).await.unwrap().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(server.requests().len(), 3);
+  }
+
+  #[tokio::test]
+  async fn test_rate_limiter() {
+    let url = serve(&[]).await.url;
+    let client = reqwest::Client::new();
+    let limiter = RateLimiter::new(20.0);
+
+    let start = std::time::Instant::now();
+    for _ in 0..4 {
+      policy(0).send(client.get(url.clone()), Some(&limiter)).await.unwrap();
+    }
+    // Each request takes 50ms of the rate.
+    assert!(start.elapsed() >= Duration::from_millis(190), "{:?}", start.elapsed());
+  }
+}
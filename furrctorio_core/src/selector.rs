@@ -0,0 +1,272 @@
+//! Picks the release of a single mod to install.
+//!
+//! A [`ReleaseSelector`] keeps the newest release that matches a version requirement, loads on
+//! the version of the game and passes the [`ReleasePolicy`]. When none does, the returned
+//! [`NoCompatibleRelease`] lists every release with the reason it was rejected.
+
+use crate::{
+  error::Error,
+  model::{
+    fmod::FModRelease,
+    version::{FactorioVersion, FactorioVersionReq, GameVersion},
+  },
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::fmt::Display;
+use tracing::debug;
+
+/// Optional rules on top of the version requirement.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReleasePolicy {
+  /// Skips the releases with a `0.y.z` version.
+  ///
+  /// Factorio versions have no pre-release marker and many stable mods stay on 0.x, so this is
+  /// only a rule for authors who use 0.x for unstable releases, it is off by default.
+  pub exclude_zero_major: bool,
+  /// Skips releases published less than this long ago, to let broken releases be replaced.
+  pub cooldown: Option<TimeDelta>,
+}
+
+/// Why a release was not selected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+  /// Its version doesn't match the requirement.
+  Requirement,
+  /// It is made for a version of the game that doesn't load it.
+  GameVersion(GameVersion),
+  /// Its major version is 0 and those are excluded.
+  ZeroMajor,
+  /// It was published too recently, it can be selected from the given date.
+  Cooldown(DateTime<Utc>),
+}
+
+impl Display for Rejection {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Rejection::Requirement => write!(f, "does not match the requirement"),
+      Rejection::GameVersion(version) => write!(f, "is made for Factorio {}", version),
+      Rejection::ZeroMajor => write!(f, "has a 0.y.z version"),
+      Rejection::Cooldown(until) => write!(f, "is too recent, it can be used from {}", until),
+    }
+  }
+}
+
+/// No release of a mod can be selected.
+#[derive(Debug, Clone)]
+pub struct NoCompatibleRelease {
+  /// The name of the mod.
+  pub name: String,
+  /// The version requirement on the mod.
+  pub requirement: FactorioVersionReq,
+  /// The version of the game the release must load on, if known.
+  pub game_version: Option<GameVersion>,
+  /// Every release of the mod, from the newest, with the reason it was rejected.
+  pub rejected: Vec<(FactorioVersion, Rejection)>,
+}
+
+impl NoCompatibleRelease {
+  /// The number of rejected releases shown before the rest are summarized.
+  const SHOWN_REJECTIONS: usize = 10;
+}
+
+impl Display for NoCompatibleRelease {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "No release of {} matches {}", self.name, self.requirement)?;
+    if let Some(game_version) = self.game_version {
+      write!(f, " on Factorio {}", game_version)?;
+    }
+    if self.rejected.is_empty() {
+      return write!(f, ", it has no release");
+    }
+
+    write!(f, ":")?;
+    for (version, rejection) in self.rejected.iter().take(Self::SHOWN_REJECTIONS) {
+      write!(f, "\n  - {} {}", version, rejection)?;
+    }
+    if self.rejected.len() > Self::SHOWN_REJECTIONS {
+      write!(
+        f,
+        "\n  - and {} older releases",
+        self.rejected.len() - Self::SHOWN_REJECTIONS
+      )?;
+    }
+    Ok(())
+  }
+}
+
+impl std::error::Error for NoCompatibleRelease {}
+
+/// Selects the newest acceptable release of a mod.
+#[derive(Debug, Clone)]
+pub struct ReleaseSelector {
+  requirement: FactorioVersionReq,
+  game_version: Option<GameVersion>,
+  policy: ReleasePolicy,
+}
+
+impl ReleaseSelector {
+  /// Creates a selector accepting the releases matching the requirement.
+  pub fn new(requirement: FactorioVersionReq) -> Self {
+    Self {
+      requirement,
+      game_version: None,
+      policy: ReleasePolicy::default(),
+    }
+  }
+
+  /// Only accepts releases that the given version of the game loads.
+  pub fn game_version(mut self, game_version: GameVersion) -> Self {
+    self.game_version = Some(game_version);
+    self
+  }
+
+  pub fn policy(mut self, policy: ReleasePolicy) -> Self {
+    self.policy = policy;
+    self
+  }
+
+  /// Checks a single release.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<(), Rejection>` - Returns Ok if the release can be selected, or the first reason it can't.
+  pub fn check(&self, release: &FModRelease) -> Result<(), Rejection> {
+    if !release.match_version(&self.requirement) {
+      return Err(Rejection::Requirement);
+    }
+    if let Some(game_version) = self.game_version {
+      if !release.loads_on(game_version) {
+        return Err(Rejection::GameVersion(release.factorio_version()));
+      }
+    }
+    if self.policy.exclude_zero_major && release.version.major() == 0 {
+      return Err(Rejection::ZeroMajor);
+    }
+    if let Some(cooldown) = self.policy.cooldown {
+      let until = release.released_at + cooldown;
+      if until > Utc::now() {
+        return Err(Rejection::Cooldown(until));
+      }
+    }
+    Ok(())
+  }
+
+  /// Returns the newest release that passes [`ReleaseSelector::check`].
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The name of the mod, for the error.
+  /// * `releases` - The releases of the mod, in any order.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<FModRelease, Error>` - Returns the selected release, or `Error::NoCompatibleRelease` listing why each release was rejected.
+  pub fn select(&self, name: &str, releases: &[FModRelease]) -> Result<FModRelease, Error> {
+    let mut sorted: Vec<&FModRelease> = releases.iter().collect();
+    sorted.sort_by(|a, b| b.cmp(a));
+
+    let mut rejected = Vec::new();
+    for release in sorted {
+      match self.check(release) {
+        Ok(()) => return Ok(release.clone()),
+        Err(rejection) => {
+          debug!("Skipping {} {}: {}", name, release.version, rejection);
+          rejected.push((release.version.clone(), rejection));
+        }
+      }
+    }
+
+    Err(Error::NoCompatibleRelease(Box::new(NoCompatibleRelease {
+      name: name.to_string(),
+      requirement: self.requirement.clone(),
+      game_version: self.game_version,
+      rejected,
+    })))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn release(version: &str, factorio_version: GameVersion, age_days: i64) -> FModRelease {
+    let mut release = FModRelease {
+      version: version.parse().unwrap(),
+      released_at: Utc::now() - TimeDelta::days(age_days),
+      ..Default::default()
+    };
+    release.info_json.factorio_version = Some(factorio_version);
+    release
+  }
+
+  fn releases() -> Vec<FModRelease> {
+    vec![
+      release("0.9.0", GameVersion::V1_0, 900),
+      release("1.1.0", GameVersion::V1_1, 300),
+      release("2.0.1", GameVersion::V2_0, 1),
+      release("2.0.0", GameVersion::V2_0, 30),
+    ]
+  }
+
+  fn selected(selector: ReleaseSelector) -> String {
+    selector.select("a", &releases()).unwrap().version.to_string()
+  }
+
+  #[test]
+  fn test_select() {
+    let any = || ReleaseSelector::new(FactorioVersionReq::STAR);
+    assert_eq!(selected(any()), "2.0.1");
+    assert_eq!(selected(any().game_version(GameVersion::V1_1)), "1.1.0");
+    assert_eq!(selected(any().game_version(GameVersion::V1_0)), "0.9.0");
+    assert_eq!(
+      selected(ReleaseSelector::new("<1.1".parse().unwrap())),
+      "0.9.0"
+    );
+
+    let cooldown = ReleasePolicy {
+      cooldown: Some(TimeDelta::days(7)),
+      ..Default::default()
+    };
+    assert_eq!(selected(any().policy(cooldown)), "2.0.0");
+
+    let unversioned = FModRelease::default();
+    assert_eq!(
+      any().game_version(GameVersion::V2_0).check(&unversioned),
+      Err(Rejection::GameVersion(GameVersion::V0_12))
+    );
+  }
+
+  #[test]
+  fn test_no_compatible_release() {
+    let policy = ReleasePolicy {
+      exclude_zero_major: true,
+      cooldown: None,
+    };
+    let err = ReleaseSelector::new("<2.0".parse().unwrap())
+      .game_version(GameVersion::V1_0)
+      .policy(policy)
+      .select("a", &releases())
+      .unwrap_err();
+
+    let Error::NoCompatibleRelease(no_release) = &err else {
+      panic!("{}", err);
+    };
+    let reasons: Vec<&Rejection> = no_release.rejected.iter().map(|(_, r)| r).collect();
+    assert_eq!(
+      reasons,
+      [
+        &Rejection::Requirement,
+        &Rejection::Requirement,
+        &Rejection::GameVersion(GameVersion::V1_1),
+        &Rejection::ZeroMajor
+      ]
+    );
+    assert_eq!(
+      err.to_string(),
+      "No release of a matches <2.0 on Factorio 1.0:\n  - 2.0.1 does not match the requirement\n  - 2.0.0 does not match the requirement\n  - 1.1.0 is made for Factorio 1.1\n  - 0.9.0 has a 0.y.z version"
+    );
+
+    let err = ReleaseSelector::new(FactorioVersionReq::STAR).select("b", &[]).unwrap_err();
+    assert_eq!(err.to_string(), "No release of b matches *, it has no release");
+  }
+}
@@ -0,0 +1,144 @@
+//! A local HTTP server standing in for the mod portal in tests.
+
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use url::Url;
+
+/// A request received by [`serve`].
+#[derive(Debug, Clone)]
+pub(crate) struct Request {
+  /// The URL of the request on the server, with its query.
+  pub url: Url,
+  /// The headers, with lowercase names.
+  pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+  /// Returns the value of a header, by its lowercase name.
+  pub fn header(&self, name: &str) -> Option<&str> {
+    self
+      .headers
+      .iter()
+      .find(|(key, _)| key == name)
+      .map(|(_, value)| value.as_str())
+  }
+
+  /// Returns the value of a query parameter.
+  pub fn query(&self, name: &str) -> Option<String> {
+    self
+      .url
+      .query_pairs()
+      .find(|(key, _)| key == name)
+      .map(|(_, value)| value.into_owned())
+  }
+}
+
+/// An answer of [`serve`].
+#[derive(Debug, Clone)]
+pub(crate) struct Response {
+  status: String,
+  headers: Vec<(String, String)>,
+  body: Vec<u8>,
+}
+
+impl Response {
+  /// An answer without body, like `Response::new("404 Not Found")`.
+  pub fn new(status: &str) -> Self {
+    Self {
+      status: status.to_string(),
+      headers: Vec::new(),
+      body: Vec::new(),
+    }
+  }
+
+  /// A `200 OK` answer with a JSON body.
+  pub fn json(value: &impl Serialize) -> Self {
+    Self::new("200 OK").json_body(serde_json::to_string(value).unwrap())
+  }
+
+  pub fn header(mut self, name: &str, value: &str) -> Self {
+    self.headers.push((name.to_string(), value.to_string()));
+    self
+  }
+
+  pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+    self.body = body.into();
+    self
+  }
+
+  /// Sets a body that is already JSON.
+  pub fn json_body(self, body: impl Into<Vec<u8>>) -> Self {
+    self.header("Content-Type", "application/json").body(body)
+  }
+
+  fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = format!("HTTP/1.1 {}\r\n", self.status);
+    for (name, value) in &self.headers {
+      bytes.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    bytes.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", self.body.len()));
+    let mut bytes = bytes.into_bytes();
+    bytes.extend_from_slice(&self.body);
+    bytes
+  }
+}
+
+/// A server started by [`serve`].
+pub(crate) struct Server {
+  /// The root URL of the server.
+  pub url: Url,
+  requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl Server {
+  /// Returns the requests received so far, in order.
+  pub fn requests(&self) -> Vec<Request> {
+    self.requests.lock().unwrap().clone()
+  }
+}
+
+/// Serves on a local port until the end of the test.
+///
+/// # Arguments
+///
+/// * `answer` - Answers a request, given its number, starting at 0, and the request.
+pub(crate) async fn serve(
+  answer: impl Fn(usize, &Request) -> Response + Send + 'static,
+) -> Server {
+  let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+  let requests = Arc::new(Mutex::new(Vec::new()));
+
+  let (root, received) = (url.clone(), requests.clone());
+  tokio::spawn(async move {
+    loop {
+      let (mut socket, _) = listener.accept().await.unwrap();
+      let mut buffer = vec![0; 4096];
+      let len = socket.read(&mut buffer).await.unwrap();
+      let text = String::from_utf8_lossy(&buffer[..len]).to_string();
+
+      let mut lines = text.lines();
+      let target = lines.next().unwrap_or_default().split(' ').nth(1).unwrap_or("/");
+      let headers = lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+      let request = Request {
+        url: root.join(target).unwrap(),
+        headers,
+      };
+
+      let index = {
+        let mut received = received.lock().unwrap();
+        received.push(request.clone());
+        received.len() - 1
+      };
+      let response = answer(index, &request);
+      socket.write_all(&response.to_bytes()).await.unwrap();
+    }
+  });
+
+  Server { url, requests }
+}
@@ -0,0 +1,9 @@
+use furrctorio_core::error::Error;
+
+/// The errors of this crate, which are the ones of `furrctorio_core`.
+pub type ConfigError = Error;
+
+/// Turns an invalid configuration or lockfile into `Error::ParcingError`.
+pub(crate) fn yaml_error(e: serde_yaml::Error) -> Error {
+  Error::ParcingError(format!("Invalid YAML: {}", e))
+}
@@ -1,2 +1,5 @@
+pub mod error;
 pub mod model;
+pub mod plan;
+pub mod sync;
 pub mod prelude;
\ No newline at end of file
@@ -1,13 +1,28 @@
 use semver::Version;
 use serde::{Deserialize, Serialize};
-use std::{fs::create_dir_all, path::PathBuf};
-use crate::model::mod_entry::ConfigModEntry;
+use std::{
+  collections::BTreeMap,
+  path::{Path, PathBuf},
+};
+use tracing::{debug, instrument};
+use crate::{
+  error::{yaml_error, ConfigError},
+  model::{mod_entry::ConfigModEntry, settings::ModSettingsEntry},
+  sync::SaveSync,
+};
+use furrctorio_core::{
+  constants::BUILTIN_MODS,
+  prelude::{Context, Error, FModRelease, GameVersion, ReleasePolicy, SaveHeader},
+};
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 #[serde(rename_all = "PascalCase")]
 pub struct FurrConfig {
   pub(crate) metadata: Metadata,
   pub mods: Vec<ConfigModEntry>,
+  /// The startup and runtime-global settings written to `mod-settings.dat`, by mod name.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub settings: BTreeMap<String, ModSettingsEntry>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -17,6 +32,10 @@ pub struct Metadata {
   pub version: Version,
   pub factorio_version: Option<Version>,
   pub factorio_mod_folder: Option<PathBuf>,
+  /// True if the game has the Space Age expansion, which provides the `elevated-rails`,
+  /// `quality` and `space-age` mods from Factorio 2.0 on.
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub space_age: bool,
 }
 
 impl Default for Metadata {
@@ -24,15 +43,259 @@ impl Default for Metadata {
     Self {
       version: Version::new(0, 1, 0),
       factorio_version: None,
-      factorio_mod_folder: dirs::home_dir()
-        .map(|folder| {
-          let f = folder.join(".factorio").join("mods");
-          if !f.exists() {
-            create_dir_all(&f).unwrap();
-          }
-          Some(f)
+      // The folder is created when a plan is applied.
+      factorio_mod_folder: dirs::home_dir().map(|folder| folder.join(".factorio").join("mods")),
+      space_age: false,
+    }
+  }
+}
+
+impl FurrConfig {
+  /// Reads a configuration from a YAML file.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - The path of the configuration file.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<Self, ConfigError>` - Returns the parsed configuration or a ConfigError.
+  #[instrument]
+  pub async fn load(path: &Path) -> Result<Self, ConfigError> {
+    debug!("Reading configuration from '{}'", path.display());
+
+    let content = tokio::fs::read_to_string(path).await?;
+    serde_yaml::from_str(&content).map_err(yaml_error)
+  }
+
+  /// Writes the configuration to a YAML file, replacing it if it already exists.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - The path of the configuration file.
+  #[instrument(skip(self))]
+  pub async fn save(&self, path: &Path) -> Result<(), ConfigError> {
+    debug!("Writing configuration to '{}'", path.display());
+
+    let content = serde_yaml::to_string(self).map_err(yaml_error)?;
+    tokio::fs::write(path, content).await?;
+    Ok(())
+  }
+
+  /// Creates a configuration loading exactly the mods of a save.
+  ///
+  /// # Arguments
+  ///
+  /// * `save` - The header of the save, see [`SaveHeader::from_zip`].
+  pub fn from_save(save: &SaveHeader) -> Result<Self, ConfigError> {
+    let mut cfg = FurrConfig::default();
+    SaveSync::new(&cfg, save)?.apply(&mut cfg);
+    Ok(cfg)
+  }
+
+  /// Returns the metadata of the configuration.
+  pub fn metadata(&self) -> &Metadata {
+    &self.metadata
+  }
+
+  /// Returns a mutable reference to the metadata of the configuration.
+  pub fn metadata_mut(&mut self) -> &mut Metadata {
+    &mut self.metadata
+  }
+
+  /// Returns the mod folder, or an error if none is configured.
+  pub fn mod_folder(&self) -> Result<&Path, ConfigError> {
+    self
+      .metadata
+      .factorio_mod_folder
+      .as_deref()
+      .ok_or(ConfigError::MissingModFolder)
+  }
+
+  /// Returns the entry of the mod with the given name.
+  pub fn get_mod(&self, name: &str) -> Option<&ConfigModEntry> {
+    self.mods.iter().find(|m| m.name == name)
+  }
+
+  /// Returns a mutable reference to the entry of the mod with the given name.
+  pub fn get_mod_mut(&mut self, name: &str) -> Option<&mut ConfigModEntry> {
+    self.mods.iter_mut().find(|m| m.name == name)
+  }
+
+  /// Adds a mod to the configuration.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<(), ConfigError>` - Returns `ConfigError::ModAlreadyPresent` if a mod with the same name exists.
+  pub fn add_mod(&mut self, entry: ConfigModEntry) -> Result<(), ConfigError> {
+    if self.get_mod(&entry.name).is_some() {
+      return Err(ConfigError::ModAlreadyPresent(entry.name));
+    }
+
+    self.mods.push(entry);
+    Ok(())
+  }
+
+  /// Removes a mod from the configuration and returns its entry.
+  pub fn remove_mod(&mut self, name: &str) -> Result<ConfigModEntry, ConfigError> {
+    let index = self
+      .mods
+      .iter()
+      .position(|m| m.name == name)
+      .ok_or_else(|| ConfigError::ModNotFound(name.to_string()))?;
+
+    Ok(self.mods.remove(index))
+  }
+
+  /// Enables or disables a mod of the configuration.
+  pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<(), ConfigError> {
+    self
+      .get_mod_mut(name)
+      .ok_or_else(|| ConfigError::ModNotFound(name.to_string()))?
+      .enabled = enabled;
+    Ok(())
+  }
+
+  /// Returns the mods that come with the configured game, see [`GameVersion::builtin_mods`].
+  ///
+  /// When the Factorio version is unknown, the expansion mods are included if it is owned.
+  pub fn builtin_mods(&self) -> Vec<&'static str> {
+    let game_version = self.metadata.factorio_version.as_ref().map(GameVersion::from);
+    game_version
+      .unwrap_or(GameVersion::V2_0)
+      .builtin_mods(self.metadata.space_age)
+  }
+
+  /// Checks that every enabled built-in mod of the configuration comes with the configured game.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<(), ConfigError>` - Returns `ConfigError::MissingDlc` for the first enabled mod of an expansion the game doesn't have.
+  pub fn check_builtin_mods(&self) -> Result<(), ConfigError> {
+    let available = self.builtin_mods();
+    match self.mods.iter().find(|m| {
+      m.enabled && BUILTIN_MODS.contains(&m.name.as_str()) && !available.contains(&m.name.as_str())
+    }) {
+      Some(entry) => Err(ConfigError::MissingDlc(entry.name.clone())),
+      None => Ok(()),
+    }
+  }
+
+  /// Finds the most recent release of each enabled mod that matches its requirement and the
+  /// Factorio version of the configuration.
+  ///
+  /// All the mods are fetched in a handful of requests, see [`Context::get_mods_by_name`].
+  /// The base mod and the DLCs come with the game and are skipped.
+  ///
+  /// # Arguments
+  ///
+  /// * `policy` - The 0.x and cooldown rules.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<BTreeMap<String, Result<FModRelease, Error>>, ConfigError>` - Returns the release of each mod, or `Error::NoCompatibleRelease` explaining why none fits.
+  #[instrument(skip_all)]
+  pub async fn latest_releases(
+    &self,
+    ctx: &Context,
+    policy: &ReleasePolicy,
+  ) -> Result<BTreeMap<String, Result<FModRelease, Error>>, ConfigError> {
+    let entries: Vec<&ConfigModEntry> = self
+      .mods
+      .iter()
+      .filter(|m| m.enabled && !BUILTIN_MODS.contains(&m.name.as_str()))
+      .collect();
+    let names: Vec<&str> = entries.iter().map(|m| m.name.as_str()).collect();
+    let mods = ctx.get_mods_by_name(&names).await?;
+    let game_version = self.metadata.factorio_version.as_ref().map(GameVersion::from);
+
+    Ok(
+      entries
+        .iter()
+        .map(|entry| {
+          let releases = mods.get(&entry.name).map_or(&[][..], |m| &m.releases);
+          let release = entry.selector(game_version, policy).select(&entry.name, releases);
+          (entry.name.clone(), release)
         })
-        .unwrap_or(None),
+        .collect(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use furrctorio_core::prelude::FactorioVersionReq;
+
+  fn config() -> FurrConfig {
+    FurrConfig {
+      metadata: Metadata {
+        version: Version::new(0, 1, 0),
+        factorio_version: Some(Version::new(1, 1, 110)),
+        factorio_mod_folder: Some(PathBuf::from("/srv/factorio/mods")),
+        space_age: false,
+      },
+      mods: vec![ConfigModEntry::new(
+        "flib".to_string(),
+        ">=0.12".parse().unwrap(),
+        true,
+      )],
+      settings: BTreeMap::new(),
     }
   }
+
+  #[test]
+  fn test_add_remove_mod() {
+    let mut cfg = config();
+
+    assert!(cfg
+      .add_mod(ConfigModEntry::new("flib".to_string(), FactorioVersionReq::STAR, true))
+      .is_err());
+    cfg
+      .add_mod(ConfigModEntry::new("stdlib".to_string(), FactorioVersionReq::STAR, true))
+      .unwrap();
+    assert_eq!(cfg.mods.len(), 2);
+
+    cfg.set_enabled("stdlib", false).unwrap();
+    assert!(!cfg.get_mod("stdlib").unwrap().enabled);
+
+    assert_eq!(cfg.remove_mod("flib").unwrap().name, "flib");
+    assert!(cfg.remove_mod("flib").is_err());
+    assert!(cfg.set_enabled("flib", true).is_err());
+  }
+
+  #[test]
+  fn test_check_builtin_mods() {
+    let mut cfg = config();
+    cfg
+      .add_mod(ConfigModEntry::new("quality".to_string(), FactorioVersionReq::STAR, false))
+      .unwrap();
+    assert!(cfg.check_builtin_mods().is_ok());
+
+    cfg.set_enabled("quality", true).unwrap();
+    assert!(matches!(cfg.check_builtin_mods(), Err(ConfigError::MissingDlc(name)) if name == "quality"));
+
+    // The expansion needs Factorio 2.0.
+    cfg.metadata_mut().space_age = true;
+    assert!(cfg.check_builtin_mods().is_err());
+    cfg.metadata_mut().factorio_version = Some(Version::new(2, 0, 28));
+    assert!(cfg.check_builtin_mods().is_ok());
+    assert_eq!(cfg.builtin_mods(), ["base", "elevated-rails", "quality", "space-age"]);
+  }
+
+  #[tokio::test]
+  async fn test_load_save() {
+    let path = std::env::temp_dir().join(format!("furrctorio-config-{}.yaml", std::process::id()));
+    let cfg = config();
+
+    cfg.save(&path).await.unwrap();
+    let loaded = FurrConfig::load(&path).await.unwrap();
+    tokio::fs::remove_file(&path).await.unwrap();
+
+    assert_eq!(loaded.metadata().factorio_version, Some(Version::new(1, 1, 110)));
+    assert_eq!(loaded.mod_folder().unwrap(), Path::new("/srv/factorio/mods"));
+    assert_eq!(loaded.mods.len(), 1);
+    assert_eq!(loaded.mods[0].name, "flib");
+    assert_eq!(loaded.mods[0].version, ">=0.12".parse().unwrap());
+  }
 }
@@ -0,0 +1,297 @@
+use crate::{
+  error::{yaml_error, ConfigError},
+  model::config::FurrConfig,
+};
+use furrctorio_core::{
+  constants::BUILTIN_MODS,
+  prelude::{Context, FModRelease, FactorioVersion, FactorioVersionReq, GameVersion},
+  resolver::Resolver,
+};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{debug, instrument};
+
+/// The name of the lockfile, written next to the configuration file.
+pub const LOCKFILE_NAME: &str = "furrctorio.lock";
+
+/// Pins every mod needed by a [`FurrConfig`] to an exact release.
+///
+/// The lockfile contains the enabled mods of the configuration and all of their
+/// required dependencies, so that applying the same configuration on another server
+/// installs exactly the same files.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct LockFile {
+  #[serde(rename = "_v")]
+  pub version: Version,
+  /// The Factorio version the mods were resolved for.
+  pub factorio_version: Option<Version>,
+  /// True if the mods were resolved for a game with the Space Age expansion.
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub space_age: bool,
+  /// The locked mods, sorted by name.
+  pub mods: Vec<LockedMod>,
+}
+
+/// A mod pinned to an exact release.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct LockedMod {
+  /// The name of the mod.
+  pub name: String,
+  /// The version of the locked release.
+  pub version: FactorioVersion,
+  /// The name of the file of the locked release.
+  pub file_name: String,
+  /// The URL to download the locked release, relative to the mod portal.
+  pub download_url: String,
+  /// The SHA1 hash of the locked release.
+  pub sha1: String,
+  /// True if the mod is part of the configuration, false if it is only a dependency.
+  pub direct: bool,
+}
+
+impl LockedMod {
+  /// Creates a locked mod from a release of the mod portal.
+  pub fn from_release(name: &str, release: &FModRelease, direct: bool) -> Self {
+    Self {
+      name: name.to_string(),
+      version: release.version.clone(),
+      file_name: release.file_name.clone(),
+      download_url: release.download_url.clone(),
+      sha1: release.sha1.clone(),
+      direct,
+    }
+  }
+
+  /// Returns the release this entry is locked to, which can be downloaded and validated.
+  ///
+  /// Only the fields stored in the lockfile are set.
+  pub fn release(&self) -> FModRelease {
+    FModRelease {
+      download_url: self.download_url.clone(),
+      file_name: self.file_name.clone(),
+      version: self.version.clone(),
+      sha1: self.sha1.clone(),
+      ..Default::default()
+    }
+  }
+}
+
+impl LockFile {
+  /// Returns the path of the lockfile belonging to a configuration file.
+  pub fn path_for(config_path: &Path) -> PathBuf {
+    config_path.with_file_name(LOCKFILE_NAME)
+  }
+
+  /// Reads a lockfile.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<Option<Self>, ConfigError>` - Returns None if the file does not exist.
+  #[instrument]
+  pub async fn load(path: &Path) -> Result<Option<Self>, ConfigError> {
+    match tokio::fs::read_to_string(path).await {
+      Ok(content) => Ok(Some(serde_yaml::from_str(&content).map_err(yaml_error)?)),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+      Err(e) => Err(e.into()),
+    }
+  }
+
+  /// Writes the lockfile, replacing it if it already exists.
+  #[instrument(skip(self))]
+  pub async fn save(&self, path: &Path) -> Result<(), ConfigError> {
+    debug!("Writing lockfile to '{}'", path.display());
+
+    let content = serde_yaml::to_string(self).map_err(yaml_error)?;
+    tokio::fs::write(path, content).await?;
+    Ok(())
+  }
+
+  /// Returns the locked entry of a mod.
+  pub fn get(&self, name: &str) -> Option<&LockedMod> {
+    self.mods.iter().find(|m| m.name == name)
+  }
+
+  /// Checks whether the lockfile still describes the configuration.
+  ///
+  /// The lockfile is up to date when it was resolved for the same game, when every enabled
+  /// mod of the configuration is locked to a release matching its requirement, and when no
+  /// other mod is locked as part of the configuration. Built-in mods are never locked.
+  pub fn is_up_to_date(&self, cfg: &FurrConfig) -> bool {
+    if self.factorio_version != cfg.metadata().factorio_version
+      || self.space_age != cfg.metadata().space_age
+    {
+      return false;
+    }
+
+    let enabled = cfg
+      .mods
+      .iter()
+      .filter(|m| m.enabled && !BUILTIN_MODS.contains(&m.name.as_str()));
+    let all_locked = enabled.clone().all(|entry| {
+      self.get(&entry.name).is_some_and(|locked| {
+        locked.direct && locked.release().match_version(&entry.version)
+      })
+    });
+
+    all_locked && self.mods.iter().filter(|m| m.direct).count() == enabled.count()
+  }
+
+  /// Resolves every enabled mod of a configuration and its required dependencies.
+  ///
+  /// Releases locked in `previous` are kept as long as they still satisfy every constraint,
+  /// other mods are locked to their most recent release that does. The built-in mods of the
+  /// game satisfy the dependencies on them and are never locked.
+  ///
+  /// # Arguments
+  ///
+  /// * `cfg` - The configuration to resolve.
+  /// * `ctx` - The context used to query the mod portal.
+  /// * `previous` - The lockfile to keep releases from, if any.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<Self, ConfigError>` - Returns the new lockfile or a ConfigError explaining the conflict, or `ConfigError::MissingDlc` if the configuration enables a mod of an expansion the game doesn't have.
+  #[instrument(skip_all)]
+  pub async fn resolve(
+    cfg: &FurrConfig,
+    ctx: &Context,
+    previous: Option<&LockFile>,
+  ) -> Result<Self, ConfigError> {
+    cfg.check_builtin_mods()?;
+    let requirements: Vec<(String, FactorioVersionReq)> = cfg
+      .mods
+      .iter()
+      .filter(|m| m.enabled)
+      .map(|m| (m.name.clone(), m.version.clone()))
+      .collect();
+
+    // The base mod and the DLCs come with the game and cannot be downloaded.
+    let factorio_version = cfg.metadata().factorio_version.as_ref();
+    let mut resolver = Resolver::new(ctx).builtin(
+      factorio_version.map(FactorioVersion::from),
+      cfg.metadata().space_age,
+    );
+    if let Some(version) = factorio_version {
+      resolver = resolver.game_version(GameVersion::from(version));
+    }
+    for locked in previous.iter().flat_map(|lock| &lock.mods) {
+      resolver = resolver.prefer(&locked.name, &locked.sha1);
+    }
+
+    let resolution = resolver.resolve(&requirements).await?;
+    let mods = resolution
+      .releases
+      .iter()
+      .map(|(name, release)| {
+        debug!("Locking '{}' to {}", name, release.version);
+        let direct = requirements.iter().any(|(n, _)| n == name);
+        LockedMod::from_release(name, release, direct)
+      })
+      .collect();
+
+    Ok(Self {
+      version: Version::new(0, 1, 0),
+      factorio_version: cfg.metadata().factorio_version.clone(),
+      space_age: cfg.metadata().space_age,
+      mods,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::model::mod_entry::ConfigModEntry;
+
+  fn locked(name: &str, version: &str, direct: bool) -> LockedMod {
+    LockedMod {
+      name: name.to_string(),
+      version: version.parse().unwrap(),
+      file_name: format!("{}_{}.zip", name, version),
+      download_url: format!("/download/{}/0123456789abcdef", name),
+      sha1: "55f7bbcfc0c0e831008b57c321db509bf3a25285".to_string(),
+      direct,
+    }
+  }
+
+  fn config(mods: Vec<ConfigModEntry>) -> FurrConfig {
+    let mut cfg = FurrConfig {
+      mods,
+      ..Default::default()
+    };
+    cfg.metadata_mut().factorio_version = Some(Version::new(1, 1, 110));
+    cfg
+  }
+
+  #[test]
+  fn test_path_for() {
+    assert_eq!(
+      LockFile::path_for(Path::new("/srv/factorio/furrctorio.yaml")),
+      PathBuf::from("/srv/factorio/furrctorio.lock")
+    );
+  }
+
+  #[test]
+  fn test_is_up_to_date() {
+    let lock = LockFile {
+      version: Version::new(0, 1, 0),
+      factorio_version: Some(Version::new(1, 1, 110)),
+      space_age: false,
+      mods: vec![locked("flib", "0.12.9", true), locked("stdlib", "1.0.8", false)],
+    };
+
+    let flib = ConfigModEntry::new("flib".to_string(), ">=0.12".parse().unwrap(), true);
+    assert!(lock.is_up_to_date(&config(vec![flib.clone()])));
+
+    let disabled = ConfigModEntry::new("helmod".to_string(), FactorioVersionReq::STAR, false);
+    assert!(lock.is_up_to_date(&config(vec![flib.clone(), disabled])));
+
+    let newer = ConfigModEntry::new("flib".to_string(), ">=0.13".parse().unwrap(), true);
+    assert!(!lock.is_up_to_date(&config(vec![newer])));
+
+    let added = ConfigModEntry::new("helmod".to_string(), FactorioVersionReq::STAR, true);
+    assert!(!lock.is_up_to_date(&config(vec![flib.clone(), added])));
+
+    assert!(!lock.is_up_to_date(&config(vec![])));
+
+    let mut other_version = config(vec![flib.clone()]);
+    other_version.metadata_mut().factorio_version = Some(Version::new(1, 1, 109));
+    assert!(!lock.is_up_to_date(&other_version));
+
+    let base = ConfigModEntry::new("base".to_string(), ">= 1.1".parse().unwrap(), true);
+    assert!(lock.is_up_to_date(&config(vec![flib.clone(), base])));
+
+    let mut space_age = config(vec![flib]);
+    space_age.metadata_mut().space_age = true;
+    assert!(!lock.is_up_to_date(&space_age));
+  }
+
+  #[tokio::test]
+  async fn test_load_save() {
+    let path = std::env::temp_dir().join(format!("furrctorio-{}.lock", std::process::id()));
+    assert!(LockFile::load(&path).await.unwrap().is_none());
+
+    let lock = LockFile {
+      version: Version::new(0, 1, 0),
+      factorio_version: Some(Version::new(1, 1, 110)),
+      space_age: false,
+      mods: vec![locked("flib", "0.12.9", true)],
+    };
+    lock.save(&path).await.unwrap();
+    let loaded = LockFile::load(&path).await.unwrap();
+    tokio::fs::remove_file(&path).await.unwrap();
+
+    assert_eq!(loaded, Some(lock));
+  }
+
+  #[test]
+  fn test_locked_release() {
+    let release = locked("flib", "0.12.9", true).release();
+
+    assert_eq!(release.file_name, "flib_0.12.9.zip");
+    assert_eq!(release.version.to_string(), "0.12.9");
+    assert!(release.match_version(&"=0.12.9".parse().unwrap()));
+  }
+}
@@ -1,2 +1,4 @@
 pub mod config;
-pub mod mod_entry;
\ No newline at end of file
+pub mod lockfile;
+pub mod mod_entry;
+pub mod settings;
\ No newline at end of file
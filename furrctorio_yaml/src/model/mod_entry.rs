@@ -1,18 +1,20 @@
-use furrctorio_core::prelude::{Context, FModFull, FModRelease, FModShort};
-use semver::VersionReq;
+use furrctorio_core::prelude::{
+  Context, Error, FModFull, FModRelease, FModShort, FactorioVersionReq, GameVersion, ReleasePolicy,
+  ReleaseSelector,
+};
 use serde::{Deserialize, Serialize};
 use tracing::{debug, instrument};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ConfigModEntry {
   pub name: String,
-  pub version: VersionReq,
+  pub version: FactorioVersionReq,
   pub enabled: bool,
 }
 
 impl ConfigModEntry {
   #[instrument]
-  pub fn new(name: String, version: VersionReq, enabled: bool) -> Self {
+  pub fn new(name: String, version: FactorioVersionReq, enabled: bool) -> Self {
     Self {
       name,
       version,
@@ -21,40 +23,56 @@ impl ConfigModEntry {
   }
 
   #[instrument]
-  pub async fn get_mod(&self, ctx: &Context) -> Result<FModShort, reqwest::Error> {
+  pub async fn get_mod(&self, ctx: &Context) -> Result<FModShort, Error> {
     debug!("Downloading short information for mod '{}'", &self.name);
 
     ctx.get_mod_info(&self.name).await
   }
 
   #[instrument]
-  pub async fn get_mod_full(&self, ctx: &Context) -> Result<FModFull, reqwest::Error> {
+  pub async fn get_mod_full(&self, ctx: &Context) -> Result<FModFull, Error> {
     debug!("Downloading full information for mod '{}'", &self.name);
 
     ctx.get_mod_info_full(&self.name).await
   }
 
-  pub async fn find_last_release(&self, ctx: &Context) -> Result<Option<FModRelease>, reqwest::Error> {
+  /// Returns a selector for the releases matching the version requirement of this entry.
+  ///
+  /// # Arguments
+  ///
+  /// * `game_version` - The version of the game the release must load on, if known.
+  /// * `policy` - The 0.x and cooldown rules.
+  pub fn selector(&self, game_version: Option<GameVersion>, policy: &ReleasePolicy) -> ReleaseSelector {
+    let selector = ReleaseSelector::new(self.version.clone()).policy(policy.clone());
+    match game_version {
+      Some(game_version) => selector.game_version(game_version),
+      None => selector,
+    }
+  }
+
+  /// Finds the most recent release of this entry that loads on the given version of the game.
+  ///
+  /// The latest release is used when it is acceptable, otherwise every release is fetched.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<FModRelease, Error>` - Returns the selected release, or `Error::NoCompatibleRelease` listing why each release was rejected.
+  pub async fn find_last_release(
+    &self,
+    ctx: &Context,
+    game_version: Option<GameVersion>,
+    policy: &ReleasePolicy,
+  ) -> Result<FModRelease, Error> {
+    let selector = self.selector(game_version, policy);
     let smod = self.get_mod(ctx).await?;
     if let Some(last) = smod.latest_release {
-      return Ok(Some(last));        
+      if selector.check(&last).is_ok() {
+        return Ok(last);
+      }
     }
 
     let fmod = self.get_mod_full(ctx).await?;
-    if fmod.releases.is_empty() {
-      Ok(None)
-    } else {
-      let mut releases = fmod
-        .releases
-        .clone()
-        .iter()
-        .filter(|&r| r.match_version(&self.version))
-        .cloned()
-        .collect::<Vec<FModRelease>>();
-      releases.sort();
-
-      Ok(Some(releases.last().unwrap().clone()))
-    }
+    selector.select(&self.name, &fmod.releases)
   }
 }
 
@@ -62,15 +80,14 @@ impl ConfigModEntry {
 mod tests {
   use super::*;
   use furrctorio_core::prelude::Context;
-  use semver::VersionReq;
 
   #[tokio::test]
   async fn test_get_mod() {
     dotenv::dotenv().ok();
     
-    let ctx = Context::new_from_env();
+    let ctx = Context::new_from_env().unwrap();
 
-    let entry = ConfigModEntry::new("stdlib".to_string(), VersionReq::parse("*").unwrap(), true);
+    let entry = ConfigModEntry::new("stdlib".to_string(), FactorioVersionReq::STAR, true);
 
     let mod_info = entry.get_mod(&ctx).await.unwrap();
 
@@ -81,9 +98,9 @@ mod tests {
   async fn test_get_mod_full() {
     dotenv::dotenv().ok();
 
-    let ctx = Context::new_from_env();
+    let ctx = Context::new_from_env().unwrap();
 
-    let entry = ConfigModEntry::new("stdlib".to_string(), VersionReq::parse("*").unwrap(), true);
+    let entry = ConfigModEntry::new("stdlib".to_string(), FactorioVersionReq::STAR, true);
 
     let mod_info = entry.get_mod_full(&ctx).await.unwrap();
 
@@ -94,18 +111,42 @@ mod tests {
   async fn test_find_last_release() {
     dotenv::dotenv().ok();
     
-    let ctx = Context::new_from_env();
-
-    let entry = ConfigModEntry::new("stdlib".to_string(), VersionReq::parse("*").unwrap(), true);
+    let ctx = Context::new_from_env().unwrap();
 
-    let releases = entry.find_last_release(&ctx).await.unwrap();
+    let entry = ConfigModEntry::new("stdlib".to_string(), FactorioVersionReq::STAR, true);
 
-    assert!(releases.is_some());
-
-    let rel = releases.unwrap();
+    let rel = entry
+      .find_last_release(&ctx, None, &ReleasePolicy::default())
+      .await
+      .unwrap();
     let binding = entry.get_mod(&ctx).await.unwrap();
     let max_version = binding.releases.iter().max().unwrap();
 
     assert_eq!(&rel, max_version);
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_selector() {
+    let release = |version: &str, factorio_version: GameVersion| {
+      let mut release = FModRelease {
+        version: version.parse().unwrap(),
+        ..Default::default()
+      };
+      release.info_json.factorio_version = Some(factorio_version);
+      release
+    };
+    let releases = [
+      release("1.0.0", GameVersion::V1_0),
+      release("1.1.0", GameVersion::V1_1),
+      release("2.0.0", GameVersion::V2_0),
+    ];
+    let entry = ConfigModEntry::new("a".to_string(), "<2.0".parse().unwrap(), true);
+    let policy = ReleasePolicy::default();
+
+    let selected = entry.selector(None, &policy).select("a", &releases).unwrap();
+    assert_eq!(selected.version.to_string(), "1.1.0");
+    let selected = entry.selector(Some(GameVersion::V1_0), &policy).select("a", &releases).unwrap();
+    assert_eq!(selected.version.to_string(), "1.0.0");
+    assert!(entry.selector(Some(GameVersion::V2_0), &policy).select("a", &releases).is_err());
+  }
+}
@@ -0,0 +1,133 @@
+use furrctorio_core::prelude::{PropertyTree, SettingsSection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The settings of a mod declared in the configuration, by setting name.
+///
+/// Per-user settings are left out, as they belong to each player rather than to the server.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct ModSettingsEntry {
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub startup: BTreeMap<String, SettingValue>,
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub runtime_global: BTreeMap<String, SettingValue>,
+}
+
+impl ModSettingsEntry {
+  /// Lists every declared setting with its section.
+  pub fn iter(&self) -> impl Iterator<Item = (SettingsSection, &String, &SettingValue)> {
+    let startup = self.startup.iter().map(|(k, v)| (SettingsSection::Startup, k, v));
+    let runtime = self
+      .runtime_global
+      .iter()
+      .map(|(k, v)| (SettingsSection::RuntimeGlobal, k, v));
+    startup.chain(runtime)
+  }
+}
+
+/// The value of a setting as written in the configuration.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum SettingValue {
+  Bool(bool),
+  Int(i64),
+  Float(f64),
+  String(String),
+  Color(Color),
+}
+
+/// The value of a color setting, with components between 0 and 1.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Color {
+  pub r: f64,
+  pub g: f64,
+  pub b: f64,
+  #[serde(default = "Color::opaque")]
+  pub a: f64,
+}
+
+impl Color {
+  fn opaque() -> f64 {
+    1.0
+  }
+}
+
+impl SettingValue {
+  /// Converts the value to the property tree stored in `mod-settings.dat`.
+  ///
+  /// # Arguments
+  ///
+  /// * `current` - The value currently in the file. Integers are written as numbers unless
+  ///   the game already stored them as 64-bit integers.
+  pub fn to_property_tree(&self, current: Option<&PropertyTree>) -> PropertyTree {
+    match self {
+      SettingValue::Bool(value) => PropertyTree::Bool(*value),
+      SettingValue::Int(value) => match current {
+        Some(PropertyTree::SignedInteger(_)) => PropertyTree::SignedInteger(*value),
+        Some(PropertyTree::UnsignedInteger(_)) if *value >= 0 => {
+          PropertyTree::UnsignedInteger(*value as u64)
+        }
+        _ => PropertyTree::Number(*value as f64),
+      },
+      SettingValue::Float(value) => PropertyTree::Number(*value),
+      SettingValue::String(value) => PropertyTree::String(value.clone()),
+      SettingValue::Color(color) => PropertyTree::Dictionary(vec![
+        ("r".to_string(), PropertyTree::Number(color.r)),
+        ("g".to_string(), PropertyTree::Number(color.g)),
+        ("b".to_string(), PropertyTree::Number(color.b)),
+        ("a".to_string(), PropertyTree::Number(color.a)),
+      ]),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_settings() {
+    let entry: ModSettingsEntry = serde_yaml::from_str(
+      r#"
+Startup:
+  rso-regions: 5
+  rso-ore-frequency: 1.5
+RuntimeGlobal:
+  flib-gui: blue
+  flib-enabled: true
+  flib-color: { r: 1, g: 0.5, b: 0 }
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(entry.startup["rso-regions"], SettingValue::Int(5));
+    assert_eq!(entry.startup["rso-ore-frequency"], SettingValue::Float(1.5));
+    assert_eq!(entry.runtime_global["flib-enabled"], SettingValue::Bool(true));
+    assert_eq!(
+      entry.runtime_global["flib-color"],
+      SettingValue::Color(Color {
+        r: 1.0,
+        g: 0.5,
+        b: 0.0,
+        a: 1.0
+      })
+    );
+    assert_eq!(entry.iter().count(), 5);
+  }
+
+  #[test]
+  fn test_to_property_tree() {
+    let value = SettingValue::Int(3);
+
+    assert_eq!(value.to_property_tree(None), PropertyTree::Number(3.0));
+    assert_eq!(
+      value.to_property_tree(Some(&PropertyTree::SignedInteger(1))),
+      PropertyTree::SignedInteger(3)
+    );
+    assert_eq!(
+      SettingValue::String("blue".to_string()).to_property_tree(None),
+      PropertyTree::String("blue".to_string())
+    );
+  }
+}
@@ -0,0 +1,628 @@
+//! Compares the desired state of a configuration with the mod folder of a server.
+//!
+//! A [`Plan`] lists every change needed to make the mod folder and its `mod-list.json`
+//! match a [`LockFile`], and its `mod-settings.dat` match the settings of the configuration.
+//! It can be reviewed before being applied with [`Plan::apply`].
+
+use crate::{
+  error::ConfigError,
+  model::{
+    config::FurrConfig,
+    lockfile::{LockFile, LockedMod},
+  },
+};
+use furrctorio_core::prelude::{
+  installed_mods, ApplicationVersion, Context, Downloader, FModRelease, FactorioVersion,
+  InstalledMod, ModList, ModSettings, PropertyTree, SettingsSection,
+};
+use semver::Version;
+use std::{
+  cmp::{Ordering, Reverse},
+  collections::BTreeMap,
+  fmt::Display,
+  path::PathBuf,
+  sync::Arc,
+};
+use tracing::{info, instrument};
+
+/// A single change to the mod folder.
+///
+/// Only versioned releases are changed, unversioned mods like mods under development are left alone.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+  /// Downloads a mod that is not installed.
+  Install { release: LockedMod },
+  /// Replaces an installed release by a newer one.
+  Upgrade { from: InstalledMod, to: LockedMod },
+  /// Replaces an installed release by an older one.
+  Downgrade { from: InstalledMod, to: LockedMod },
+  /// Deletes an installed release.
+  Remove { installed: InstalledMod },
+  /// Enables a mod in `mod-list.json`.
+  Enable { name: String },
+  /// Disables a mod in `mod-list.json`.
+  Disable { name: String },
+  /// Changes the value of a setting in `mod-settings.dat`.
+  Setting {
+    mod_name: String,
+    section: SettingsSection,
+    name: String,
+    from: Option<PropertyTree>,
+    to: PropertyTree,
+  },
+}
+
+impl Change {
+  /// Returns the name of the mod the change applies to.
+  pub fn name(&self) -> &str {
+    match self {
+      Change::Install { release } => &release.name,
+      Change::Upgrade { to, .. } | Change::Downgrade { to, .. } => &to.name,
+      Change::Remove { installed } => &installed.name,
+      Change::Enable { name } | Change::Disable { name } => name,
+      Change::Setting { mod_name, .. } => mod_name,
+    }
+  }
+}
+
+impl Display for Change {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Change::Install { release } => write!(f, "+ {} {}", release.name, release.version),
+      Change::Upgrade { from, to } => write!(f, "~ {} -> {}", from, to.version),
+      Change::Downgrade { from, to } => write!(f, "~ {} -> {} (downgrade)", from, to.version),
+      Change::Remove { installed } => write!(f, "- {}", installed),
+      Change::Enable { name } => write!(f, "* {} enabled", name),
+      Change::Disable { name } => write!(f, "* {} disabled", name),
+      Change::Setting {
+        section,
+        name,
+        from,
+        to,
+        ..
+      } => match from {
+        Some(from) => write!(f, "= {} ({}): {} -> {}", name, section, from, to),
+        None => write!(f, "= {} ({}): {}", name, section, to),
+      },
+    }
+  }
+}
+
+/// The changes needed to bring a mod folder to the desired state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+  /// The mod folder the plan applies to.
+  pub mod_folder: PathBuf,
+  /// The version of the game, used when `mod-settings.dat` has to be created.
+  pub factorio_version: Option<Version>,
+  /// The changes, sorted by mod name.
+  pub changes: Vec<Change>,
+}
+
+impl Plan {
+  /// Computes the changes needed to install exactly the releases of a lockfile.
+  ///
+  /// Every locked mod is installed and enabled, the disabled mods of the configuration
+  /// are kept but disabled, and every other release found in the mod folder is removed.
+  /// The built-in mods of the configuration that come with the game, like the DLCs, are
+  /// only enabled or disabled in `mod-list.json`.
+  /// Settings of the configuration that differ from `mod-settings.dat` are changed, other
+  /// settings are left alone.
+  ///
+  /// # Arguments
+  ///
+  /// * `cfg` - The configuration, which gives the mod folder, the disabled mods and the settings.
+  /// * `lock` - The releases to install.
+  ///
+  /// # Returns
+  ///
+  /// * `Result<Self, ConfigError>` - Returns the plan or a ConfigError if the mod folder cannot be read, or `ConfigError::MissingDlc` if the configuration enables a mod of an expansion the game doesn't have.
+  #[instrument(skip_all)]
+  pub async fn new(cfg: &FurrConfig, lock: &LockFile) -> Result<Self, ConfigError> {
+    cfg.check_builtin_mods()?;
+    let mod_folder = cfg.mod_folder()?.to_path_buf();
+    let mod_list = ModList::load(&mod_folder).await?;
+    let enabled = |name: &str| mod_list.get(name).map(|m| m.enabled);
+
+    let mut installed: BTreeMap<String, Vec<InstalledMod>> = BTreeMap::new();
+    for release in installed_mods(&mod_folder).await? {
+      if release.version.is_some() {
+        installed.entry(release.name.clone()).or_default().push(release);
+      }
+    }
+
+    let mut changes = Vec::new();
+    for locked in &lock.mods {
+      let mut releases = installed.remove(&locked.name).unwrap_or_default();
+      releases.sort_by_key(|r| Reverse(version(r)));
+
+      match releases.iter().position(|r| version(r).as_ref() == Some(&locked.version)) {
+        Some(index) => {
+          releases.remove(index);
+        }
+        None if releases.is_empty() => changes.push(Change::Install {
+          release: locked.clone(),
+        }),
+        None => {
+          let from = releases.remove(0);
+          changes.push(match version(&from).map(|v| locked.version.cmp(&v)) {
+            Some(Ordering::Less) => Change::Downgrade {
+              from,
+              to: locked.clone(),
+            },
+            _ => Change::Upgrade {
+              from,
+              to: locked.clone(),
+            },
+          });
+        }
+      }
+
+      changes.extend(releases.into_iter().map(|installed| Change::Remove { installed }));
+      if enabled(&locked.name) == Some(false) {
+        changes.push(Change::Enable {
+          name: locked.name.clone(),
+        });
+      }
+    }
+
+    for entry in cfg.mods.iter().filter(|m| !m.enabled) {
+      if lock.get(&entry.name).is_none()
+        && installed.remove(&entry.name).is_some()
+        && enabled(&entry.name) != Some(false)
+      {
+        changes.push(Change::Disable {
+          name: entry.name.clone(),
+        });
+      }
+    }
+
+    // Like Factorio, built-in mods without an entry count as enabled.
+    let builtin_mods = cfg.builtin_mods();
+    let builtin = cfg
+      .mods
+      .iter()
+      .filter(|m| builtin_mods.contains(&m.name.as_str()));
+    for entry in builtin {
+      let name = entry.name.clone();
+      match (entry.enabled, enabled(&entry.name)) {
+        (true, Some(false)) => changes.push(Change::Enable { name }),
+        (false, Some(true) | None) => changes.push(Change::Disable { name }),
+        _ => (),
+      }
+    }
+
+    changes.extend(
+      installed
+        .into_values()
+        .flatten()
+        .map(|installed| Change::Remove { installed }),
+    );
+
+    let mod_settings = ModSettings::load(&mod_folder).await?;
+    for (mod_name, entry) in &cfg.settings {
+      for (section, name, value) in entry.iter() {
+        let from = mod_settings.as_ref().and_then(|s| s.get(section, name)).cloned();
+        let to = value.to_property_tree(from.as_ref());
+        if from.as_ref() != Some(&to) {
+          changes.push(Change::Setting {
+            mod_name: mod_name.clone(),
+            section,
+            name: name.clone(),
+            from,
+            to,
+          });
+        }
+      }
+    }
+    changes.sort_by(|a, b| a.name().cmp(b.name()));
+
+    Ok(Self {
+      mod_folder,
+      factorio_version: cfg.metadata().factorio_version.clone(),
+      changes,
+    })
+  }
+
+  /// Returns true if nothing needs to change.
+  pub fn is_empty(&self) -> bool {
+    self.changes.is_empty()
+  }
+
+  /// Returns the releases the plan downloads.
+  pub fn releases(&self) -> Vec<FModRelease> {
+    self
+      .changes
+      .iter()
+      .filter_map(|change| match change {
+        Change::Install { release: to } | Change::Upgrade { to, .. } | Change::Downgrade { to, .. } => {
+          Some(to.release())
+        }
+        _ => None,
+      })
+      .collect()
+  }
+
+  /// Executes every change of the plan, then updates `mod-list.json` and `mod-settings.dat`.
+  ///
+  /// # Arguments
+  ///
+  /// * `ctx` - The context used to download mods, which must be authenticated.
+  pub async fn apply(&self, ctx: &Arc<Context>) -> Result<(), ConfigError> {
+    self.apply_with(&Downloader::new(ctx)).await
+  }
+
+  /// Executes every change of the plan, downloading the new releases concurrently.
+  ///
+  /// Nothing is removed from the mod folder unless every download succeeded.
+  ///
+  /// # Arguments
+  ///
+  /// * `downloader` - The downloader of the new releases, whose context must be authenticated.
+  #[instrument(skip_all)]
+  pub async fn apply_with(&self, downloader: &Downloader<'_>) -> Result<(), ConfigError> {
+    tokio::fs::create_dir_all(&self.mod_folder).await?;
+
+    for result in downloader.download(&self.releases(), &self.mod_folder).await {
+      result?;
+    }
+
+    for change in &self.changes {
+      info!("{}", change);
+      match change {
+        Change::Upgrade { from, .. } | Change::Downgrade { from, .. } => remove(from).await?,
+        Change::Remove { installed } => remove(installed).await?,
+        Change::Install { .. } | Change::Enable { .. } | Change::Disable { .. } | Change::Setting { .. } => (),
+      }
+    }
+
+    self.write_mod_list().await?;
+    self.write_mod_settings().await
+  }
+
+  /// Updates `mod-list.json` to match the plan and the mods left in the mod folder.
+  async fn write_mod_list(&self) -> Result<(), ConfigError> {
+    let mut mod_list = ModList::load(&self.mod_folder).await?;
+    mod_list.reconcile(&installed_mods(&self.mod_folder).await?);
+
+    for change in &self.changes {
+      match change {
+        Change::Install { .. } | Change::Enable { .. } => mod_list.set_enabled(change.name(), true),
+        Change::Disable { .. } => mod_list.set_enabled(change.name(), false),
+        _ => (),
+      }
+    }
+
+    mod_list.save().await
+  }
+
+  /// Writes the settings changed by the plan to `mod-settings.dat`, creating it if needed.
+  async fn write_mod_settings(&self) -> Result<(), ConfigError> {
+    let settings: Vec<_> = self
+      .changes
+      .iter()
+      .filter_map(|change| match change {
+        Change::Setting {
+          section, name, to, ..
+        } => Some((*section, name, to)),
+        _ => None,
+      })
+      .collect();
+    if settings.is_empty() {
+      return Ok(());
+    }
+
+    let mut mod_settings = match ModSettings::load(&self.mod_folder).await? {
+      Some(mod_settings) => mod_settings,
+      None => {
+        let version = self
+          .factorio_version
+          .as_ref()
+          .ok_or(ConfigError::MissingFactorioVersion)?;
+        ModSettings::new(ApplicationVersion::new(
+          version.major as u16,
+          version.minor as u16,
+          version.patch as u16,
+          0,
+        ))
+      }
+    };
+
+    for (section, name, value) in settings {
+      mod_settings.set(section, name, value.clone());
+    }
+    mod_settings.save(&self.mod_folder).await
+  }
+}
+
+impl Display for Plan {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.is_empty() {
+      return writeln!(f, "No changes, '{}' is up to date.", self.mod_folder.display());
+    }
+
+    let mut counts = [0; 7];
+    for change in &self.changes {
+      writeln!(f, "  {}", change)?;
+      counts[match change {
+        Change::Install { .. } => 0,
+        Change::Upgrade { .. } => 1,
+        Change::Downgrade { .. } => 2,
+        Change::Remove { .. } => 3,
+        Change::Enable { .. } => 4,
+        Change::Disable { .. } => 5,
+        Change::Setting { .. } => 6,
+      }] += 1;
+    }
+
+    writeln!(
+      f,
+      "\nPlan: {} to install, {} to upgrade, {} to downgrade, {} to remove, {} to enable, {} to disable, {} settings to change.",
+      counts[0], counts[1], counts[2], counts[3], counts[4], counts[5], counts[6]
+    )
+  }
+}
+
+/// Returns the version of an installed release, or None if Factorio could not read it either.
+fn version(installed: &InstalledMod) -> Option<FactorioVersion> {
+  installed.version.as_deref()?.parse().ok()
+}
+
+async fn remove(installed: &InstalledMod) -> Result<(), ConfigError> {
+  if installed.path.is_dir() {
+    tokio::fs::remove_dir_all(&installed.path).await?;
+  } else {
+    tokio::fs::remove_file(&installed.path).await?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::model::{mod_entry::ConfigModEntry, settings::SettingValue};
+  use furrctorio_core::prelude::FactorioVersionReq;
+  use std::path::Path;
+
+  fn locked(name: &str, version: &str) -> LockedMod {
+    LockedMod {
+      name: name.to_string(),
+      version: version.parse().unwrap(),
+      file_name: format!("{}_{}.zip", name, version),
+      download_url: format!("/download/{}/0123456789abcdef", name),
+      sha1: String::new(),
+      direct: true,
+    }
+  }
+
+  async fn mod_folder(test: &str, files: &[&str], mod_list: &str) -> PathBuf {
+    let folder = std::env::temp_dir().join(format!("furrctorio-plan-{}-{}", test, std::process::id()));
+    let _ = tokio::fs::remove_dir_all(&folder).await;
+    tokio::fs::create_dir_all(&folder).await.unwrap();
+
+    for file in files {
+      if file.ends_with(".zip") {
+        tokio::fs::write(folder.join(file), b"").await.unwrap();
+      } else {
+        tokio::fs::create_dir(folder.join(file)).await.unwrap();
+      }
+    }
+    tokio::fs::write(folder.join(ModList::FILE_NAME), mod_list).await.unwrap();
+    folder
+  }
+
+  fn config(folder: &Path, mods: Vec<ConfigModEntry>) -> FurrConfig {
+    let mut cfg = FurrConfig {
+      mods,
+      ..Default::default()
+    };
+    cfg.metadata_mut().factorio_mod_folder = Some(folder.to_path_buf());
+    cfg
+  }
+
+  #[test]
+  fn test_installed_version() {
+    let installed = |v: &str| InstalledMod {
+      name: "a".to_string(),
+      version: Some(v.to_string()),
+      path: PathBuf::from(format!("a_{}.zip", v)),
+    };
+    assert!(version(&installed("0.10.0")) > version(&installed("0.9.12")));
+    assert_eq!(version(&installed("0.18.05")), Some(FactorioVersion::new(0, 18, 5)));
+    assert_eq!(version(&installed("dev")), None);
+  }
+
+  #[tokio::test]
+  async fn test_plan() {
+    let folder = mod_folder(
+      "plan",
+      &[
+        "flib_0.12.8.zip",
+        "stdlib_1.0.8.zip",
+        "helmod_2.0.0.zip",
+        "helmod_1.9.0.zip",
+        "oldmod_0.1.0.zip",
+        "rso-mod_6.2.0.zip",
+      ],
+      r#"{"mods": [
+        {"name": "base", "enabled": true},
+        {"name": "stdlib", "enabled": false},
+        {"name": "rso-mod", "enabled": true}
+      ]}"#,
+    )
+    .await;
+
+    let cfg = config(
+      &folder,
+      vec![
+        ConfigModEntry::new("flib".to_string(), FactorioVersionReq::STAR, true),
+        ConfigModEntry::new("rso-mod".to_string(), FactorioVersionReq::STAR, false),
+      ],
+    );
+    let lock = LockFile {
+      version: Version::new(0, 1, 0),
+      factorio_version: None,
+      space_age: false,
+      mods: vec![
+        locked("flib", "0.12.9"),
+        locked("helmod", "1.9.5"),
+        locked("stdlib", "1.0.8"),
+        locked("yarm", "1.0.0"),
+      ],
+    };
+
+    let plan = Plan::new(&cfg, &lock).await.unwrap();
+    tokio::fs::remove_dir_all(&folder).await.unwrap();
+
+    let changes: Vec<String> = plan.changes.iter().map(|c| c.to_string()).collect();
+    assert_eq!(
+      changes,
+      vec![
+        "~ flib 0.12.8 -> 0.12.9",
+        "~ helmod 2.0.0 -> 1.9.5 (downgrade)",
+        "- helmod 1.9.0",
+        "- oldmod 0.1.0",
+        "* rso-mod disabled",
+        "* stdlib enabled",
+        "+ yarm 1.0.0",
+      ]
+    );
+    assert!(plan.to_string().contains(
+      "Plan: 1 to install, 1 to upgrade, 1 to downgrade, 2 to remove, 1 to enable, 1 to disable, 0 settings to change."
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_plan_builtin() {
+    let folder = mod_folder(
+      "builtin",
+      &[],
+      r#"{"mods": [
+        {"name": "base", "enabled": true},
+        {"name": "elevated-rails", "enabled": false},
+        {"name": "quality", "enabled": true}
+      ]}"#,
+    )
+    .await;
+
+    let builtin = |name: &str, enabled| {
+      ConfigModEntry::new(name.to_string(), FactorioVersionReq::STAR, enabled)
+    };
+    let mut cfg = config(
+      &folder,
+      vec![
+        builtin("elevated-rails", true),
+        builtin("quality", false),
+        builtin("space-age", true),
+      ],
+    );
+    cfg.metadata_mut().factorio_version = Some(Version::new(2, 0, 28));
+    let lock = LockFile {
+      version: Version::new(0, 1, 0),
+      factorio_version: cfg.metadata().factorio_version.clone(),
+      space_age: false,
+      mods: Vec::new(),
+    };
+
+    assert!(matches!(
+      Plan::new(&cfg, &lock).await,
+      Err(ConfigError::MissingDlc(name)) if name == "elevated-rails"
+    ));
+
+    cfg.metadata_mut().space_age = true;
+    let plan = Plan::new(&cfg, &lock).await.unwrap();
+    plan.apply(&Arc::new(Context::anonymous().unwrap())).await.unwrap();
+    let mod_list = ModList::load(&folder).await.unwrap();
+    tokio::fs::remove_dir_all(&folder).await.unwrap();
+
+    let changes: Vec<String> = plan.changes.iter().map(|c| c.to_string()).collect();
+    assert_eq!(changes, vec!["* elevated-rails enabled", "* quality disabled"]);
+    let entries: Vec<(&str, bool)> = mod_list.mods.iter().map(|m| (m.name.as_str(), m.enabled)).collect();
+    assert_eq!(
+      entries,
+      vec![("base", true), ("elevated-rails", true), ("quality", false)]
+    );
+  }
+
+  #[tokio::test]
+  async fn test_apply_without_downloads() {
+    let folder = mod_folder(
+      "apply",
+      &["stdlib_1.0.8.zip", "oldmod_0.1.0.zip"],
+      r#"{"mods": [
+        {"name": "base", "enabled": true},
+        {"name": "stdlib", "enabled": false},
+        {"name": "oldmod", "enabled": true}
+      ]}"#,
+    )
+    .await;
+
+    let cfg = config(&folder, vec![ConfigModEntry::new("stdlib".to_string(), FactorioVersionReq::STAR, true)]);
+    let lock = LockFile {
+      version: Version::new(0, 1, 0),
+      factorio_version: None,
+      space_age: false,
+      mods: vec![locked("stdlib", "1.0.8")],
+    };
+
+    let plan = Plan::new(&cfg, &lock).await.unwrap();
+    plan.apply(&Arc::new(Context::anonymous().unwrap())).await.unwrap();
+
+    assert!(Plan::new(&cfg, &lock).await.unwrap().is_empty());
+    assert!(!folder.join("oldmod_0.1.0.zip").exists());
+    let mod_list = ModList::load(&folder).await.unwrap();
+    tokio::fs::remove_dir_all(&folder).await.unwrap();
+
+    let entries: Vec<(&str, bool)> = mod_list.mods.iter().map(|m| (m.name.as_str(), m.enabled)).collect();
+    assert_eq!(entries, vec![("base", true), ("stdlib", true)]);
+  }
+
+  #[tokio::test]
+  async fn test_apply_settings() {
+    let folder = mod_folder("settings", &[], r#"{"mods": [{"name": "base", "enabled": true}]}"#).await;
+
+    let mut cfg = config(&folder, Vec::new());
+    let entry = cfg.settings.entry("rso-mod".to_string()).or_default();
+    entry.startup.insert("rso-regions".to_string(), SettingValue::Int(5));
+    entry
+      .runtime_global
+      .insert("rso-vanilla".to_string(), SettingValue::Bool(false));
+    let lock = LockFile {
+      version: Version::new(0, 1, 0),
+      factorio_version: None,
+      space_age: false,
+      mods: Vec::new(),
+    };
+
+    let plan = Plan::new(&cfg, &lock).await.unwrap();
+    assert_eq!(plan.changes.len(), 2);
+    assert_eq!(plan.changes[0].to_string(), "= rso-regions (startup): 5");
+    assert!(matches!(
+      plan.apply(&Arc::new(Context::anonymous().unwrap())).await,
+      Err(ConfigError::MissingFactorioVersion)
+    ));
+
+    cfg.metadata_mut().factorio_version = Some(Version::new(1, 1, 110));
+    let plan = Plan::new(&cfg, &lock).await.unwrap();
+    plan.apply(&Arc::new(Context::anonymous().unwrap())).await.unwrap();
+    assert!(Plan::new(&cfg, &lock).await.unwrap().is_empty());
+
+    cfg.settings.get_mut("rso-mod").unwrap().startup.insert("rso-regions".to_string(), SettingValue::Int(7));
+    let plan = Plan::new(&cfg, &lock).await.unwrap();
+    assert_eq!(plan.changes[0].to_string(), "= rso-regions (startup): 5 -> 7");
+    plan.apply(&Arc::new(Context::anonymous().unwrap())).await.unwrap();
+
+    let mod_settings = ModSettings::load(&folder).await.unwrap().unwrap();
+    tokio::fs::remove_dir_all(&folder).await.unwrap();
+
+    assert_eq!(mod_settings.version, ApplicationVersion::new(1, 1, 110, 0));
+    assert_eq!(
+      mod_settings.get(SettingsSection::Startup, "rso-regions"),
+      Some(&PropertyTree::Number(7.0))
+    );
+    assert_eq!(
+      mod_settings.get(SettingsSection::RuntimeGlobal, "rso-vanilla"),
+      Some(&PropertyTree::Bool(false))
+    );
+  }
+}
@@ -0,0 +1,11 @@
+pub use crate::{
+  error::ConfigError,
+  model::{
+    config::{FurrConfig, Metadata},
+    lockfile::{LockFile, LockedMod},
+    mod_entry::ConfigModEntry,
+    settings::{Color, ModSettingsEntry, SettingValue},
+  },
+  plan::{Change, Plan},
+  sync::{SaveSync, SyncChange},
+};
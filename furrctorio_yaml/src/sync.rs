@@ -0,0 +1,350 @@
+//! Makes a configuration match the mods a save was made with.
+//!
+//! Like the "sync mods with save" button of the game, a [`SaveSync`] enables every mod of
+//! the save in its exact version and disables the other mods. The mods of the Space Age
+//! expansion are enabled or disabled like the save, and mark the expansion as owned. It can be
+//! reviewed before being applied with [`SaveSync::apply`].
+
+use crate::{
+  error::ConfigError,
+  model::{config::FurrConfig, mod_entry::ConfigModEntry},
+};
+use furrctorio_core::{
+  constants::{BUILTIN_MODS, SPACE_AGE_MODS},
+  error::Error,
+  prelude::{FactorioVersion, FactorioVersionReq, SaveHeader, VersionOp},
+};
+use semver::Version;
+use std::fmt::Display;
+
+/// A single change to a configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncChange {
+  /// Sets the Factorio version to the one that wrote the save.
+  FactorioVersion { from: Option<Version>, to: Version },
+  /// Marks the Space Age expansion as owned, as the save uses its mods.
+  SpaceAge,
+  /// Adds a mod of the save that is not in the configuration.
+  Add { name: String, version: FactorioVersionReq },
+  /// Pins a mod of the configuration to the version of the save.
+  Pin {
+    name: String,
+    from: FactorioVersionReq,
+    to: FactorioVersionReq,
+  },
+  /// Enables a mod of the save that is disabled in the configuration.
+  Enable { name: String },
+  /// Disables a mod that is not in the save, adding an entry for a built-in mod without one.
+  Disable { name: String },
+}
+
+impl Display for SyncChange {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      SyncChange::FactorioVersion { from: Some(from), to } => {
+        write!(f, "= factorio {} -> {}", from, to)
+      }
+      SyncChange::FactorioVersion { from: None, to } => write!(f, "= factorio {}", to),
+      SyncChange::SpaceAge => write!(f, "= space age owned"),
+      SyncChange::Add { name, version } => write!(f, "+ {} {}", name, version),
+      SyncChange::Pin { name, from, to } => write!(f, "~ {} {} -> {}", name, from, to),
+      SyncChange::Enable { name } => write!(f, "* {} enabled", name),
+      SyncChange::Disable { name } => write!(f, "* {} disabled", name),
+    }
+  }
+}
+
+/// The changes needed to make a configuration match a save.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveSync {
+  /// The changes, in the order of the save followed by the disabled mods.
+  pub changes: Vec<SyncChange>,
+}
+
+impl SaveSync {
+  /// Computes the changes needed to make a configuration load the mods of a save.
+  ///
+  /// The base mod comes with the game, so it is left out of the configuration. The mods of
+  /// the Space Age expansion are added when the save uses them, and disabled otherwise.
+  ///
+  /// # Arguments
+  ///
+  /// * `cfg` - The configuration to compare with the save.
+  /// * `save` - The header of the save, see [`SaveHeader::from_zip`].
+  ///
+  /// # Returns
+  ///
+  /// * `Result<Self, ConfigError>` - Returns the changes or a ConfigError if a version of the save is invalid.
+  pub fn new(cfg: &FurrConfig, save: &SaveHeader) -> Result<Self, ConfigError> {
+    let mut changes = Vec::new();
+
+    let version = Version::new(
+      save.version.major as u64,
+      save.version.minor as u64,
+      save.version.patch as u64,
+    );
+    if cfg.metadata().factorio_version.as_ref() != Some(&version) {
+      changes.push(SyncChange::FactorioVersion {
+        from: cfg.metadata().factorio_version.clone(),
+        to: version,
+      });
+    }
+
+    let space_age = save.mods.iter().any(|m| SPACE_AGE_MODS.contains(&m.name.as_str()));
+    if space_age && !cfg.metadata().space_age {
+      changes.push(SyncChange::SpaceAge);
+    }
+
+    for saved in &save.mods {
+      if BUILTIN_MODS.contains(&saved.name.as_str()) {
+        // Built-in mods come in the version of the game, they are only enabled.
+        match cfg.get_mod(&saved.name) {
+          Some(entry) if !entry.enabled => changes.push(SyncChange::Enable {
+            name: saved.name.clone(),
+          }),
+          None if SPACE_AGE_MODS.contains(&saved.name.as_str()) => changes.push(SyncChange::Add {
+            name: saved.name.clone(),
+            version: FactorioVersionReq::STAR,
+          }),
+          _ => (),
+        }
+        continue;
+      }
+
+      let version = saved
+        .version
+        .parse::<FactorioVersion>()
+        .map_err(|e| Error::ParcingError(format!("Invalid version of '{}': {}", saved.name, e)))?;
+      let version = FactorioVersionReq::new(VersionOp::Exact, version);
+
+      match cfg.get_mod(&saved.name) {
+        None => changes.push(SyncChange::Add {
+          name: saved.name.clone(),
+          version,
+        }),
+        Some(entry) => {
+          if entry.version != version {
+            changes.push(SyncChange::Pin {
+              name: saved.name.clone(),
+              from: entry.version.clone(),
+              to: version,
+            });
+          }
+          if !entry.enabled {
+            changes.push(SyncChange::Enable {
+              name: saved.name.clone(),
+            });
+          }
+        }
+      }
+    }
+
+    let in_save = |name: &str| save.mods.iter().any(|m| m.name == name);
+    for entry in cfg.mods.iter().filter(|m| m.enabled) {
+      if !in_save(&entry.name) {
+        changes.push(SyncChange::Disable {
+          name: entry.name.clone(),
+        });
+      }
+    }
+
+    // Like Factorio, the expansion mods without an entry count as enabled.
+    if save.version.major >= 2 && (space_age || cfg.metadata().space_age) {
+      for name in SPACE_AGE_MODS {
+        if !in_save(name) && cfg.get_mod(name).is_none() {
+          changes.push(SyncChange::Disable {
+            name: name.to_string(),
+          });
+        }
+      }
+    }
+
+    Ok(Self { changes })
+  }
+
+  /// Returns true if the configuration already matches the save.
+  pub fn is_empty(&self) -> bool {
+    self.changes.is_empty()
+  }
+
+  /// Applies every change to a configuration.
+  pub fn apply(&self, cfg: &mut FurrConfig) {
+    for change in &self.changes {
+      match change {
+        SyncChange::FactorioVersion { to, .. } => {
+          cfg.metadata_mut().factorio_version = Some(to.clone())
+        }
+        SyncChange::SpaceAge => cfg.metadata_mut().space_age = true,
+        SyncChange::Add { name, version } => {
+          cfg.mods.push(ConfigModEntry::new(name.clone(), version.clone(), true))
+        }
+        SyncChange::Pin { name, to, .. } => {
+          if let Some(entry) = cfg.get_mod_mut(name) {
+            entry.version = to.clone();
+          }
+        }
+        SyncChange::Enable { name } | SyncChange::Disable { name } => {
+          let enabled = matches!(change, SyncChange::Enable { .. });
+          match cfg.get_mod_mut(name) {
+            Some(entry) => entry.enabled = enabled,
+            None => cfg.mods.push(ConfigModEntry::new(
+              name.clone(),
+              FactorioVersionReq::STAR,
+              enabled,
+            )),
+          }
+        }
+      }
+    }
+  }
+}
+
+impl Display for SaveSync {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.is_empty() {
+      return writeln!(f, "No changes, the configuration matches the save.");
+    }
+
+    for change in &self.changes {
+      writeln!(f, "  {}", change)?;
+    }
+    writeln!(f, "\nSync: {} changes.", self.changes.len())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use furrctorio_core::prelude::{ApplicationVersion, SavedMod};
+
+  fn header(version: ApplicationVersion, mods: &[(&str, &str)]) -> SaveHeader {
+    SaveHeader {
+      version,
+      campaign: String::new(),
+      level_name: "freeplay".to_string(),
+      base_mod: "base".to_string(),
+      mods: mods
+        .iter()
+        .map(|(name, version)| SavedMod {
+          name: name.to_string(),
+          version: version.to_string(),
+          crc: 0,
+        })
+        .collect(),
+    }
+  }
+
+  fn save() -> SaveHeader {
+    header(
+      ApplicationVersion::new(1, 1, 110, 0),
+      &[("base", "1.1.110"), ("flib", "0.12.9"), ("stdlib", "1.0.8"), ("yarm", "1.0.0")],
+    )
+  }
+
+  /// A 2.0 save, with the mods of the expansion if `space_age` is true.
+  fn save_2_0(space_age: bool) -> SaveHeader {
+    let mut mods = vec![("base", "2.0.28")];
+    if space_age {
+      mods.extend([("elevated-rails", "2.0.28"), ("quality", "2.0.28"), ("space-age", "2.0.28")]);
+    }
+    mods.push(("flib", "0.15.0"));
+    header(ApplicationVersion::new(2, 0, 28, 0), &mods)
+  }
+
+  #[test]
+  fn test_sync() {
+    let mut cfg = FurrConfig {
+      mods: vec![
+        ConfigModEntry::new("flib".to_string(), ">=0.12".parse().unwrap(), true),
+        ConfigModEntry::new("stdlib".to_string(), "=1.0.8".parse().unwrap(), false),
+        ConfigModEntry::new("helmod".to_string(), FactorioVersionReq::STAR, true),
+        ConfigModEntry::new("rso-mod".to_string(), FactorioVersionReq::STAR, false),
+      ],
+      ..Default::default()
+    };
+
+    let sync = SaveSync::new(&cfg, &save()).unwrap();
+    let changes: Vec<String> = sync.changes.iter().map(|c| c.to_string()).collect();
+    assert_eq!(
+      changes,
+      vec![
+        "= factorio 1.1.110",
+        "~ flib >=0.12 -> = 0.12.9",
+        "* stdlib enabled",
+        "+ yarm = 1.0.0",
+        "* helmod disabled",
+      ]
+    );
+
+    sync.apply(&mut cfg);
+    assert!(SaveSync::new(&cfg, &save()).unwrap().is_empty());
+    assert!(!cfg.get_mod("helmod").unwrap().enabled);
+    assert!(!cfg.get_mod("rso-mod").unwrap().enabled);
+
+    // The expansion is used by the save.
+    let mut cfg = FurrConfig {
+      mods: vec![
+        ConfigModEntry::new("quality".to_string(), FactorioVersionReq::STAR, false),
+        ConfigModEntry::new("flib".to_string(), "=0.15.0".parse().unwrap(), true),
+      ],
+      ..Default::default()
+    };
+    cfg.metadata_mut().factorio_version = Some(Version::new(2, 0, 28));
+
+    let sync = SaveSync::new(&cfg, &save_2_0(true)).unwrap();
+    let changes: Vec<String> = sync.changes.iter().map(|c| c.to_string()).collect();
+    assert_eq!(
+      changes,
+      vec!["= space age owned", "+ elevated-rails *", "* quality enabled", "+ space-age *"]
+    );
+
+    sync.apply(&mut cfg);
+    assert!(cfg.metadata().space_age);
+    assert!(cfg.check_builtin_mods().is_ok());
+    assert!(SaveSync::new(&cfg, &save_2_0(true)).unwrap().is_empty());
+
+    // The expansion is owned but not used by the save.
+    let sync = SaveSync::new(&cfg, &save_2_0(false)).unwrap();
+    let changes: Vec<String> = sync.changes.iter().map(|c| c.to_string()).collect();
+    assert_eq!(
+      changes,
+      vec!["* quality disabled", "* elevated-rails disabled", "* space-age disabled"]
+    );
+
+    let mut cfg = FurrConfig {
+      mods: vec![ConfigModEntry::new("flib".to_string(), "=0.15.0".parse().unwrap(), true)],
+      ..Default::default()
+    };
+    cfg.metadata_mut().factorio_version = Some(Version::new(2, 0, 28));
+    cfg.metadata_mut().space_age = true;
+    let sync = SaveSync::new(&cfg, &save_2_0(false)).unwrap();
+    let changes: Vec<String> = sync.changes.iter().map(|c| c.to_string()).collect();
+    assert_eq!(
+      changes,
+      vec!["* elevated-rails disabled", "* quality disabled", "* space-age disabled"]
+    );
+
+    sync.apply(&mut cfg);
+    assert!(cfg.mods.iter().filter(|m| m.name != "flib").all(|m| !m.enabled));
+    assert!(SaveSync::new(&cfg, &save_2_0(false)).unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_from_save() {
+    let cfg = FurrConfig::from_save(&save()).unwrap();
+
+    assert_eq!(cfg.metadata().factorio_version, Some(Version::new(1, 1, 110)));
+    let names: Vec<&str> = cfg.mods.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, vec!["flib", "stdlib", "yarm"]);
+    assert!(cfg.mods.iter().all(|m| m.enabled));
+    assert!(!cfg.metadata().space_age);
+
+    let cfg = FurrConfig::from_save(&save_2_0(true)).unwrap();
+    assert_eq!(cfg.metadata().factorio_version, Some(Version::new(2, 0, 28)));
+    assert!(cfg.metadata().space_age);
+    let names: Vec<&str> = cfg.mods.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, vec!["elevated-rails", "quality", "space-age", "flib"]);
+    assert!(cfg.mods.iter().all(|m| m.enabled));
+    assert!(cfg.check_builtin_mods().is_ok());
+  }
+}